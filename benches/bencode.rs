@@ -0,0 +1,192 @@
+//! Rough timings of the bencode decoders on generated inputs, run with
+//! `cargo test --release --bench bencode -- --nocapture`
+//!
+//! The fixtures are generated here so no large binaries have to be committed. The
+//! timings are plain `Instant`s since there is no benchmark crate (Cargo.toml is
+//! managed by CodeCrafters and can't change). For the same reason the target is built
+//! with the default test harness, which runs [`bencode_decoders`]: with
+//! `[[bench]] name = "bencode"` and `harness = false` it would be a plain binary that
+//! starts at `main` and takes the number of iterations (`cargo bench --bench bencode -- 20`).
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bittorrent_starter_rust::bencode::{
+    borrowed, decode_bencoded_value, encode_bencoded_value, events, DecodeOptions, Integer, Value,
+    DEFAULT_MAX_DEPTH,
+};
+
+const DEFAULT_ITERATIONS: u32 = 20;
+
+fn bytes(s: &str) -> Value {
+    Value::Bytes(s.as_bytes().to_vec())
+}
+
+fn int(i: i64) -> Value {
+    Value::Int(Integer::I64(i))
+}
+
+/// Deterministic filler for the pieces blob (xorshift)
+fn pseudo_random_bytes(length: usize) -> Vec<u8> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    (0..length)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn torrent(info: Vec<(Vec<u8>, Value)>) -> Vec<u8> {
+    encode_bencoded_value(&Value::Dict(vec![
+        (
+            b"announce".to_vec(),
+            bytes("http://tracker.example/announce"),
+        ),
+        (b"info".to_vec(), Value::Dict(info)),
+    ]))
+}
+
+/// Single file, a handful of pieces
+fn small_torrent() -> Vec<u8> {
+    torrent(vec![
+        (b"length".to_vec(), int(92063)),
+        (b"name".to_vec(), bytes("sample.txt")),
+        (b"piece length".to_vec(), int(32768)),
+        (
+            b"pieces".to_vec(),
+            Value::Bytes(pseudo_random_bytes(3 * 20)),
+        ),
+    ])
+}
+
+/// 2,000 files and a 4 MiB pieces blob
+fn large_torrent() -> Vec<u8> {
+    let files = (0..2000)
+        .map(|i| {
+            Value::Dict(vec![
+                (b"length".to_vec(), int(1_000_000 + i)),
+                (
+                    b"path".to_vec(),
+                    Value::List(vec![bytes("dir"), bytes(&format!("file-{}.bin", i))]),
+                ),
+            ])
+        })
+        .collect();
+
+    torrent(vec![
+        (b"files".to_vec(), Value::List(files)),
+        (b"name".to_vec(), bytes("large")),
+        (b"piece length".to_vec(), int(262144)),
+        (
+            b"pieces".to_vec(),
+            Value::Bytes(pseudo_random_bytes(4 << 20)),
+        ),
+    ])
+}
+
+/// Lists nested just below the depth limit
+fn deeply_nested() -> Vec<u8> {
+    let depth = DEFAULT_MAX_DEPTH - 1;
+    let mut encoded = vec![b'l'; depth];
+    encoded.extend_from_slice(b"i1e");
+    encoded.resize(encoded.len() + depth, b'e');
+    encoded
+}
+
+fn many_integers() -> Vec<u8> {
+    encode_bencoded_value(&Value::List((0..100_000).map(|i| int(i % 1000)).collect()))
+}
+
+/// Decoder name along with a closure that decodes the fixture once
+type NamedRun<'a> = (&'static str, Box<dyn Fn() + 'a>);
+
+fn time(iterations: u32, mut run: impl FnMut()) -> Duration {
+    // once to warm up the caches and the allocator
+    run();
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        run();
+    }
+    start.elapsed() / iterations
+}
+
+/// Entry point with `harness = false`
+#[cfg(not(test))]
+fn main() {
+    let iterations = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("iterations should be a number"))
+        .unwrap_or(DEFAULT_ITERATIONS);
+    run(iterations);
+}
+
+/// Entry point with the default test harness, which takes no arguments of ours
+#[test]
+fn bencode_decoders() {
+    run(DEFAULT_ITERATIONS);
+}
+
+fn run(iterations: u32) {
+    let fixtures = [
+        ("small torrent", small_torrent()),
+        ("2000 file torrent", large_torrent()),
+        ("deeply nested", deeply_nested()),
+        ("100k integers", many_integers()),
+    ];
+    let options = DecodeOptions::default();
+
+    println!(
+        "{:<20} {:>10}  {:<16} {:>12} {:>10}",
+        "fixture", "bytes", "decoder", "per run", "MiB/s"
+    );
+    for (name, encoded) in &fixtures {
+        let decoders: [NamedRun; 4] = [
+            (
+                "owned",
+                Box::new(|| {
+                    black_box(decode_bencoded_value(encoded, &options).unwrap());
+                }),
+            ),
+            (
+                "borrowed",
+                Box::new(|| {
+                    black_box(borrowed::parse(encoded, &options).unwrap());
+                }),
+            ),
+            (
+                "events",
+                Box::new(|| {
+                    black_box(events::parse_events(encoded, &options, |event| {
+                        black_box(event);
+                    }))
+                    .unwrap();
+                }),
+            ),
+            (
+                "serde_bencode",
+                Box::new(|| {
+                    black_box(
+                        serde_bencode::from_bytes::<serde_bencode::value::Value>(encoded).unwrap(),
+                    );
+                }),
+            ),
+        ];
+
+        for (decoder, run) in &decoders {
+            let elapsed = time(iterations, run);
+            let throughput = encoded.len() as f64 / elapsed.as_secs_f64() / (1 << 20) as f64;
+            println!(
+                "{:<20} {:>10}  {:<16} {:>12?} {:>10.1}",
+                name,
+                encoded.len(),
+                decoder,
+                elapsed,
+                throughput
+            );
+        }
+    }
+}
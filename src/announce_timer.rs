@@ -0,0 +1,118 @@
+//! When to announce again: after the tracker's "interval" with some jitter, so clients
+//! that started together don't all come back together, and sooner when the peers run
+//! low, but never before its "min interval". The caller gives the time, a test can
+//! drive the timer with instants of its own
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::random;
+
+/// Floor of the early announces for trackers that don't send a "min interval"
+pub const DEFAULT_MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Jitter is at most this fraction of the interval (a tenth)
+const JITTER_DIVISOR: u32 = 10;
+
+/// When the last announce was and when the next one is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnounceTimer {
+    last: Instant,
+    interval: Duration,
+    min_interval: Duration,
+    jitter: Duration,
+}
+
+impl AnnounceTimer {
+    /// A timer for an announce at `now` answered with `interval` and maybe a
+    /// `min_interval`, the next regular announce is `jitter` past the interval
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    ///
+    /// use bittorrent_starter_rust::announce_timer::AnnounceTimer;
+    ///
+    /// let start = Instant::now();
+    /// let minutes = |count: u64| Duration::from_secs(count * 60);
+    /// let timer = AnnounceTimer::new(start, minutes(30), Some(minutes(5)), Duration::from_secs(7));
+    /// assert_eq!(timer.next_announce(false), start + minutes(30) + Duration::from_secs(7));
+    /// // the peers ran low, still not before the min interval
+    /// assert_eq!(timer.next_announce(true), start + minutes(5));
+    ///
+    /// assert!(!timer.is_due(start + minutes(4), true));
+    /// assert!(timer.is_due(start + minutes(5), true));
+    /// assert!(!timer.is_due(start + minutes(30), false));
+    /// assert!(timer.is_due(start + minutes(31), false));
+    ///
+    /// // a min interval above the interval wins over it
+    /// let timer = AnnounceTimer::new(start, minutes(1), Some(minutes(2)), Duration::ZERO);
+    /// assert_eq!(timer.next_announce(false), start + minutes(2));
+    /// // without one the default floor applies, capped at the interval
+    /// let timer = AnnounceTimer::new(start, Duration::from_secs(20), None, Duration::ZERO);
+    /// assert_eq!(timer.next_announce(true), start + Duration::from_secs(20));
+    /// ```
+    pub fn new(
+        now: Instant,
+        interval: Duration,
+        min_interval: Option<Duration>,
+        jitter: Duration,
+    ) -> Self {
+        let min_interval = min_interval.unwrap_or(DEFAULT_MIN_INTERVAL.min(interval));
+        Self {
+            last: now,
+            interval,
+            min_interval,
+            jitter,
+        }
+    }
+
+    /// When to announce again, at the min interval when `peers_low`
+    pub fn next_announce(&self, peers_low: bool) -> Instant {
+        let earliest = self.last + self.min_interval;
+        match peers_low {
+            true => earliest,
+            false => (self.last + self.interval + self.jitter).max(earliest),
+        }
+    }
+
+    pub fn is_due(&self, now: Instant, peers_low: bool) -> bool {
+        now >= self.next_announce(peers_low)
+    }
+
+    /// Restarts the timer for an announce at `now` that failed, the tracker's last
+    /// intervals are waited again before the next try
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    ///
+    /// use bittorrent_starter_rust::announce_timer::AnnounceTimer;
+    ///
+    /// let start = Instant::now();
+    /// let mut timer = AnnounceTimer::new(start, Duration::from_secs(60), None, Duration::ZERO);
+    /// timer.restart(start + Duration::from_secs(60));
+    /// assert_eq!(timer.next_announce(false), start + Duration::from_secs(120));
+    /// ```
+    pub fn restart(&mut self, now: Instant) {
+        self.last = now;
+    }
+}
+
+/// A random wait of up to a tenth of `interval` from the system's random source
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use bittorrent_starter_rust::announce_timer::random_jitter;
+///
+/// let interval = Duration::from_secs(1800);
+/// assert!(random_jitter(interval).unwrap() < Duration::from_secs(180));
+/// assert_eq!(random_jitter(Duration::ZERO).unwrap(), Duration::ZERO);
+/// ```
+pub fn random_jitter(interval: Duration) -> io::Result<Duration> {
+    let random = random::random_u64()?;
+    let most = (interval / JITTER_DIVISOR).as_millis() as u64;
+    Ok(Duration::from_millis(match most {
+        0 => 0,
+        most => random % most,
+    }))
+}
@@ -0,0 +1,374 @@
+use std::ops::Range;
+
+use super::events::{parse_events, parse_events_at, Event, EventKind};
+use super::{BencodeError, DecodeOptions, DecodeWarning, Integer};
+
+/// Decoded bencode value that borrows its byte strings from the input buffer,
+/// only lists and dictionaries allocate (for their entries)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value<'a> {
+    Bytes(&'a [u8]),
+    Int(Integer),
+    List(Vec<Value<'a>>),
+    /// entries in the order they appear in the encoded value
+    Dict(Vec<(&'a [u8], Value<'a>)>),
+}
+
+impl<'a> Value<'a> {
+    /// Looks up a dictionary entry, the last one wins for duplicate keys
+    pub fn get(&self, key: &[u8]) -> Option<&Value<'a>> {
+        match self {
+            Self::Dict(d) => d.iter().rev().find(|(k, _)| *k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// The byte string, still borrowed from the input
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            Self::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        self.as_bytes().and_then(|b| std::str::from_utf8(b).ok())
+    }
+
+    pub fn as_int(&self) -> Option<&Integer> {
+        match self {
+            Self::Int(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// Copies the borrowed byte strings into an owned [`super::Value`]
+    pub fn to_owned_value(&self) -> super::Value {
+        super::Value::from(self.clone())
+    }
+}
+
+impl From<Value<'_>> for super::Value {
+    fn from(value: Value<'_>) -> Self {
+        match value {
+            Value::Bytes(b) => Self::Bytes(b.to_vec()),
+            Value::Int(i) => Self::Int(i),
+            Value::List(l) => Self::List(l.into_iter().map(super::Value::from).collect()),
+            Value::Dict(d) => Self::Dict(
+                d.into_iter()
+                    .map(|(key, value)| (key.to_vec(), super::Value::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Parses the bencoded value at the start of `encoded` as `options` allow,
+/// returns the value along with the number of bytes consumed
+pub fn parse<'a>(
+    encoded: &'a [u8],
+    options: &DecodeOptions,
+) -> Result<(Value<'a>, usize), BencodeError> {
+    build(encoded, options, value_node)
+}
+
+/// [`parse`] for the rest of an input from its byte `base` on, see
+/// [`parse_events_at`], the duplicate keys it accepted go to `warnings`
+pub(super) fn parse_at<'a>(
+    encoded: &'a [u8],
+    base: usize,
+    options: &DecodeOptions,
+    warnings: &mut Vec<DecodeWarning>,
+) -> Result<(Value<'a>, usize), BencodeError> {
+    let mut builder = Builder::new(value_node);
+    let consumed = parse_events_at(
+        encoded,
+        base,
+        options,
+        |event| builder.push_event(event),
+        Some(warnings),
+    )?;
+
+    Ok((
+        builder.root.expect("events ended without a value"),
+        consumed,
+    ))
+}
+
+fn value_node<'a>(node: Node<'a, Value<'a>>, _: Range<usize>) -> Value<'a> {
+    match node {
+        Node::Bytes(b) => Value::Bytes(b),
+        Node::Int(i) => Value::Int(i),
+        Node::List(l) => Value::List(l),
+        Node::Dict(d) => Value::Dict(d),
+    }
+}
+
+/// Value that remembers where it was in the input, so the original bytes of any
+/// element (like the "info" dictionary of a torrent) can be used without re-encoding
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<'a> {
+    /// offset of the first byte of the element in the input
+    pub offset: usize,
+    /// the element exactly as it was encoded
+    pub raw: &'a [u8],
+    pub value: SpannedValue<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpannedValue<'a> {
+    Bytes(&'a [u8]),
+    Int(Integer),
+    List(Vec<Spanned<'a>>),
+    /// entries in the order they appear in the encoded value
+    Dict(Vec<(&'a [u8], Spanned<'a>)>),
+}
+
+impl<'a> Spanned<'a> {
+    /// Byte range of the element in the input
+    pub fn span(&self) -> Range<usize> {
+        self.offset..self.offset + self.raw.len()
+    }
+
+    /// Looks up a dictionary entry, the last one wins for duplicate keys
+    pub fn get(&self, key: &[u8]) -> Option<&Spanned<'a>> {
+        match &self.value {
+            SpannedValue::Dict(d) => d.iter().rev().find(|(k, _)| *k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+/// Parses the bencoded value at the start of `encoded` keeping the span of every element,
+/// returns the value along with the number of bytes consumed
+pub fn parse_spanned<'a>(
+    encoded: &'a [u8],
+    options: &DecodeOptions,
+) -> Result<(Spanned<'a>, usize), BencodeError> {
+    build(encoded, options, |node, span| Spanned {
+        offset: span.start,
+        raw: &encoded[span],
+        value: match node {
+            Node::Bytes(b) => SpannedValue::Bytes(b),
+            Node::Int(i) => SpannedValue::Int(i),
+            Node::List(l) => SpannedValue::List(l),
+            Node::Dict(d) => SpannedValue::Dict(d),
+        },
+    })
+}
+
+/// Finished element handed to the `make` function of [`build`]
+enum Node<'a, T> {
+    Bytes(&'a [u8]),
+    Int(Integer),
+    List(Vec<T>),
+    Dict(Vec<(&'a [u8], T)>),
+}
+
+/// List or dictionary whose end hasn't been reached yet, along with its offset
+enum Open<'a, T> {
+    List(usize, Vec<T>),
+    /// the key waiting for its value is kept until the value is finished
+    Dict(usize, Vec<(&'a [u8], T)>, Option<&'a [u8]>),
+}
+
+/// Builds a tree out of the events of [`parse_events`], `make` turns each finished
+/// element and its byte range into a node of the tree
+struct Builder<'a, T, F> {
+    open: Vec<Open<'a, T>>,
+    root: Option<T>,
+    make: F,
+}
+
+impl<'a, T, F: FnMut(Node<'a, T>, Range<usize>) -> T> Builder<'a, T, F> {
+    fn new(make: F) -> Self {
+        Self {
+            open: Vec::new(),
+            root: None,
+            make,
+        }
+    }
+
+    fn push_event(&mut self, event: Event<'a>) {
+        let end = event.offset + event.length;
+        let finished = match event.kind {
+            EventKind::DictStart => {
+                return self.open.push(Open::Dict(event.offset, Vec::new(), None))
+            }
+            EventKind::ListStart => return self.open.push(Open::List(event.offset, Vec::new())),
+            EventKind::DictKey(key) => {
+                if let Some(Open::Dict(_, _, pending_key)) = self.open.last_mut() {
+                    *pending_key = Some(key);
+                }
+                return;
+            }
+            EventKind::Bytes(b) => (self.make)(Node::Bytes(b), event.offset..end),
+            EventKind::Int(i) => (self.make)(Node::Int(i), event.offset..end),
+            EventKind::End => match self.open.pop() {
+                Some(container) => self.close(container, end),
+                None => unreachable!("end event outside of a container"),
+            },
+        };
+        self.attach(finished);
+    }
+
+    fn close(&mut self, container: Open<'a, T>, end: usize) -> T {
+        match container {
+            Open::List(offset, values) => (self.make)(Node::List(values), offset..end),
+            Open::Dict(offset, entries, _) => (self.make)(Node::Dict(entries), offset..end),
+        }
+    }
+
+    /// Adds a finished element to the innermost open container
+    fn attach(&mut self, finished: T) {
+        match self.open.last_mut() {
+            Some(Open::List(_, values)) => values.push(finished),
+            Some(Open::Dict(_, entries, pending_key)) => {
+                let key = pending_key.take().expect("dictionary value without a key");
+                entries.push((key, finished));
+            }
+            None => self.root = Some(finished),
+        }
+    }
+
+    /// What the next element should have been
+    fn expected(&self) -> &'static str {
+        match self.open.last() {
+            Some(Open::List(..)) => "a list item or 'e'",
+            Some(Open::Dict(_, _, Some(_))) => "the value of a dictionary entry",
+            Some(Open::Dict(_, _, None)) => "a dictionary key or 'e'",
+            None if self.root.is_some() => "the end of input",
+            None => "a value",
+        }
+    }
+
+    /// Closes the containers that are still open, a key without its value is dropped
+    fn finish_partial(mut self, end: usize) -> Option<T> {
+        while let Some(container) = self.open.pop() {
+            let finished = self.close(container, end);
+            self.attach(finished);
+        }
+        self.root
+    }
+}
+
+fn build<'a, T>(
+    encoded: &'a [u8],
+    options: &DecodeOptions,
+    make: impl FnMut(Node<'a, T>, Range<usize>) -> T,
+) -> Result<(T, usize), BencodeError> {
+    let mut builder = Builder::new(make);
+    let consumed = parse_events(encoded, options, |event| builder.push_event(event))?;
+
+    Ok((
+        builder.root.expect("events ended without a value"),
+        consumed,
+    ))
+}
+
+/// Value decoded before [`parse_partial`] failed
+#[derive(Debug)]
+pub struct Partial<'a> {
+    /// everything before the error, with the lists and dictionaries that were
+    /// still open closed off (`None` when the error is at the very first value)
+    pub value: Option<Value<'a>>,
+    pub error: BencodeError,
+    /// what the parser was looking for when it failed, like "a dictionary key or 'e'"
+    pub expected: &'static str,
+}
+
+/// Like [`parse`] but an error gives back the part of the value that was decoded
+pub fn parse_partial<'a>(
+    encoded: &'a [u8],
+    options: &DecodeOptions,
+) -> Result<(Value<'a>, usize), Partial<'a>> {
+    let mut builder = Builder::new(value_node);
+    match parse_events(encoded, options, |event| builder.push_event(event)) {
+        Ok(consumed) => Ok((
+            builder.root.expect("events ended without a value"),
+            consumed,
+        )),
+        Err(error) => {
+            let expected = builder.expected();
+            Err(Partial {
+                value: builder.finish_partial(encoded.len()),
+                error,
+                expected,
+            })
+        }
+    }
+}
+
+/// Structural element of the encoded input, see [`tokenize`]
+pub use super::events::EventKind as TokenKind;
+
+/// Token along with where it is in the input and the dot-separated path
+/// of the value it belongs to ("" for the top level value)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token<'a> {
+    pub offset: usize,
+    /// number of encoded bytes, including the length prefix of strings
+    pub length: usize,
+    pub kind: TokenKind<'a>,
+    pub path: String,
+}
+
+/// Splits the bencoded value at the start of `encoded` into its tokens,
+/// returns them along with the number of bytes consumed
+pub fn tokenize<'a>(
+    encoded: &'a [u8],
+    options: &DecodeOptions,
+) -> Result<(Vec<Token<'a>>, usize), BencodeError> {
+    let mut tokens = Vec::new();
+    // path of every open container along with the index of its next list item
+    let mut open: Vec<(String, usize)> = Vec::new();
+    // path of the dictionary value that follows the last key
+    let mut key_path: Option<String> = None;
+
+    let consumed = parse_events(encoded, options, |event| {
+        let path = match &event.kind {
+            EventKind::DictKey(key) => {
+                let (parent, _) = open.last().expect("key outside of a dictionary");
+                let path = child_path(parent, &key_segment(key));
+                key_path = Some(path.clone());
+                path
+            }
+            EventKind::End => open.pop().map(|(path, _)| path).unwrap_or_default(),
+            _ => match (key_path.take(), open.last_mut()) {
+                (Some(path), _) => path,
+                (None, Some((parent, index))) => {
+                    *index += 1;
+                    child_path(parent, &(*index - 1).to_string())
+                }
+                (None, None) => String::new(),
+            },
+        };
+        if matches!(event.kind, EventKind::DictStart | EventKind::ListStart) {
+            open.push((path.clone(), 0));
+        }
+
+        tokens.push(Token {
+            offset: event.offset,
+            length: event.length,
+            kind: event.kind,
+            path,
+        });
+    })?;
+
+    Ok((tokens, consumed))
+}
+
+/// Path segment for a dictionary key, keys that are not text are shown as hex
+fn key_segment(key: &[u8]) -> String {
+    match std::str::from_utf8(key) {
+        Ok(s) if !s.chars().any(char::is_control) => s.to_string(),
+        _ => key.iter().map(|byte| format!("{:02x}", byte)).collect(),
+    }
+}
+
+fn child_path(path: &str, segment: &str) -> String {
+    match path {
+        "" => segment.to_string(),
+        path => format!("{}.{}", path, segment),
+    }
+}
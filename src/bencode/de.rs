@@ -0,0 +1,207 @@
+use serde::de::{self, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use thiserror::Error;
+
+use super::borrowed::{self, Value};
+use super::{BencodeError, DecodeOptions, Integer};
+
+/// Error while deserializing, either malformed bencode or a value that
+/// doesn't match the type it is deserialized into
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Bencode(#[from] BencodeError),
+    #[error("{0}")]
+    Message(String),
+}
+
+impl de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+/// Deserializes `T` from a single bencoded value, byte strings are borrowed from `encoded`.
+/// The input is decoded with the default [`DecodeOptions`]
+///
+/// ```
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Info<'a> {
+///     name: &'a str,
+///     #[serde(rename = "piece length")]
+///     piece_length: u32,
+///     #[serde(borrow)]
+///     pieces: &'a [u8],
+///     length: Option<u64>,
+/// }
+///
+/// let encoded = b"d4:name5:a.txt12:piece lengthi16e6:pieces3:\x01\x02\x037:privatei1ee";
+/// let info: Info = bittorrent_starter_rust::bencode::de::from_bytes(encoded).unwrap();
+/// assert_eq!(info.pieces, &[1, 2, 3]);
+/// assert_eq!(info.length, None);
+/// ```
+pub fn from_bytes<'de, T: de::Deserialize<'de>>(encoded: &'de [u8]) -> Result<T, Error> {
+    let (value, consumed) = borrowed::parse(encoded, &DecodeOptions::default())?;
+    if consumed != encoded.len() {
+        return Err(BencodeError::TrailingData {
+            consumed,
+            total: encoded.len(),
+        }
+        .into());
+    }
+
+    T::deserialize(value)
+}
+
+/// Owned values can be deserialized from any self-describing format, byte strings and
+/// text both become [`super::Value::Bytes`]
+impl<'de> de::Deserialize<'de> for super::Value {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(OwnedValueVisitor)
+    }
+}
+
+struct OwnedValueVisitor;
+
+impl<'de> Visitor<'de> for OwnedValueVisitor {
+    type Value = super::Value;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a bencode value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        self.visit_i64(i64::from(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(super::Value::Int(Integer::I64(v)))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(super::Value::Int(
+            i64::try_from(v).map_or(Integer::U64(v), Integer::I64),
+        ))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        self.visit_bytes(v.as_bytes())
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        self.visit_byte_buf(v.into_bytes())
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(super::Value::Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(super::Value::Bytes(v))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(super::Value::List(values))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::new();
+        while let Some((key, value)) = map.next_entry::<serde_bytes::ByteBuf, _>()? {
+            entries.push((key.into_vec(), value));
+        }
+        Ok(super::Value::Dict(entries))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Value<'de> {
+    type Error = Error;
+
+    /// Byte strings are given as text when they are valid UTF-8
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Bytes(b) => match std::str::from_utf8(b) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => visitor.visit_borrowed_bytes(b),
+            },
+            Value::Int(Integer::I64(i)) => visitor.visit_i64(i),
+            Value::Int(Integer::U64(u)) => visitor.visit_u64(u),
+            Value::Int(Integer::Big(text)) => visitor.visit_string(text),
+            Value::List(l) => visitor.visit_seq(de::value::SeqDeserializer::new(l.into_iter())),
+            Value::Dict(d) => visitor.visit_map(de::value::MapDeserializer::new(
+                d.into_iter().map(|(key, value)| (Value::Bytes(key), value)),
+            )),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Bytes(b) => visitor.visit_borrowed_bytes(b),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    /// Bencode has no null, a missing dictionary key is what makes a field `None`
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    /// Flags like "private" are stored as `i0e` or `i1e`
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Int(Integer::I64(0)) => visitor.visit_bool(false),
+            Value::Int(Integer::I64(1)) => visitor.visit_bool(true),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Only unit variants, written as their name
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            Value::Bytes(b) => match std::str::from_utf8(b) {
+                Ok(s) => visitor.visit_enum(s.into_deserializer()),
+                Err(_) => Err(de::Error::custom("enum variant is not UTF-8")),
+            },
+            _ => Err(de::Error::custom("expected an enum variant name")),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+
+use super::{is_canonical_integer, BencodeError, DecodeOptions, DecodeWarning, Integer};
+
+/// Structural element of the encoded input, see [`parse_events`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventKind<'a> {
+    DictStart,
+    DictKey(&'a [u8]),
+    Bytes(&'a [u8]),
+    Int(Integer),
+    ListStart,
+    /// end of the innermost list or dictionary
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event<'a> {
+    pub offset: usize,
+    /// number of encoded bytes, including the length prefix of strings
+    pub length: usize,
+    pub kind: EventKind<'a>,
+}
+
+/// Walks the bencoded value at the start of `encoded` without building it, calling
+/// `on_event` for every element in the order they appear, returns the number of
+/// bytes consumed
+///
+/// Byte strings are borrowed from `encoded`, events emitted before an error are not
+/// taken back. Input that goes over one of the limits of `options` fails where it
+/// does, duplicate keys are accepted without a warning unless `options.strict`.
+///
+/// ```
+/// use bittorrent_starter_rust::bencode::events::{parse_events, EventKind};
+/// use bittorrent_starter_rust::bencode::{BencodeError, DecodeLimits, DecodeOptions};
+///
+/// let mut keys = Vec::new();
+/// let options = DecodeOptions::default();
+/// let consumed = parse_events(b"d3:cow3:moo4:spaml1:a1:bee", &options, |event| {
+///     if let EventKind::DictKey(key) = event.kind {
+///         keys.push(key);
+///     }
+/// })
+/// .unwrap();
+/// assert_eq!(keys, [b"cow".as_slice(), b"spam"]);
+/// assert_eq!(consumed, 26);
+///
+/// let options = DecodeOptions {
+///     limits: DecodeLimits {
+///         max_elements: 3,
+///         ..DecodeLimits::default()
+///     },
+///     ..DecodeOptions::default()
+/// };
+/// assert_eq!(
+///     parse_events(b"li1ei2ei3ee", &options, |_| {}),
+///     Err(BencodeError::TooManyElements { offset: 7, max_elements: 3 })
+/// );
+/// ```
+pub fn parse_events<'a, F: FnMut(Event<'a>)>(
+    encoded: &'a [u8],
+    options: &DecodeOptions,
+    on_event: F,
+) -> Result<usize, BencodeError> {
+    parse_events_at(encoded, 0, options, on_event, None)
+}
+
+/// Keys of a dictionary that is still open, for the duplicate and order checks
+#[derive(Default)]
+struct OpenKeys<'a> {
+    first_offsets: HashMap<&'a [u8], usize>,
+    previous: Option<(&'a [u8], usize)>,
+}
+
+/// [`parse_events`] for `encoded` that is the rest of an input from its byte `base`
+/// on, offsets and `max_total_bytes` count from the start of that input. Duplicate
+/// keys that are accepted go to `warnings` when there is one, the count returned is
+/// of the bytes consumed from `encoded`
+pub(super) fn parse_events_at<'a, F: FnMut(Event<'a>)>(
+    encoded: &'a [u8],
+    base: usize,
+    options: &DecodeOptions,
+    mut on_event: F,
+    mut warnings: Option<&mut Vec<DecodeWarning>>,
+) -> Result<usize, BencodeError> {
+    let limits = &options.limits;
+    let mut parser = Parser {
+        encoded,
+        base,
+        index: 0,
+        options,
+    };
+    let check_keys = options.strict || warnings.is_some();
+    // values so far, for `max_elements`
+    let mut elements: usize = 0;
+    // keys of each open container, `None` for lists
+    let mut open: Vec<Option<OpenKeys<'a>>> = Vec::new();
+
+    loop {
+        if let Some(keys) = open.last_mut() {
+            if parser.peek()? == b'e' {
+                on_event(Event {
+                    offset: parser.offset(),
+                    length: 1,
+                    kind: EventKind::End,
+                });
+                parser.index += 1;
+                open.pop();
+                if open.is_empty() {
+                    break;
+                }
+                continue;
+            }
+
+            if let Some(keys) = keys {
+                let key_offset = parser.offset();
+                if !parser.peek()?.is_ascii_digit() {
+                    return Err(BencodeError::UnexpectedByte {
+                        offset: key_offset,
+                        byte: parser.peek()?,
+                        expected: "a string dictionary key",
+                    });
+                }
+                let key = parser.parse_bytes()?;
+                if check_keys {
+                    check_key(keys, key, key_offset, options.strict, &mut warnings)?;
+                }
+                on_event(Event {
+                    offset: key_offset,
+                    length: parser.offset() - key_offset,
+                    kind: EventKind::DictKey(key),
+                });
+            }
+        }
+
+        let start_offset = parser.offset();
+        let byte = parser.peek()?;
+        elements += 1;
+        if elements > limits.max_elements {
+            return Err(BencodeError::TooManyElements {
+                offset: start_offset,
+                max_elements: limits.max_elements,
+            });
+        }
+        let kind = match byte {
+            b'i' => EventKind::Int(parser.parse_integer()?),
+            b'0'..=b'9' => EventKind::Bytes(parser.parse_bytes()?),
+            byte @ (b'l' | b'd') => {
+                if open.len() >= limits.max_depth {
+                    return Err(BencodeError::NestingTooDeep {
+                        offset: start_offset,
+                        max_depth: limits.max_depth,
+                    });
+                }
+                parser.index += 1;
+                open.push((byte == b'd').then(OpenKeys::default));
+
+                match byte {
+                    b'd' => EventKind::DictStart,
+                    _ => EventKind::ListStart,
+                }
+            }
+            byte => {
+                return Err(BencodeError::UnexpectedByte {
+                    offset: start_offset,
+                    byte,
+                    expected: "a value",
+                })
+            }
+        };
+        on_event(Event {
+            offset: start_offset,
+            length: parser.offset() - start_offset,
+            kind,
+        });
+
+        if open.is_empty() {
+            break;
+        }
+    }
+
+    Ok(parser.index)
+}
+
+/// Checks the next `key` of a dictionary against the keys before it, duplicates
+/// are an error when `strict` and a warning otherwise, the order only when `strict`
+fn check_key<'a>(
+    keys: &mut OpenKeys<'a>,
+    key: &'a [u8],
+    offset: usize,
+    strict: bool,
+    warnings: &mut Option<&mut Vec<DecodeWarning>>,
+) -> Result<(), BencodeError> {
+    let text = || String::from_utf8_lossy(key).into_owned();
+    match keys.first_offsets.get(key) {
+        Some(&first_offset) if strict => {
+            return Err(BencodeError::DuplicateKey {
+                offset,
+                first_offset,
+                key: text(),
+            })
+        }
+        Some(&first_offset) => {
+            if let Some(warnings) = warnings {
+                warnings.push(DecodeWarning::DuplicateKey {
+                    offset,
+                    first_offset,
+                    key: text(),
+                });
+            }
+        }
+        None => {
+            keys.first_offsets.insert(key, offset);
+        }
+    }
+
+    // keys should be strictly increasing when compared as raw bytes
+    if let Some((previous_key, previous_offset)) = keys.previous {
+        if strict && key < previous_key {
+            return Err(BencodeError::UnsortedKeys {
+                offset,
+                key: text(),
+                previous_offset,
+                previous_key: String::from_utf8_lossy(previous_key).into_owned(),
+            });
+        }
+    }
+    keys.previous = Some((key, offset));
+
+    Ok(())
+}
+
+struct Parser<'a, 'o> {
+    encoded: &'a [u8],
+    /// offset of `encoded` in the whole input
+    base: usize,
+    index: usize,
+    options: &'o DecodeOptions,
+}
+
+impl<'a> Parser<'a, '_> {
+    /// Offset of the next byte in the whole input
+    fn offset(&self) -> usize {
+        self.base + self.index
+    }
+
+    /// End of the bytes that can be read (an index into `encoded`), the input or
+    /// `max_total_bytes` if it is shorter
+    fn end(&self) -> usize {
+        let max_total_bytes = self.options.limits.max_total_bytes;
+        self.encoded
+            .len()
+            .min(max_total_bytes.saturating_sub(self.base))
+    }
+
+    /// Error for a byte that is needed at `index` but can't be read
+    fn missing_byte(&self, index: usize) -> BencodeError {
+        let offset = self.base + index;
+        let max_total_bytes = self.options.limits.max_total_bytes;
+        match offset >= max_total_bytes {
+            true => BencodeError::InputTooLarge {
+                offset,
+                max_total_bytes,
+            },
+            false => BencodeError::UnexpectedEof { offset },
+        }
+    }
+
+    fn peek(&self) -> Result<u8, BencodeError> {
+        match self.index < self.end() {
+            true => Ok(self.encoded[self.index]),
+            false => Err(self.missing_byte(self.index)),
+        }
+    }
+
+    /// Example: "i52e" -> 52
+    fn parse_integer(&mut self) -> Result<Integer, BencodeError> {
+        let start_offset = self.offset();
+        self.index += 1;
+
+        let remaining = &self.encoded[self.index..self.end()];
+        let end = remaining
+            .iter()
+            .position(|&byte| byte == b'e')
+            .ok_or_else(|| self.missing_byte(self.end()))?;
+        let text = &remaining[..end];
+        self.index += end + 1;
+
+        std::str::from_utf8(text)
+            .ok()
+            .filter(|_| self.options.lenient_integers || is_canonical_integer(text))
+            .and_then(Integer::parse)
+            .ok_or_else(|| BencodeError::InvalidInteger {
+                offset: start_offset,
+                text: String::from_utf8_lossy(text).into_owned(),
+            })
+    }
+
+    /// Example: "5:hello" -> "hello"
+    fn parse_bytes(&mut self) -> Result<&'a [u8], BencodeError> {
+        let start_offset = self.offset();
+        let limits = &self.options.limits;
+
+        let remaining = &self.encoded[self.index..self.end()];
+        let digits = remaining.iter().take_while(|b| b.is_ascii_digit()).count();
+        match remaining.get(digits) {
+            Some(b':') => {}
+            Some(_) => {
+                return Err(BencodeError::MissingColon {
+                    offset: start_offset + digits,
+                })
+            }
+            None => return Err(self.missing_byte(self.index + digits)),
+        }
+
+        // "03:abc" is not canonical, like the integer "i03e"
+        let is_padded = digits > 1 && remaining[0] == b'0';
+        let length = std::str::from_utf8(&remaining[..digits])
+            .ok()
+            .filter(|_| !(self.options.strict && is_padded))
+            .and_then(|s| s.parse::<usize>().ok())
+            .ok_or(BencodeError::InvalidStringLength {
+                offset: start_offset,
+            })?;
+        self.index += digits + 1;
+
+        // checked before the length is trusted for anything
+        if length > limits.max_string_length {
+            return Err(BencodeError::StringTooLong {
+                declared: length,
+                max: limits.max_string_length,
+                offset: start_offset,
+            });
+        }
+        if self.offset().saturating_add(length) > limits.max_total_bytes {
+            return Err(BencodeError::InputTooLarge {
+                offset: start_offset,
+                max_total_bytes: limits.max_total_bytes,
+            });
+        }
+        let available = self.encoded.len() - self.index;
+        if length > available {
+            return Err(BencodeError::StringTruncated {
+                declared: length,
+                available,
+                offset: start_offset,
+            });
+        }
+
+        let bytes = &self.encoded[self.index..self.index + length];
+        self.index += length;
+        Ok(bytes)
+    }
+}
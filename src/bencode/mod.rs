@@ -0,0 +1,982 @@
+use std::io::Read;
+
+use thiserror::Error;
+
+pub mod borrowed;
+pub mod de;
+pub mod events;
+pub mod ser;
+
+/// Decoded bencode value, byte strings are kept as raw bytes and only
+/// converted to text at the json boundary
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bytes(Vec<u8>),
+    Int(Integer),
+    List(Vec<Value>),
+    /// entries in the order they appear in the encoded value
+    Dict(Vec<(Vec<u8>, Value)>),
+}
+
+impl Value {
+    /// Looks up a dictionary entry, the last one wins for duplicate keys
+    pub fn get(&self, key: &[u8]) -> Option<&Value> {
+        self.as_dict()?
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// The byte string if it is valid UTF-8
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|b| std::str::from_utf8(b).ok())
+    }
+
+    pub fn as_int(&self) -> Option<&Integer> {
+        match self {
+            Self::Int(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Self::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict(&self) -> Option<&[(Vec<u8>, Value)]> {
+        match self {
+            Self::Dict(d) => Some(d),
+            _ => None,
+        }
+    }
+}
+
+/// Dictionary lookup, panics if the value is not a dictionary or has no such key
+///
+/// ```
+/// use bittorrent_starter_rust::bencode::{decode_bencoded_value, DecodeOptions};
+///
+/// let encoded = b"d8:announce3:url4:infod12:piece lengthi32768eee";
+/// let torrent = decode_bencoded_value(encoded, &DecodeOptions::default()).unwrap();
+/// let piece_length = torrent["info"]["piece length"].as_int().and_then(|i| i.as_i64());
+/// assert_eq!(piece_length, Some(32768));
+/// ```
+impl std::ops::Index<&str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        self.get(key.as_bytes())
+            .unwrap_or_else(|| panic!("No dictionary key {:?}", key))
+    }
+}
+
+/// List lookup, panics if the value is not a list or the index is out of bounds
+impl std::ops::Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Value {
+        self.as_list()
+            .and_then(|l| l.get(index))
+            .unwrap_or_else(|| panic!("No list item at index {}", index))
+    }
+}
+
+/// Bencode integers have no size limit, the ones that don't fit in an
+/// `i64` or `u64` are kept as their decimal text
+#[derive(Debug, Clone, PartialEq)]
+pub enum Integer {
+    I64(i64),
+    U64(u64),
+    Big(String),
+}
+
+impl Integer {
+    /// Parses the text between 'i' and 'e', `None` if it is not a number
+    pub fn parse(text: &str) -> Option<Self> {
+        if let Ok(i) = text.parse::<i64>() {
+            return Some(Self::I64(i));
+        }
+        if let Ok(u) = text.parse::<u64>() {
+            return Some(Self::U64(u));
+        }
+
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        let is_number = !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit());
+        is_number.then(|| Self::Big(text.to_string()))
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::I64(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::I64(i) => u64::try_from(*i).ok(),
+            Self::U64(u) => Some(*u),
+            Self::Big(_) => None,
+        }
+    }
+}
+
+impl From<i64> for Integer {
+    fn from(i: i64) -> Self {
+        Self::I64(i)
+    }
+}
+
+impl std::fmt::Display for Integer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::I64(i) => write!(f, "{}", i),
+            Self::U64(u) => write!(f, "{}", u),
+            Self::Big(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+impl From<serde_bencode::value::Value> for Value {
+    fn from(value: serde_bencode::value::Value) -> Self {
+        match value {
+            serde_bencode::value::Value::Bytes(b) => Self::Bytes(b),
+            serde_bencode::value::Value::Int(i) => Self::Int(i.into()),
+            serde_bencode::value::Value::List(l) => {
+                Self::List(l.into_iter().map(Value::from).collect())
+            }
+            serde_bencode::value::Value::Dict(d) => {
+                let mut entries: Vec<(Vec<u8>, Value)> = d
+                    .into_iter()
+                    .map(|(key, value)| (key, Value::from(value)))
+                    .collect();
+                // HashMap has no order, fall back to the canonical one
+                entries.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+                Self::Dict(entries)
+            }
+        }
+    }
+}
+
+/// Encodes the value as bencode, dictionary entries are written in their given order
+pub fn encode_bencoded_value(value: &Value) -> Vec<u8> {
+    let mut encoded: Vec<u8> = Vec::new();
+    write_bencoded_value(value, &mut encoded);
+    encoded
+}
+
+fn write_bencoded_value(value: &Value, encoded: &mut Vec<u8>) {
+    match value {
+        Value::Bytes(b) => {
+            encoded.extend(b.len().to_string().as_bytes());
+            encoded.push(b':');
+            encoded.extend(b);
+        }
+        Value::Int(i) => {
+            encoded.extend(format!("i{}e", i).as_bytes());
+        }
+        Value::List(l) => {
+            encoded.push(b'l');
+            l.iter()
+                .for_each(|value| write_bencoded_value(value, encoded));
+            encoded.push(b'e');
+        }
+        Value::Dict(d) => {
+            encoded.push(b'd');
+            d.iter().for_each(|(key, value)| {
+                write_bencoded_value(&Value::Bytes(key.clone()), encoded);
+                write_bencoded_value(value, encoded);
+            });
+            encoded.push(b'e');
+        }
+    }
+}
+
+/// Sorts the keys of every dictionary byte-wise (the last of duplicate keys wins),
+/// returns the number of dictionaries that were changed
+pub fn canonicalize_value(value: &mut Value) -> usize {
+    match value {
+        Value::List(l) => l.iter_mut().map(canonicalize_value).sum(),
+        Value::Dict(d) => {
+            let mut changed: usize = d.iter_mut().map(|(_, v)| canonicalize_value(v)).sum();
+
+            let is_sorted = d.windows(2).all(|pair| pair[0].0 < pair[1].0);
+            if !is_sorted {
+                // the sort is stable, so after reversing the last duplicate comes first
+                d.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+                d.reverse();
+                d.dedup_by(|(k1, _), (k2, _)| k1 == k2);
+                d.reverse();
+                changed += 1;
+            }
+            changed
+        }
+        Value::Bytes(_) | Value::Int(_) => 0,
+    }
+}
+
+/// Error while decoding bencode, every variant records the byte offset
+/// where parsing failed
+#[derive(Error, Debug, PartialEq)]
+pub enum BencodeError {
+    #[error("error at byte {offset}: unexpected end of input")]
+    UnexpectedEof { offset: usize },
+    #[error("error at byte {offset}: invalid integer {text:?}")]
+    InvalidInteger { offset: usize, text: String },
+    #[error("error at byte {offset}: invalid string length")]
+    InvalidStringLength { offset: usize },
+    #[error(
+        "error at byte {offset}: string declares {declared} bytes but only {available} are left"
+    )]
+    StringTruncated {
+        declared: usize,
+        available: usize,
+        offset: usize,
+    },
+    #[error(
+        "error at byte {offset}: string declares {declared} bytes, more than the limit of {max}"
+    )]
+    StringTooLong {
+        declared: usize,
+        max: usize,
+        offset: usize,
+    },
+    #[error("error at byte {offset}: expected ':' in string length prefix")]
+    MissingColon { offset: usize },
+    #[error("error at byte {offset}: unexpected byte {byte:#04x}, expected {expected}")]
+    UnexpectedByte {
+        offset: usize,
+        byte: u8,
+        expected: &'static str,
+    },
+    #[error("error at byte {offset}: duplicate dictionary key {key:?} (first seen at byte {first_offset})")]
+    DuplicateKey {
+        offset: usize,
+        first_offset: usize,
+        key: String,
+    },
+    #[error("error at byte {offset}: dictionary key {key:?} is not sorted after {previous_key:?} at byte {previous_offset}")]
+    UnsortedKeys {
+        offset: usize,
+        key: String,
+        previous_offset: usize,
+        previous_key: String,
+    },
+    #[error("error at byte {offset}: nesting is deeper than the limit of {max_depth}")]
+    NestingTooDeep { offset: usize, max_depth: usize },
+    #[error("error at byte {offset}: more than the limit of {max_elements} elements")]
+    TooManyElements { offset: usize, max_elements: usize },
+    #[error("error at byte {offset}: input is larger than the limit of {max_total_bytes} bytes")]
+    InputTooLarge {
+        offset: usize,
+        max_total_bytes: usize,
+    },
+    #[error("error at byte {consumed}: unexpected trailing data after the value ({} of {total} bytes left)", .total - .consumed)]
+    TrailingData { consumed: usize, total: usize },
+    #[error("error at byte {offset}: could not read input: {message}")]
+    Io { offset: usize, message: String },
+}
+
+impl BencodeError {
+    /// Byte offset where decoding failed
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::UnexpectedEof { offset }
+            | Self::InvalidInteger { offset, .. }
+            | Self::InvalidStringLength { offset }
+            | Self::StringTruncated { offset, .. }
+            | Self::StringTooLong { offset, .. }
+            | Self::MissingColon { offset }
+            | Self::UnexpectedByte { offset, .. }
+            | Self::DuplicateKey { offset, .. }
+            | Self::UnsortedKeys { offset, .. }
+            | Self::NestingTooDeep { offset, .. }
+            | Self::TooManyElements { offset, .. }
+            | Self::InputTooLarge { offset, .. }
+            | Self::Io { offset, .. } => *offset,
+            Self::TrailingData { consumed, .. } => *consumed,
+        }
+    }
+}
+
+/// Input the decoder accepted that canonical bencode doesn't allow, see [`DecodeOptions::strict`]
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum DecodeWarning {
+    /// the last value wins, just like inserting into a map
+    #[error("duplicate dictionary key {key:?} at bytes {first_offset} and {offset}")]
+    DuplicateKey {
+        offset: usize,
+        first_offset: usize,
+        key: String,
+    },
+}
+
+/// Default limit for how deeply lists and dictionaries can be nested
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Default limit for the length of a single byte string (64 MiB)
+pub const DEFAULT_MAX_STRING_LENGTH: usize = 64 * 1024 * 1024;
+
+/// Default limit for the number of values (strings, integers, lists and dictionaries)
+pub const DEFAULT_MAX_ELEMENTS: usize = 4 * 1024 * 1024;
+
+/// Default limit for the size of the whole input (256 MiB)
+pub const DEFAULT_MAX_TOTAL_BYTES: usize = 256 * 1024 * 1024;
+
+/// Guards against small hostile inputs that would decode into huge values
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeLimits {
+    /// maximum nesting of lists and dictionaries
+    pub max_depth: usize,
+    /// maximum length of a single byte string
+    pub max_string_length: usize,
+    /// maximum number of values in the whole input
+    pub max_elements: usize,
+    /// maximum number of bytes read from the input
+    pub max_total_bytes: usize,
+}
+
+impl DecodeLimits {
+    /// No limits at all, only for trusted input
+    pub fn none() -> Self {
+        Self {
+            max_depth: usize::MAX,
+            max_string_length: usize::MAX,
+            max_elements: usize::MAX,
+            max_total_bytes: usize::MAX,
+        }
+    }
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_string_length: DEFAULT_MAX_STRING_LENGTH,
+            max_elements: DEFAULT_MAX_ELEMENTS,
+            max_total_bytes: DEFAULT_MAX_TOTAL_BYTES,
+        }
+    }
+}
+
+/// Options controlling how strictly the input is validated
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions {
+    /// accept integers the spec forbids, like `i03e`, `i-0e` and `i+5e`
+    pub lenient_integers: bool,
+    /// fail on duplicate or unsorted dictionary keys instead of accepting them, and on
+    /// string lengths with leading zeros
+    pub strict: bool,
+    pub limits: DecodeLimits,
+}
+
+/// Violations of canonical bencode are reported as regular decode errors
+/// ([`BencodeError::UnsortedKeys`], [`BencodeError::DuplicateKey`],
+/// [`BencodeError::InvalidInteger`] and [`BencodeError::InvalidStringLength`])
+pub type CanonicalityError = BencodeError;
+
+/// Integers are "0" or an optional '-' followed by digits without a leading zero,
+/// so "-0", "03" and "+5" are all rejected
+fn is_canonical_integer(text: &[u8]) -> bool {
+    let digits = text.strip_prefix(b"-").unwrap_or(text);
+
+    match digits {
+        [b'0'] => text.len() == 1,
+        [b'1'..=b'9', rest @ ..] => rest.iter().all(u8::is_ascii_digit),
+        _ => false,
+    }
+}
+
+/// Decodes the bencoded value at the start of `encoded`,
+/// returns the value along with the number of bytes consumed. Duplicate keys are
+/// accepted without a warning, [`BencodeReader::take_warnings`] has them
+pub fn decode_bencoded_prefix(
+    encoded: &[u8],
+    options: &DecodeOptions,
+) -> Result<(Value, usize), BencodeError> {
+    let (value, consumed) = borrowed::parse(encoded, options)?;
+
+    Ok((Value::from(value), consumed))
+}
+
+/// Decodes `encoded` which should contain exactly one bencoded value
+pub fn decode_bencoded_value(
+    encoded: &[u8],
+    options: &DecodeOptions,
+) -> Result<Value, BencodeError> {
+    let (value, consumed) = decode_bencoded_prefix(encoded, options)?;
+    if consumed != encoded.len() {
+        return Err(BencodeError::TrailingData {
+            consumed,
+            total: encoded.len(),
+        });
+    }
+
+    Ok(value)
+}
+
+/// Decodes `encoded` as a stream of back-to-back bencoded values
+pub fn decode_bencoded_values(
+    encoded: &[u8],
+    options: &DecodeOptions,
+) -> Result<Vec<Value>, BencodeError> {
+    let mut values = Vec::new();
+    let mut offset = 0;
+    // accepted without a warning, like in `decode_bencoded_prefix`
+    let mut warnings = Vec::new();
+    while offset < encoded.len() {
+        let (value, consumed) =
+            borrowed::parse_at(&encoded[offset..], offset, options, &mut warnings)?;
+        values.push(Value::from(value));
+        offset += consumed;
+    }
+
+    Ok(values)
+}
+
+/// Smallest read from the reader of a [`BencodeReader`]
+const READ_CHUNK: usize = 8 * 1024;
+
+/// Streaming decoder that reads values from `reader` as they are needed,
+/// so large inputs don't have to be loaded into memory first
+///
+/// Only the value being decoded is buffered: it is parsed from what was read so far,
+/// and when that runs out before the value ends at least as much again is read and
+/// the value parsed once more.
+pub struct BencodeReader<R: Read> {
+    reader: R,
+    options: DecodeOptions,
+    /// bytes read but not decoded yet
+    buffer: Vec<u8>,
+    /// offset of the first byte of `buffer` in the input
+    offset: usize,
+    at_eof: bool,
+    warnings: Vec<DecodeWarning>,
+}
+
+impl<R: Read> BencodeReader<R> {
+    pub fn new(reader: R, options: DecodeOptions) -> Self {
+        Self {
+            reader,
+            options,
+            buffer: Vec::new(),
+            offset: 0,
+            at_eof: false,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Decodes the next value, it is an error if the input ends before a complete value
+    pub fn next_value(&mut self) -> Result<Value, BencodeError> {
+        loop {
+            let mut warnings = Vec::new();
+            let parsed =
+                borrowed::parse_at(&self.buffer, self.offset, &self.options, &mut warnings);
+            let error = match parsed {
+                Ok((value, consumed)) => {
+                    let value = Value::from(value);
+                    self.buffer.drain(..consumed);
+                    self.offset += consumed;
+                    self.warnings.append(&mut warnings);
+                    return Ok(value);
+                }
+                Err(err) => err,
+            };
+
+            // the value goes on past what was read, the warnings come again next time
+            let buffer_end = self.offset + self.buffer.len();
+            let is_cut_off = match error {
+                BencodeError::UnexpectedEof { offset } => offset == buffer_end,
+                BencodeError::StringTruncated { .. } => true,
+                _ => false,
+            };
+            if !is_cut_off || self.at_eof {
+                return Err(error);
+            }
+            self.fill()?;
+        }
+    }
+
+    /// Reads at least as many bytes as are buffered (and `READ_CHUNK`) unless the input
+    /// ends first, but never much past `max_total_bytes`
+    fn fill(&mut self) -> Result<(), BencodeError> {
+        let buffer_end = self.offset + self.buffer.len();
+        let room = self
+            .options
+            .limits
+            .max_total_bytes
+            .saturating_sub(buffer_end);
+        let wanted = self.buffer.len().max(READ_CHUNK).min(room).max(1);
+        match (&mut self.reader)
+            .take(wanted as u64)
+            .read_to_end(&mut self.buffer)
+        {
+            Ok(read) => {
+                self.at_eof = read < wanted;
+                Ok(())
+            }
+            Err(err) => Err(BencodeError::Io {
+                offset: self.offset + self.buffer.len(),
+                message: err.to_string(),
+            }),
+        }
+    }
+
+    /// Whether all of the input has been consumed
+    pub fn is_at_end(&mut self) -> Result<bool, BencodeError> {
+        if self.buffer.is_empty() && !self.at_eof {
+            self.fill()?;
+        }
+        Ok(self.buffer.is_empty())
+    }
+
+    /// What the values decoded so far had that `strict` would have refused, the next
+    /// call only returns the ones after
+    pub fn take_warnings(&mut self) -> Vec<DecodeWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Number of bytes consumed from the reader so far
+    pub fn bytes_consumed(&self) -> usize {
+        self.offset
+    }
+
+    /// Reads and discards the rest of the input, returns the number of bytes skipped
+    pub fn skip_to_end(&mut self) -> Result<usize, BencodeError> {
+        let buffered = self.buffer.len();
+        self.buffer.clear();
+        self.offset += buffered;
+        match std::io::copy(&mut self.reader, &mut std::io::sink()) {
+            Ok(skipped) => {
+                self.at_eof = true;
+                self.offset += skipped as usize;
+                Ok(buffered + skipped as usize)
+            }
+            Err(err) => Err(BencodeError::Io {
+                offset: self.offset,
+                message: err.to_string(),
+            }),
+        }
+    }
+}
+
+/// Checks that `encoded` is a single canonical bencoded value, i.e. every dictionary
+/// has strictly increasing keys and every integer and string length is in its
+/// canonical form
+pub fn validate_canonical(encoded: &[u8]) -> Result<(), CanonicalityError> {
+    let options = DecodeOptions {
+        strict: true,
+        ..Default::default()
+    };
+    decode_bencoded_value(encoded, &options)?;
+
+    Ok(())
+}
+
+/// Entry point for fuzzing the decoder with arbitrary bytes, panics when the
+/// decoders disagree with each other or a decoded value doesn't survive
+/// re-encoding (`decode(encode(v)) == v`, and canonical input encodes byte-for-byte)
+///
+/// Every decoder gets the same options, within the same limits valid input is
+/// decoded by all of them or by none.
+pub fn fuzz_decode(data: &[u8]) {
+    let options = DecodeOptions::default();
+    let decoded = decode_bencoded_prefix(data, &options);
+    let tokens = borrowed::tokenize(data, &options);
+    let mut reader = BencodeReader::new(data, options.clone());
+    let streamed = reader
+        .next_value()
+        .map(|value| (value, reader.bytes_consumed()));
+    assert_eq!(decoded, streamed, "streaming decoder disagrees");
+    assert_eq!(
+        decoded.as_ref().map(|(_, consumed)| *consumed),
+        tokens.as_ref().map(|(_, consumed)| *consumed),
+        "tokenizer disagrees"
+    );
+
+    let (value, consumed) = match decoded {
+        Ok(decoded) => decoded,
+        Err(_) => return,
+    };
+    let encoded = encode_bencoded_value(&value);
+    assert_eq!(
+        decode_bencoded_value(&encoded, &options).as_ref(),
+        Ok(&value),
+        "re-encoded value decodes differently"
+    );
+    if validate_canonical(&data[..consumed]).is_ok() {
+        assert_eq!(
+            encoded,
+            &data[..consumed],
+            "canonical input re-encodes differently"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xorshift64, enough to generate values without a dependency
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: u64) -> u64 {
+            self.next() % bound
+        }
+
+        fn bytes(&mut self, max_length: u64) -> Vec<u8> {
+            (0..self.below(max_length + 1))
+                .map(|_| self.next() as u8)
+                .collect()
+        }
+    }
+
+    /// Random value with keys, strings and integers of every kind, nested up to `depth`
+    fn random_value(random: &mut Random, depth: usize) -> Value {
+        let kinds = match depth {
+            0 => 3,
+            _ => 5,
+        };
+        match random.below(kinds) {
+            0 => Value::Bytes(random.bytes(24)),
+            1 => Value::Int(match random.below(4) {
+                0 => Integer::I64(random.below(3) as i64 - 1),
+                1 => Integer::I64(random.next() as i64),
+                2 => Integer::U64(random.next() | 1 << 63),
+                _ => Integer::Big(format!("-{}{:020}", random.below(9) + 1, random.next())),
+            }),
+            2 => Value::Bytes(b"e:il3:d0".to_vec()),
+            3 => Value::List(
+                (0..random.below(5))
+                    .map(|_| random_value(random, depth - 1))
+                    .collect(),
+            ),
+            _ => {
+                let mut entries: Vec<(Vec<u8>, Value)> = (0..random.below(5))
+                    .map(|_| (random.bytes(6), random_value(random, depth - 1)))
+                    .collect();
+                entries.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+                entries.dedup_by(|(k1, _), (k2, _)| k1 == k2);
+                Value::Dict(entries)
+            }
+        }
+    }
+
+    fn bytes(b: &[u8]) -> Value {
+        Value::Bytes(b.to_vec())
+    }
+
+    fn int(i: i64) -> Value {
+        Value::Int(Integer::I64(i))
+    }
+
+    fn decode(encoded: &[u8]) -> Result<Value, BencodeError> {
+        decode_bencoded_value(encoded, &DecodeOptions::default())
+    }
+
+    #[test]
+    fn the_info_of_a_real_torrent_keeps_its_pieces() {
+        let torrent = decode(include_bytes!("../../sample.torrent")).unwrap();
+        let info = torrent.get(b"info").unwrap();
+        assert_eq!(info.get(b"name"), Some(&bytes(b"sample.txt")));
+        assert_eq!(info.get(b"length"), Some(&int(92063)));
+        assert_eq!(info.get(b"piece length"), Some(&int(32768)));
+
+        let pieces = info.get(b"pieces").and_then(Value::as_bytes).unwrap();
+        assert_eq!(pieces.len(), 60);
+        let hex: String = pieces.iter().map(|byte| format!("{:02x}", byte)).collect();
+        assert_eq!(
+            hex,
+            [
+                "e876f67a2a8886e8f36b136726c30fa29703022d",
+                "6e2275e604a0766656736e81ff10b55204ad8d35",
+                "f00d937a0213df1982bc8d097227ad9e909acc17",
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn borrowed_pieces_are_a_slice_of_the_input() {
+        let encoded = include_bytes!("../../sample.torrent").as_slice();
+        let (torrent, consumed) = borrowed::parse(encoded, &DecodeOptions::default()).unwrap();
+        assert_eq!(consumed, encoded.len());
+
+        let pieces = torrent
+            .get(b"info")
+            .and_then(|info| info.get(b"pieces"))
+            .and_then(borrowed::Value::as_bytes)
+            .unwrap();
+        let key = b"6:pieces60:";
+        let start = encoded
+            .windows(key.len())
+            .position(|window| window == key)
+            .unwrap()
+            + key.len();
+        assert!(std::ptr::eq(pieces, &encoded[start..start + 60]));
+
+        assert_eq!(torrent.to_owned_value(), decode(encoded).unwrap());
+    }
+
+    #[test]
+    fn canonical_values_are_left_as_they_are() {
+        let sample = include_bytes!("../../sample.torrent").as_slice();
+        for encoded in [sample, b"d1:ad1:xi1e1:yi2ee1:bl0:d1:ci1eeee", b"le"] {
+            let mut value = decode(encoded).unwrap();
+            assert_eq!(canonicalize_value(&mut value), 0);
+            assert_eq!(encode_bencoded_value(&value), encoded);
+        }
+    }
+
+    #[test]
+    fn canonicalize_counts_the_dictionaries_it_reorders() {
+        // the top level one, one in a list and one nested are out of order, one isn't
+        let mut value = decode(b"d1:bld1:zi1e1:ai2eed1:ai1e1:bi2eee1:ad1:yi1e1:xi2eee").unwrap();
+        assert_eq!(canonicalize_value(&mut value), 3);
+        assert_eq!(
+            encode_bencoded_value(&value),
+            b"d1:ad1:xi2e1:yi1ee1:bld1:ai2e1:zi1eed1:ai1e1:bi2eeee"
+        );
+
+        // a duplicate key changes its dictionary too, the last value is kept
+        let mut value = decode(b"d1:ai1e1:ai2ee").unwrap();
+        assert_eq!(canonicalize_value(&mut value), 1);
+        assert_eq!(encode_bencoded_value(&value), b"d1:ai2ee");
+    }
+
+    #[test]
+    fn strings_can_hold_the_bytes_of_bencode_itself() {
+        assert_eq!(
+            decode(b"l3:exei5ee"),
+            Ok(Value::List(vec![bytes(b"exe"), int(5)]))
+        );
+        assert_eq!(
+            decode(b"d1:a2:ie1:b3:lee1:c1:d1:d4:i1e:e"),
+            Ok(Value::Dict(vec![
+                (b"a".to_vec(), bytes(b"ie")),
+                (b"b".to_vec(), bytes(b"lee")),
+                (b"c".to_vec(), bytes(b"d")),
+                (b"d".to_vec(), bytes(b"i1e:")),
+            ]))
+        );
+        // digits and colons that look like the length prefix of another string
+        assert_eq!(
+            decode(b"l4:12:34:3:abi-7ee"),
+            Ok(Value::List(vec![bytes(b"12:3"), bytes(b"3:ab"), int(-7)]))
+        );
+        assert_eq!(
+            decode(b"d3:1:e5:l1:ee4:d0:ei0ee"),
+            Ok(Value::Dict(vec![
+                (b"1:e".to_vec(), bytes(b"l1:ee")),
+                (b"d0:e".to_vec(), int(0)),
+            ]))
+        );
+        for encoded in [
+            b"l3:exei5ee".as_slice(),
+            b"d1:a2:ie1:b3:lee1:c1:d1:d4:i1e:e",
+            b"l4:12:34:3:abi-7ee",
+            b"d3:1:e5:l1:ee4:d0:ei0ee",
+        ] {
+            fuzz_decode(encoded);
+        }
+    }
+
+    #[test]
+    fn malformed_integers_are_rejected_unless_lenient() {
+        assert_eq!(decode(b"i0e"), Ok(int(0)));
+        assert_eq!(decode(b"i-1e"), Ok(int(-1)));
+
+        let lenient = DecodeOptions {
+            lenient_integers: true,
+            ..DecodeOptions::default()
+        };
+        for (encoded, text, lenient_value) in [
+            (b"i03e".as_slice(), "03", Some(3)),
+            (b"i-0e", "-0", Some(0)),
+            (b"i+5e", "+5", Some(5)),
+            (b"i12x4e", "12x4", None),
+            (b"ie", "", None),
+        ] {
+            let error = decode(encoded).unwrap_err();
+            assert_eq!(
+                error,
+                BencodeError::InvalidInteger {
+                    offset: 0,
+                    text: text.to_string()
+                }
+            );
+            assert!(error.to_string().contains(&format!("{:?}", text)));
+
+            let decoded = decode_bencoded_value(encoded, &lenient);
+            assert_eq!(decoded.ok(), lenient_value.map(int));
+        }
+    }
+
+    #[test]
+    fn duplicate_keys_are_a_warning_or_an_error_with_strict() {
+        let encoded = b"d3:fooi1e3:fooi2ee";
+
+        let mut reader = BencodeReader::new(encoded.as_slice(), DecodeOptions::default());
+        let value = reader.next_value().unwrap();
+        assert_eq!(value.get(b"foo"), Some(&int(2)));
+        assert_eq!(
+            reader.take_warnings(),
+            [DecodeWarning::DuplicateKey {
+                offset: 9,
+                first_offset: 1,
+                key: "foo".to_string()
+            }]
+        );
+        assert!(reader.take_warnings().is_empty());
+
+        let strict = DecodeOptions {
+            strict: true,
+            ..DecodeOptions::default()
+        };
+        let mut reader = BencodeReader::new(encoded.as_slice(), strict.clone());
+        let error = BencodeError::DuplicateKey {
+            offset: 9,
+            first_offset: 1,
+            key: "foo".to_string(),
+        };
+        assert_eq!(reader.next_value(), Err(error));
+        assert!(decode_bencoded_value(encoded, &strict).is_err());
+        assert!(validate_canonical(encoded).is_err());
+    }
+
+    #[test]
+    fn negative_integers_in_lists_and_dictionaries() {
+        assert_eq!(
+            decode(b"li-42e5:helloe"),
+            Ok(Value::List(vec![int(-42), bytes(b"hello")]))
+        );
+        assert_eq!(
+            decode(b"d1:ai-1e1:bli-2ei-3ee1:cd1:di-9223372036854775808eee"),
+            Ok(Value::Dict(vec![
+                (b"a".to_vec(), int(-1)),
+                (b"b".to_vec(), Value::List(vec![int(-2), int(-3)])),
+                (
+                    b"c".to_vec(),
+                    Value::Dict(vec![(b"d".to_vec(), int(i64::MIN))])
+                ),
+            ]))
+        );
+        assert_eq!(
+            decode(b"li-99999999999999999999ee"),
+            Ok(Value::List(vec![Value::Int(Integer::Big(
+                "-99999999999999999999".to_string()
+            ))]))
+        );
+
+        for (encoded, offset, text) in [
+            (b"li-ee".as_slice(), 1, "-"),
+            (b"li--1ee", 1, "--1"),
+            (b"d1:ai-0ee", 4, "-0"),
+            (b"d1:ali1ei-01eee", 8, "-01"),
+        ] {
+            let text = text.to_string();
+            assert_eq!(
+                decode(encoded),
+                Err(BencodeError::InvalidInteger { offset, text })
+            );
+        }
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error_not_a_crash() {
+        let depth = 100_000;
+        let mut encoded = vec![b'l'; depth];
+        encoded.resize(2 * depth, b'e');
+        let error = || BencodeError::NestingTooDeep {
+            offset: DEFAULT_MAX_DEPTH,
+            max_depth: DEFAULT_MAX_DEPTH,
+        };
+        let options = DecodeOptions::default();
+
+        assert_eq!(decode(&encoded), Err(error()));
+        let mut reader = BencodeReader::new(encoded.as_slice(), options.clone());
+        assert_eq!(reader.next_value(), Err(error()));
+        assert_eq!(borrowed::parse(&encoded, &options), Err(error()));
+        assert_eq!(borrowed::tokenize(&encoded, &options), Err(error()));
+        let partial = borrowed::parse_partial(&encoded, &options).unwrap_err();
+        assert_eq!(partial.error, error());
+        assert_eq!(
+            events::parse_events(&encoded, &options, |_| {}),
+            Err(error())
+        );
+        assert_eq!(
+            de::from_bytes::<Vec<Vec<u8>>>(&encoded)
+                .unwrap_err()
+                .to_string(),
+            error().to_string()
+        );
+        fuzz_decode(&encoded);
+    }
+
+    #[test]
+    fn random_values_round_trip() {
+        let mut random = Random(0x9e37_79b9_7f4a_7c15);
+        let options = DecodeOptions {
+            strict: true,
+            ..DecodeOptions::default()
+        };
+        for _ in 0..2000 {
+            let value = random_value(&mut random, 6);
+            let encoded = encode_bencoded_value(&value);
+            assert_eq!(decode_bencoded_value(&encoded, &options), Ok(value.clone()));
+            assert_eq!(
+                encode_bencoded_value(&decode_bencoded_value(&encoded, &options).unwrap()),
+                encoded
+            );
+            fuzz_decode(&encoded);
+        }
+    }
+
+    #[test]
+    fn deep_values_round_trip() {
+        let mut value = Value::Dict(Vec::new());
+        for depth in 0..DEFAULT_MAX_DEPTH - 1 {
+            value = match depth % 2 {
+                0 => Value::List(vec![value, Value::Int(Integer::I64(-1))]),
+                _ => Value::Dict(vec![(vec![0xff], value)]),
+            };
+        }
+        let encoded = encode_bencoded_value(&value);
+        assert_eq!(
+            decode_bencoded_value(&encoded, &DecodeOptions::default()),
+            Ok(value)
+        );
+        fuzz_decode(&encoded);
+    }
+
+    #[test]
+    fn random_bytes_keep_the_decoders_in_agreement() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        let alphabet = b"ilde0123456789:-x";
+        for _ in 0..5000 {
+            let data: Vec<u8> = (0..random.below(32))
+                .map(|_| alphabet[random.below(alphabet.len() as u64) as usize])
+                .collect();
+            fuzz_decode(&data);
+        }
+    }
+}
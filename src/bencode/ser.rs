@@ -0,0 +1,449 @@
+use serde::ser::{self, Serialize};
+
+use super::de::Error;
+use super::{encode_bencoded_value, Integer, Value};
+
+impl ser::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+/// Newtype name [`Value`] uses to pass integers wider than 64 bits through serde as text,
+/// other serializers just see a string
+const BIG_INT_NAME: &str = "$bencode::BigInt";
+
+/// Serializes `value` as bencode, the keys of every dictionary (structs included)
+/// are sorted byte-wise so the output is canonical regardless of field order
+///
+/// ```
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Info<'a> {
+///     name: &'a str,
+///     #[serde(with = "serde_bytes")]
+///     pieces: &'a [u8],
+///     #[serde(rename = "piece length")]
+///     piece_length: u32,
+///     length: Option<u64>,
+///     private: Option<bool>,
+/// }
+///
+/// let info = Info {
+///     name: "a.txt",
+///     pieces: &[1, 2, 3],
+///     piece_length: 16,
+///     length: Some(5),
+///     private: None,
+/// };
+/// let encoded = bittorrent_starter_rust::bencode::ser::to_bytes(&info).unwrap();
+/// assert_eq!(encoded, b"d6:lengthi5e4:name5:a.txt12:piece lengthi16e6:pieces3:\x01\x02\x03e");
+/// ```
+pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    Ok(encode_bencoded_value(&to_value(value)?))
+}
+
+/// Like [`to_bytes`] but stops at the [`Value`], so it can be adjusted before encoding
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, Error> {
+    required(value.serialize(Serializer)?)
+}
+
+impl Serialize for Value {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use ser::{SerializeMap, SerializeSeq};
+
+        match self {
+            Self::Bytes(b) => serializer.serialize_bytes(b),
+            Self::Int(Integer::I64(i)) => serializer.serialize_i64(*i),
+            Self::Int(Integer::U64(u)) => serializer.serialize_u64(*u),
+            Self::Int(Integer::Big(text)) => {
+                serializer.serialize_newtype_struct(BIG_INT_NAME, text)
+            }
+            Self::List(l) => {
+                let mut seq = serializer.serialize_seq(Some(l.len()))?;
+                for value in l {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            Self::Dict(d) => {
+                let mut map = serializer.serialize_map(Some(d.len()))?;
+                for (key, value) in d {
+                    map.serialize_entry(serde_bytes::Bytes::new(key), value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// Builds a [`Value`], `None` stands for values bencode can't hold (`None`, `()`),
+/// struct fields with such a value are left out
+struct Serializer;
+
+/// Sorts the entries and rejects duplicate keys
+fn into_dict(mut entries: Vec<(Vec<u8>, Value)>) -> Result<Option<Value>, Error> {
+    entries.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+    if let Some(pair) = entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(ser::Error::custom(format!(
+            "duplicate dictionary key {:?}",
+            String::from_utf8_lossy(&pair[0].0)
+        )));
+    }
+    Ok(Some(Value::Dict(entries)))
+}
+
+fn required(value: Option<Value>) -> Result<Value, Error> {
+    value.ok_or_else(|| ser::Error::custom("bencode has no representation for null"))
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = DictSerializer;
+    type SerializeStruct = DictSerializer;
+    type SerializeStructVariant = DictSerializer;
+
+    /// Flags are written as `i0e` or `i1e`
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Int(Integer::I64(v))))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Error> {
+        match i64::try_from(v) {
+            Ok(i) => self.serialize_i64(i),
+            Err(_) => Ok(Some(Value::Int(Integer::U64(v)))),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Error> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Error> {
+        Err(ser::Error::custom(format!(
+            "bencode has no floats, found {}",
+            v
+        )))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Error> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Bytes(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        let value = value.serialize(self)?;
+        if name != BIG_INT_NAME {
+            return Ok(value);
+        }
+
+        match value {
+            Some(Value::Bytes(text)) => std::str::from_utf8(&text)
+                .ok()
+                .and_then(Integer::parse)
+                .map(|integer| Some(Value::Int(integer)))
+                .ok_or_else(|| ser::Error::custom("invalid integer")),
+            _ => Err(ser::Error::custom("invalid integer")),
+        }
+    }
+
+    /// Written as a dictionary with the variant name as the only key
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        let value = required(value.serialize(Serializer)?)?;
+        into_dict(vec![(variant.as_bytes().to_vec(), value)])
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer {
+            variant: None,
+            values: Vec::with_capacity(len.unwrap_or_default()),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer {
+            variant: Some(variant),
+            values: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<DictSerializer, Error> {
+        Ok(DictSerializer {
+            variant: None,
+            entries: Vec::with_capacity(len.unwrap_or_default()),
+            pending_key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<DictSerializer, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<DictSerializer, Error> {
+        Ok(DictSerializer {
+            variant: Some(variant),
+            entries: Vec::with_capacity(len),
+            pending_key: None,
+        })
+    }
+}
+
+/// Lists, tuples and tuple variants (wrapped in a dictionary keyed by the variant name)
+struct SeqSerializer {
+    variant: Option<&'static str>,
+    values: Vec<Value>,
+}
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.values.push(required(value.serialize(Serializer)?)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Value>, Error> {
+        let list = Value::List(self.values);
+        match self.variant {
+            Some(variant) => into_dict(vec![(variant.as_bytes().to_vec(), list)]),
+            None => Ok(Some(list)),
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.finish()
+    }
+}
+
+/// Maps, structs and struct variants, the entries are buffered until `end` so they
+/// can be sorted
+struct DictSerializer {
+    variant: Option<&'static str>,
+    entries: Vec<(Vec<u8>, Value)>,
+    pending_key: Option<Vec<u8>>,
+}
+
+impl DictSerializer {
+    /// Entries without a value (like `None` fields) are left out
+    fn push<T: Serialize + ?Sized>(&mut self, key: Vec<u8>, value: &T) -> Result<(), Error> {
+        if let Some(value) = value.serialize(Serializer)? {
+            self.entries.push((key, value));
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Value>, Error> {
+        let dict = required(into_dict(self.entries)?)?;
+        match self.variant {
+            Some(variant) => into_dict(vec![(variant.as_bytes().to_vec(), dict)]),
+            None => Ok(Some(dict)),
+        }
+    }
+}
+
+impl ser::SerializeMap for DictSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        match key.serialize(Serializer)? {
+            Some(Value::Bytes(key)) => {
+                self.pending_key = Some(key);
+                Ok(())
+            }
+            _ => Err(ser::Error::custom("dictionary keys must be strings")),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .pending_key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for DictSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.push(key.as_bytes().to_vec(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for DictSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.push(key.as_bytes().to_vec(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.finish()
+    }
+}
@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::bencode::{
+    canonicalize_value, encode_bencoded_value, BencodeError, BencodeReader, DecodeOptions,
+};
+
+#[derive(Args, Debug)]
+pub struct CanonicalizeArgs {
+    /// bencoded input file path
+    input_file_path: PathBuf,
+    /// path the canonical bencode is written to
+    output_file_path: PathBuf,
+}
+
+/// For command: "canonicalize"
+pub fn execute(args: &CanonicalizeArgs) {
+    let encoded = match std::fs::read(&args.input_file_path) {
+        Ok(encoded) => encoded,
+        Err(err) => {
+            eprintln!(
+                "error: could not read {}: {}",
+                args.input_file_path.display(),
+                err
+            );
+            std::process::exit(1);
+        }
+    };
+
+    // the duplicate keys are dropped, the last value is kept
+    let mut reader = BencodeReader::new(encoded.as_slice(), DecodeOptions::default());
+    let decoded = reader
+        .next_value()
+        .and_then(|value| match reader.is_at_end()? {
+            true => Ok(value),
+            false => Err(BencodeError::TrailingData {
+                consumed: reader.bytes_consumed(),
+                total: encoded.len(),
+            }),
+        });
+    for warning in reader.take_warnings() {
+        eprintln!("warning: {}", warning);
+    }
+    let mut value = match decoded {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let changed = canonicalize_value(&mut value);
+    let canonical = encode_bencoded_value(&value);
+
+    if let Err(err) = std::fs::write(&args.output_file_path, &canonical) {
+        eprintln!(
+            "error: could not write {}: {}",
+            args.output_file_path.display(),
+            err
+        );
+        std::process::exit(1);
+    }
+
+    if canonical == encoded {
+        println!("already canonical");
+    } else {
+        println!("re-ordered {} dictionaries", changed);
+    }
+}
@@ -1,6 +1,10 @@
 use clap::{Parser, Subcommand};
 
-use crate::{decode, download, download_piece, handshake, info, peers};
+use crate::peer_id;
+use crate::{
+    canonicalize, create, decode, diff, download, download_piece, edit, encode, handshake, hash,
+    info, magnet, map, peers, piece, same, scrape, stats, to_hex_string, verify,
+};
 
 #[derive(Parser, Debug)]
 #[clap(
@@ -12,27 +16,97 @@ use crate::{decode, download, download_piece, handshake, info, peers};
 struct Cli {
     #[command(subcommand)]
     pub command: Command,
+    /// start the peer id sent to trackers and peers with this instead of "-XX0001-", at
+    /// most 8 characters (or BITTORRENT_PEER_ID_PREFIX)
+    #[arg(long, allow_hyphen_values = true)]
+    peer_id_prefix: Option<String>,
+    /// send this peer id, 40 hex characters (or BITTORRENT_PEER_ID)
+    #[arg(long, conflicts_with = "peer_id_prefix")]
+    peer_id: Option<String>,
+    /// print debug messages on stderr, like the peer id that is sent
+    #[arg(long)]
+    debug: bool,
+}
+
+/// The peer id the options (or else their environment variables) ask for, `None` to
+/// generate one
+fn chosen_peer_id(cli: &Cli) -> Option<[u8; 20]> {
+    let option = |value: &Option<String>, variable: &str| {
+        value.clone().or_else(|| std::env::var(variable).ok())
+    };
+    let chosen = match (
+        option(&cli.peer_id, "BITTORRENT_PEER_ID"),
+        option(&cli.peer_id_prefix, "BITTORRENT_PEER_ID_PREFIX"),
+    ) {
+        (Some(hex), _) => peer_id::parse_hex(&hex),
+        (None, Some(prefix)) => peer_id::generate_with_prefix(&prefix),
+        (None, None) => return None,
+    };
+    match chosen {
+        Ok(chosen) => Some(chosen),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
 enum Command {
     Decode(decode::DecodeArgs),
+    Encode(encode::EncodeArgs),
     Info(info::InfoArgs),
     Peers(peers::PeersArgs),
     Handshake(handshake::HandshakeArgs),
     #[clap(name = "download_piece")]
     DownloadPiece(download_piece::DownloadPieceArgs),
     Download(download::DownloadArgs),
+    Canonicalize(canonicalize::CanonicalizeArgs),
+    Diff(diff::DiffArgs),
+    Hash(hash::HashArgs),
+    Piece(piece::PieceArgs),
+    Map(map::MapArgs),
+    Create(create::CreateArgs),
+    Edit(edit::EditArgs),
+    Magnet(magnet::MagnetArgs),
+    Verify(verify::VerifyArgs),
+    Same(same::SameArgs),
+    Stats(stats::StatsArgs),
+    Scrape(scrape::ScrapeArgs),
 }
 
 pub async fn parse_and_execute() {
     let cli = Cli::parse();
+    if let Some(chosen) = chosen_peer_id(&cli) {
+        peer_id::set_local(chosen);
+    }
+    if cli.debug {
+        let local = peer_id::local();
+        eprintln!(
+            "debug: peer id {} ({})",
+            to_hex_string(&local.to_vec()),
+            String::from_utf8_lossy(local)
+        );
+    }
     match &cli.command {
         Command::Decode(args) => decode::execute(args),
+        Command::Encode(args) => encode::execute(args),
         Command::Info(args) => info::execute(args),
         Command::Peers(args) => peers::execute(args).await,
         Command::Handshake(args) => handshake::execute(args),
         Command::DownloadPiece(args) => download_piece::execute(args).await,
         Command::Download(args) => download::execute(args).await,
+        Command::Canonicalize(args) => canonicalize::execute(args),
+        Command::Diff(args) => diff::execute(args),
+        Command::Hash(args) => hash::execute(args),
+        Command::Piece(args) => piece::execute(args),
+        Command::Map(args) => map::execute(args),
+        Command::Create(args) => create::execute(args),
+        Command::Edit(args) => edit::execute(args),
+        Command::Magnet(args) => magnet::execute(args),
+        Command::Verify(args) => verify::execute(args),
+        Command::Same(args) => same::execute(args),
+        Command::Stats(args) => stats::execute(args),
+        Command::Scrape(args) => scrape::execute(args).await,
     };
 }
@@ -0,0 +1,456 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::Args;
+use serde_bytes::ByteBuf;
+
+use crate::torrent::{
+    auto_piece_length, format_size, hash_pieces, FileEntry, Info, TorrentMetadata,
+};
+use crate::{fail, to_hex_string};
+
+#[derive(Args, Debug)]
+pub struct CreateArgs {
+    /// file or directory to make the torrent for, a directory makes a multi-file torrent
+    file_path: PathBuf,
+    /// tracker url
+    #[arg(long)]
+    announce: String,
+    /// bytes per piece, picked from the size (for 1000 to 2000 pieces) when not given
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    piece_length: Option<u32>,
+    /// where the torrent is written, "<file name>.torrent" by default
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// leave out files and directories whose name starts with '.'
+    #[arg(long)]
+    skip_hidden: bool,
+    /// include what symlinks point to, they are skipped otherwise
+    #[arg(long)]
+    follow_symlinks: bool,
+    /// "source" of the info dictionary, private trackers ask for one to tell torrents apart
+    #[arg(long)]
+    source: Option<String>,
+    /// start every file of a directory on a piece, with BEP 47 padding files between
+    /// them, so their pieces hash the same in other torrents
+    #[arg(long)]
+    align: bool,
+}
+
+/// `--align` pads each file up to a piece, with smaller pieces that adds a padding file
+/// for almost every piece
+const MIN_ALIGNED_PIECE_LENGTH: u32 = 16 * 1024;
+
+fn read_error(path: &Path, err: io::Error) -> ! {
+    fail(&format!("could not read {}: {}", path.display(), err))
+}
+
+/// Name of the last component of `path`, which has to be valid UTF-8
+fn file_name(path: &Path) -> String {
+    match path.file_name().map(|name| name.to_str()) {
+        Some(Some(name)) => name.to_string(),
+        Some(None) => fail(&format!("{} is not valid UTF-8", path.display())),
+        None => fail(&format!("{} has no file name", path.display())),
+    }
+}
+
+/// Files under `directory` in sorted path order, so the same directory always gives
+/// the same torrent
+fn walk(directory: &Path, args: &CreateArgs, files: &mut Vec<PathBuf>) {
+    let mut entries = std::fs::read_dir(directory)
+        .unwrap_or_else(|err| read_error(directory, err))
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|err| read_error(directory, err));
+    entries.sort();
+
+    for path in entries {
+        if args.skip_hidden
+            && path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+
+        let metadata =
+            std::fs::symlink_metadata(&path).unwrap_or_else(|err| read_error(&path, err));
+        let metadata = match metadata.file_type().is_symlink() {
+            true if !args.follow_symlinks => {
+                eprintln!("note: skipping symlink {}", path.display());
+                continue;
+            }
+            true => std::fs::metadata(&path).unwrap_or_else(|err| {
+                fail(&format!("could not follow {}: {}", path.display(), err))
+            }),
+            false => metadata,
+        };
+
+        if metadata.is_dir() {
+            // a followed symlink to a directory above it would be walked forever
+            let real_path =
+                std::fs::canonicalize(&path).unwrap_or_else(|err| read_error(&path, err));
+            let real_directory =
+                std::fs::canonicalize(directory).unwrap_or_else(|err| read_error(directory, err));
+            if real_directory.starts_with(&real_path) {
+                fail(&format!(
+                    "{} links to a directory that contains it",
+                    path.display()
+                ));
+            }
+            walk(&path, args, files);
+        } else if metadata.is_file() {
+            files.push(path);
+        }
+    }
+}
+
+/// What the payload of a multi-file torrent is made of, in order
+enum Part<'a> {
+    File(&'a Path),
+    /// a padding file, it is hashed as zeros but not on disk
+    Padding(u64),
+}
+
+/// The parts one after the other, as a single stream so pieces can span files
+struct Concatenated<'a> {
+    parts: std::vec::IntoIter<Part<'a>>,
+    current: Option<Box<dyn Read + 'a>>,
+}
+
+impl Read for Concatenated<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(current) = &mut self.current {
+                match current.read(buf)? {
+                    0 => self.current = None,
+                    read => return Ok(read),
+                }
+            }
+
+            // files are only opened when they are reached, a torrent can have thousands
+            self.current = match self.parts.next() {
+                Some(Part::File(path)) => Some(Box::new(BufReader::new(File::open(path)?))),
+                Some(Part::Padding(length)) => Some(Box::new(io::repeat(0).take(length))),
+                None => return Ok(0),
+            };
+        }
+    }
+}
+
+/// The given piece length, or one that suits `length` bytes
+fn piece_length(args: &CreateArgs, length: u64) -> u32 {
+    args.piece_length
+        .unwrap_or_else(|| auto_piece_length(length))
+}
+
+/// Hashes the content with a buffer of one piece, so large files aren't read into memory
+fn hash_content<R: Read>(content: R, piece_length: u32, path: &Path) -> ByteBuf {
+    let pieces =
+        hash_pieces(content, piece_length as usize).unwrap_or_else(|err| read_error(path, err));
+
+    eprintln!(
+        "note: piece length {} ({} bytes), {} pieces",
+        format_size(u64::from(piece_length)),
+        piece_length,
+        pieces.len() / 20
+    );
+    ByteBuf::from(pieces)
+}
+
+fn single_file_info(args: &CreateArgs, name: String) -> Info {
+    let path = args.file_path.display();
+    let file = File::open(&args.file_path)
+        .unwrap_or_else(|err| fail(&format!("could not open {}: {}", path, err)));
+    let length = file
+        .metadata()
+        .unwrap_or_else(|err| fail(&format!("could not read {}: {}", path, err)))
+        .len();
+
+    // an empty file has no pieces, a file that is a multiple of the piece length no short one
+    let piece_length = piece_length(args, length);
+    let pieces = hash_content(BufReader::new(file), piece_length, &args.file_path);
+
+    Info {
+        name: ByteBuf::from(name),
+        pieces,
+        piece_length,
+        length: Some(length),
+        files: None,
+        private: None,
+        meta_version: None,
+        file_tree: None,
+        md5sum: None,
+        source: args.source.clone().map(ByteBuf::from),
+        extra: Default::default(),
+    }
+}
+
+/// A BEP 47 padding file of `length` zeros, named like other clients name them
+fn padding_entry(length: u64) -> FileEntry {
+    FileEntry {
+        length,
+        path: vec![ByteBuf::from(".pad"), ByteBuf::from(length.to_string())],
+        attr: Some(ByteBuf::from("p")),
+        symlink_path: None,
+        md5sum: None,
+        extra: Default::default(),
+    }
+}
+
+fn directory_info(args: &CreateArgs, name: String) -> Info {
+    let mut paths = Vec::new();
+    walk(&args.file_path, args, &mut paths);
+    if paths.is_empty() {
+        fail(&format!("{} has no files", args.file_path.display()));
+    }
+
+    let lengths: Vec<u64> = paths
+        .iter()
+        .map(|path| {
+            std::fs::metadata(path)
+                .unwrap_or_else(|err| read_error(path, err))
+                .len()
+        })
+        .collect();
+    let piece_length = piece_length(args, lengths.iter().sum());
+    if args.align && piece_length < MIN_ALIGNED_PIECE_LENGTH {
+        fail(&format!(
+            "--align needs a piece length of at least {}",
+            format_size(u64::from(MIN_ALIGNED_PIECE_LENGTH))
+        ));
+    }
+
+    let mut files = Vec::new();
+    let mut parts = Vec::new();
+    let mut offset = 0;
+    for (path, &length) in paths.iter().zip(&lengths) {
+        // padding goes before a file that would start inside a piece, empty files
+        // take up no piece
+        let misalignment = offset % u64::from(piece_length);
+        if args.align && length > 0 && misalignment > 0 {
+            let padding = u64::from(piece_length) - misalignment;
+            files.push(padding_entry(padding));
+            parts.push(Part::Padding(padding));
+            offset += padding;
+        }
+
+        let relative = path
+            .strip_prefix(&args.file_path)
+            .expect("walked from the directory");
+        let components = relative
+            .components()
+            .map(|component| match component.as_os_str().to_str() {
+                Some(component) => ByteBuf::from(component),
+                None => fail(&format!("{} is not valid UTF-8", path.display())),
+            })
+            .collect();
+        files.push(FileEntry {
+            length,
+            path: components,
+            attr: None,
+            symlink_path: None,
+            md5sum: None,
+            extra: Default::default(),
+        });
+        parts.push(Part::File(path));
+        offset += length;
+    }
+
+    let content = Concatenated {
+        parts: parts.into_iter(),
+        current: None,
+    };
+    let pieces = hash_content(content, piece_length, &args.file_path);
+
+    Info {
+        name: ByteBuf::from(name),
+        pieces,
+        piece_length,
+        length: None,
+        files: Some(files),
+        private: None,
+        meta_version: None,
+        file_tree: None,
+        md5sum: None,
+        source: args.source.clone().map(ByteBuf::from),
+        extra: Default::default(),
+    }
+}
+
+/// For command: "create"
+pub fn execute(args: &CreateArgs) {
+    let name = file_name(&args.file_path);
+    let info = match args.file_path.is_dir() {
+        true => directory_info(args, name.clone()),
+        false => single_file_info(args, name.clone()),
+    };
+
+    let mut torrent_metadata = TorrentMetadata::new(Some(args.announce.clone()), info);
+    torrent_metadata.created_by = Some(ByteBuf::from(format!(
+        "bittorrent-client {}",
+        env!("CARGO_PKG_VERSION")
+    )));
+    torrent_metadata.creation_date = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|since| i64::try_from(since.as_secs()).ok());
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("{}.torrent", name)));
+    if let Err(err) = std::fs::write(&output, torrent_metadata.to_bytes()) {
+        fail(&format!("could not write {}: {}", output.display(), err));
+    }
+
+    println!("Torrent: {}", output.display());
+    println!(
+        "Info Hash: {}",
+        to_hex_string(&torrent_metadata.info_hash().to_vec())
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verify;
+
+    const PIECE_LENGTH: u32 = 16 * 1024;
+
+    /// A directory with nested, empty and hidden files, none a multiple of the piece
+    /// length
+    fn sample_directory() -> tempfile::TempDir {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path().join("sample");
+        std::fs::create_dir_all(root.join("nested/deeper")).unwrap();
+        std::fs::write(root.join("a.txt"), vec![b'a'; 40_000]).unwrap();
+        std::fs::write(root.join("empty"), b"").unwrap();
+        std::fs::write(root.join(".hidden"), b"hidden").unwrap();
+        let bytes: Vec<u8> = (0..=255).cycle().take(70_000).collect();
+        std::fs::write(root.join("nested/b.bin"), bytes).unwrap();
+        std::fs::write(root.join("nested/deeper/c"), b"c").unwrap();
+        directory
+    }
+
+    fn create_args(directory: &tempfile::TempDir) -> CreateArgs {
+        CreateArgs {
+            file_path: directory.path().join("sample"),
+            announce: "http://tracker.example/announce".to_string(),
+            piece_length: Some(PIECE_LENGTH),
+            output: None,
+            skip_hidden: false,
+            follow_symlinks: false,
+            source: None,
+            align: false,
+        }
+    }
+
+    /// The torrent `create` writes for the directory of `args`, read back
+    fn create(args: &CreateArgs) -> TorrentMetadata {
+        let info = directory_info(args, file_name(&args.file_path));
+        let torrent = TorrentMetadata::new(Some(args.announce.clone()), info);
+        TorrentMetadata::from_bytes(&torrent.to_bytes()).unwrap()
+    }
+
+    /// Paths of the files of a multi-file torrent, joined with '/'
+    fn paths(info: &Info) -> Vec<String> {
+        let files = info.files.as_deref().unwrap_or_default();
+        files
+            .iter()
+            .map(|file| {
+                let components: Vec<_> = file
+                    .path
+                    .iter()
+                    .map(|component| String::from_utf8_lossy(component))
+                    .collect();
+                components.join("/")
+            })
+            .collect()
+    }
+
+    /// The pieces `verify` finds bad in the directory of `args`
+    fn bad_pieces(args: &CreateArgs, info: &Info) -> Vec<usize> {
+        let every_piece: Vec<usize> = (0..info.get_pieces_count()).collect();
+        verify::bad_pieces(info, &args.file_path, &every_piece)
+    }
+
+    #[test]
+    fn a_created_directory_torrent_verifies() {
+        let directory = sample_directory();
+        let args = create_args(&directory);
+        let info = create(&args).info;
+
+        assert_eq!(
+            paths(&info),
+            [
+                ".hidden",
+                "a.txt",
+                "empty",
+                "nested/b.bin",
+                "nested/deeper/c"
+            ]
+        );
+        assert_eq!(info.total_length(), 6 + 40_000 + 70_000 + 1);
+        assert_eq!(bad_pieces(&args, &info), Vec::<usize>::new());
+
+        // the last byte of a.txt is in the third piece, changing it is caught
+        std::fs::write(
+            args.file_path.join("a.txt"),
+            [vec![b'a'; 39_999], vec![b'b']].concat(),
+        )
+        .unwrap();
+        assert_eq!(bad_pieces(&args, &info), [2]);
+    }
+
+    #[test]
+    fn hidden_files_can_be_left_out() {
+        let directory = sample_directory();
+        let args = CreateArgs {
+            skip_hidden: true,
+            ..create_args(&directory)
+        };
+        let info = create(&args).info;
+
+        assert_eq!(
+            paths(&info),
+            ["a.txt", "empty", "nested/b.bin", "nested/deeper/c"]
+        );
+        assert_eq!(bad_pieces(&args, &info), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn an_aligned_directory_torrent_verifies() {
+        let directory = sample_directory();
+        let args = CreateArgs {
+            align: true,
+            ..create_args(&directory)
+        };
+        let info = create(&args).info;
+
+        assert_eq!(
+            paths(&info),
+            [
+                ".hidden",
+                ".pad/16378",
+                "a.txt",
+                "empty",
+                ".pad/9152",
+                "nested/b.bin",
+                ".pad/11920",
+                "nested/deeper/c"
+            ]
+        );
+        // every file with bytes starts on a piece
+        let files = info.files.as_deref().unwrap_or_default();
+        for ((_, range), file) in info.file_ranges().iter().zip(files) {
+            if !file.is_padding() && file.length > 0 {
+                assert_eq!(range.start % u64::from(PIECE_LENGTH), 0);
+            }
+        }
+        // the padding files aren't on disk, verify reads them as zeros
+        assert_eq!(bad_pieces(&args, &info), Vec::<usize>::new());
+    }
+}
@@ -1,51 +1,967 @@
-use clap::Args;
-use serde_bencode;
-use serde_json;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
 
-fn transform_bencode_to_json(value: &serde_bencode::value::Value) -> serde_json::Value {
-    match value {
-        serde_bencode::value::Value::Bytes(b) => {
-            if let Ok(s) = String::from_utf8(b.clone()) {
-                serde_json::Value::String(s)
+use anyhow::bail;
+use clap::{Args, ValueEnum};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+
+use crate::bencode::borrowed::{self, Token, TokenKind};
+use crate::bencode::{
+    BencodeError, BencodeReader, DecodeLimits, DecodeOptions, Integer, Value, DEFAULT_MAX_DEPTH,
+    DEFAULT_MAX_ELEMENTS, DEFAULT_MAX_STRING_LENGTH, DEFAULT_MAX_TOTAL_BYTES,
+};
+use crate::encode::{BASE64_KEY_PREFIX, BASE64_TAG, HEX_TAG, INT_TAG};
+use crate::{to_base64_string, to_hex_string};
+
+mod cbor;
+mod escape;
+mod stats;
+mod yaml;
+
+/// How byte strings that are not printable text are rendered in the json output
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum BytesFormat {
+    /// lowercase hex string
+    #[default]
+    Hex,
+    /// standard base64 string (with padding)
+    Base64,
+    /// utf-8 with invalid sequences replaced by U+FFFD
+    Lossy,
+}
+
+impl BytesFormat {
+    fn format(&self, bytes: &[u8]) -> String {
+        match as_printable_str(bytes) {
+            Some(s) => s.to_string(),
+            None => self.format_binary(bytes),
+        }
+    }
+
+    /// Like `format` but printable text is moved into the string instead of copied
+    fn format_owned(&self, bytes: Vec<u8>) -> String {
+        match into_printable_string(bytes) {
+            Ok(s) => s,
+            Err(bytes) => self.format_binary(&bytes),
+        }
+    }
+
+    fn format_binary(&self, bytes: &[u8]) -> String {
+        match self {
+            Self::Hex => to_hex_string(&bytes.to_vec()),
+            Self::Base64 => to_base64_string(bytes),
+            Self::Lossy => String::from_utf8_lossy(bytes).into_owned(),
+        }
+    }
+}
+
+/// Output format of the decoded value(s)
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    #[default]
+    Json,
+    /// same representation of byte strings as json
+    Yaml,
+    /// raw binary output keeping byte strings as bytes, meant to be redirected into a file
+    Cbor,
+}
+
+/// Binary strings longer than this are shortened in pretty output unless `--full` is given
+const PREVIEW_THRESHOLD: usize = 32;
+
+/// Number of leading bytes shown in the preview of a shortened binary string
+const PREVIEW_BYTES: usize = 8;
+
+/// Controls how decoded values are rendered as json
+#[derive(Debug, Clone, Copy)]
+struct JsonOptions {
+    bytes_as: BytesFormat,
+    /// error out when two dictionary keys are formatted to the same json key
+    strict: bool,
+    /// replace long binary strings by a short preview like `"<3200 bytes: 1ab3f0…>"`
+    preview: bool,
+    /// lossless output that `encode --tagged` can turn back into the same bencode
+    tagged: bool,
+}
+
+impl JsonOptions {
+    fn format_bytes(&self, bytes: &[u8]) -> String {
+        if self.preview && bytes.len() > PREVIEW_THRESHOLD && as_printable_str(bytes).is_none() {
+            return format!(
+                "<{} bytes: {}…>",
+                bytes.len(),
+                to_hex_string(&bytes[..PREVIEW_BYTES].to_vec())
+            );
+        }
+
+        self.bytes_as.format(bytes)
+    }
+
+    fn format_owned_bytes(&self, bytes: Vec<u8>) -> String {
+        if self.preview && bytes.len() > PREVIEW_THRESHOLD {
+            return self.format_bytes(&bytes);
+        }
+
+        self.bytes_as.format_owned(bytes)
+    }
+}
+
+/// Returns the text if the bytes are valid UTF-8 without any control characters
+/// (tabs and newlines are allowed)
+fn as_printable_str(bytes: &[u8]) -> Option<&str> {
+    let s = std::str::from_utf8(bytes).ok()?;
+    is_printable(s).then_some(s)
+}
+
+/// Owned version of `as_printable_str`, gives the bytes back when they are not printable
+fn into_printable_string(bytes: Vec<u8>) -> Result<String, Vec<u8>> {
+    match String::from_utf8(bytes) {
+        Ok(s) if is_printable(&s) => Ok(s),
+        Ok(s) => Err(s.into_bytes()),
+        Err(err) => Err(err.into_bytes()),
+    }
+}
+
+fn is_printable(s: &str) -> bool {
+    s.chars()
+        .all(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+}
+
+/// Json value whose objects keep their keys in the order of the encoded input,
+/// `serde_json::Map` would sort them instead
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    String(String),
+    Number(serde_json::Number),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Inserts into an object, an existing key keeps its position but gets the new value
+    fn insert(entries: &mut Vec<(String, Json)>, key: String, value: Json) {
+        match entries.iter_mut().find(|(k, _)| *k == key) {
+            Some(entry) => entry.1 = value,
+            None => entries.push((key, value)),
+        }
+    }
+}
+
+impl Serialize for Json {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::String(s) => serializer.serialize_str(s),
+            Self::Number(n) => n.serialize(serializer),
+            Self::Array(values) => {
+                let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                for value in values {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            Self::Object(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", escape::to_json_string(self, false))
+    }
+}
+
+/// Dictionary key for `--tagged` output, keys that are not UTF-8 (or that would be
+/// mistaken for a tag) are written as base64 after `BASE64_KEY_PREFIX`
+fn tagged_key(key: Vec<u8>, is_single_entry: bool) -> String {
+    match String::from_utf8(key) {
+        Ok(key) => {
+            let is_tag = is_single_entry && [HEX_TAG, BASE64_TAG, INT_TAG].contains(&key.as_str());
+            if key.starts_with(BASE64_KEY_PREFIX) || is_tag {
+                format!("{}{}", BASE64_KEY_PREFIX, to_base64_string(key.as_bytes()))
             } else {
-                // serde_bytes::ByteBuf::from(b.clone())
-                serde_json::Value::Null
+                key
             }
-            // serde_json::Value::String(String::from_utf8(b.clone()).unwrap())
         }
-        serde_bencode::value::Value::Int(i) => serde_json::Value::Number((*i).into()),
-        serde_bencode::value::Value::List(l) => {
-            let values = l.iter().map(transform_bencode_to_json).collect();
-            serde_json::Value::Array(values)
+        Err(err) => format!("{}{}", BASE64_KEY_PREFIX, to_base64_string(err.as_bytes())),
+    }
+}
+
+/// Number of dictionary keys (at any depth) that can't be used as json keys as they are,
+/// those are written like binary values
+fn count_binary_keys(value: &Value, tagged: bool) -> usize {
+    match value {
+        Value::List(l) => l.iter().map(|value| count_binary_keys(value, tagged)).sum(),
+        Value::Dict(d) => d
+            .iter()
+            .map(|(key, value)| {
+                let is_binary = match tagged {
+                    true => std::str::from_utf8(key).is_err(),
+                    false => as_printable_str(key).is_none(),
+                };
+                usize::from(is_binary) + count_binary_keys(value, tagged)
+            })
+            .sum(),
+        _ => 0,
+    }
+}
+
+/// Converts the value to json, with `strict` it is an error if two different
+/// dictionary keys end up as the same json key after formatting.
+/// Takes ownership so byte strings that are valid text are moved instead of copied
+fn transform_bencode_to_json(value: Value, options: &JsonOptions) -> anyhow::Result<Json> {
+    let json = match value {
+        Value::Bytes(b) if options.tagged => match String::from_utf8(b) {
+            Ok(s) => Json::String(s),
+            Err(err) => Json::Object(vec![(
+                BASE64_TAG.to_string(),
+                Json::String(to_base64_string(err.as_bytes())),
+            )]),
+        },
+        // binary values (like piece hashes) are formatted as per `bytes_as`
+        Value::Bytes(b) => Json::String(options.format_owned_bytes(b)),
+        Value::Int(Integer::Big(text)) if options.tagged => {
+            Json::Object(vec![(INT_TAG.to_string(), Json::String(text))])
         }
-        serde_bencode::value::Value::Dict(d) => {
-            let map = d
-                .iter()
-                .filter_map(|(key, value)| {
-                    String::from_utf8(key.clone())
-                        .ok()
-                        .map(|key_str| (key_str, transform_bencode_to_json(value)))
-                })
-                .collect();
-            serde_json::Value::Object(map)
+        // integers that don't fit in a json number are written as a string
+        Value::Int(Integer::I64(i)) => Json::Number(i.into()),
+        Value::Int(Integer::U64(u)) => Json::Number(u.into()),
+        Value::Int(Integer::Big(text)) => Json::String(text),
+        Value::List(l) => {
+            let values = l
+                .into_iter()
+                .map(|value| transform_bencode_to_json(value, options))
+                .collect::<anyhow::Result<Vec<Json>>>()?;
+            Json::Array(values)
         }
-    }
+        Value::Dict(d) => {
+            let mut entries: Vec<(String, Json)> = Vec::with_capacity(d.len());
+            // raw bytes of the keys that are not printable, the others are their own json key
+            let mut binary_keys: HashMap<String, Vec<u8>> = HashMap::new();
+
+            // a lone key that is a tag name would be read back as a tagged value
+            let is_single_entry = d.len() == 1;
+            for (key, value) in d {
+                if options.tagged {
+                    let key_str = tagged_key(key, is_single_entry);
+                    let value = transform_bencode_to_json(value, options)?;
+                    Json::insert(&mut entries, key_str, value);
+                    continue;
+                }
+
+                let (key_str, binary_key) = match into_printable_string(key) {
+                    Ok(key_str) => (key_str, None),
+                    Err(key) => (options.bytes_as.format_binary(&key), Some(key)),
+                };
+
+                // exact duplicates are already reported by the decoder
+                if entries.iter().any(|(k, _)| *k == key_str) {
+                    let previous_key = binary_keys
+                        .get(&key_str)
+                        .map_or(key_str.as_bytes(), Vec::as_slice);
+                    let key = binary_key.as_deref().unwrap_or(key_str.as_bytes());
+                    if previous_key != key {
+                        let message = format!(
+                            "dictionary keys {} and {} are both formatted as {:?}",
+                            to_hex_string(&previous_key.to_vec()),
+                            to_hex_string(&key.to_vec()),
+                            key_str
+                        );
+                        if options.strict {
+                            bail!(message);
+                        }
+                        eprintln!("warning: {}", message);
+                    }
+                }
+
+                match binary_key {
+                    Some(key) => binary_keys.insert(key_str.clone(), key),
+                    None => binary_keys.remove(&key_str),
+                };
+                let value = transform_bencode_to_json(value, options)?;
+                Json::insert(&mut entries, key_str, value);
+            }
+            Json::Object(entries)
+        }
+    };
+
+    Ok(json)
 }
 
-fn decode_bencoded_value_serde_bencode(encoded_value: &[u8]) -> serde_json::Value {
+#[allow(dead_code)]
+fn decode_bencoded_value_serde_bencode(encoded_value: &[u8], bytes_as: BytesFormat) -> Json {
     let value: serde_bencode::value::Value = serde_bencode::from_bytes(encoded_value).unwrap();
-    return transform_bencode_to_json(&value);
+    let options = JsonOptions {
+        bytes_as,
+        strict: false,
+        preview: false,
+        tagged: false,
+    };
+    transform_bencode_to_json(Value::from(value), &options).unwrap()
 }
 
 #[derive(Args, Debug)]
 pub struct DecodeArgs {
-    /// encoded value (String)
-    encoded_value: String,
+    /// encoded value (String), reads stdin when missing or "-"
+    encoded_value: Option<String>,
+    /// read the encoded value from a file instead
+    #[clap(long, conflicts_with = "encoded_value")]
+    file: Option<PathBuf>,
+    /// representation for byte strings that are not printable text
+    #[clap(long, value_enum, default_value_t = BytesFormat::Hex)]
+    bytes_as: BytesFormat,
+    /// show byte strings that are not printable text as utf-8 with invalid sequences
+    /// replaced by U+FFFD, same as `--bytes-as lossy`
+    #[clap(long, conflicts_with_all = ["bytes_as", "tagged"])]
+    lossy: bool,
+    /// accept malformed integers (leading zeros, "-0", "+5") to inspect broken files
+    #[clap(long)]
+    allow_lenient: bool,
+    /// fail on duplicate or unsorted dictionary keys and zero-padded string lengths (canonical bencode only)
+    #[clap(long)]
+    strict: bool,
+    /// maximum nesting of lists and dictionaries
+    #[clap(long, default_value_t = DEFAULT_MAX_DEPTH)]
+    max_depth: usize,
+    /// maximum length of a single byte string
+    #[clap(long, default_value_t = DEFAULT_MAX_STRING_LENGTH)]
+    max_string_length: usize,
+    /// maximum number of values (strings, integers, lists and dictionaries)
+    #[clap(long, default_value_t = DEFAULT_MAX_ELEMENTS)]
+    max_elements: usize,
+    /// maximum size of the whole input
+    #[clap(long, default_value_t = DEFAULT_MAX_TOTAL_BYTES)]
+    max_total_bytes: usize,
+    /// disable all of the limits above, only for trusted local files
+    #[clap(
+        long,
+        conflicts_with_all = ["max_depth", "max_string_length", "max_elements", "max_total_bytes"]
+    )]
+    no_limits: bool,
+    /// decode the value at the start of the input and only warn about trailing data
+    #[clap(long, conflicts_with = "multi")]
+    lenient: bool,
+    /// decode back-to-back values until the end of input, printing one json document per line
+    #[clap(long)]
+    multi: bool,
+    /// only print the value at this dot-separated path, e.g. "info.piece_length" or "files.0.path"
+    #[clap(long)]
+    path: Option<String>,
+    /// print indented json, long binary strings are shortened to a preview
+    #[clap(long)]
+    pretty: bool,
+    /// with --pretty, print long binary strings in full
+    #[clap(long, requires = "pretty")]
+    full: bool,
+    /// lossless json: UTF-8 strings as they are, other byte strings as {"b64": "..."},
+    /// reversible with `encode --tagged`
+    #[clap(long, conflicts_with = "bytes_as")]
+    tagged: bool,
+    /// print the encoded size of each dictionary entry to stderr
+    #[clap(long, conflicts_with = "multi")]
+    stats: bool,
+    /// how many levels of nested dictionaries --stats goes into
+    #[clap(long, default_value_t = 1, requires = "stats")]
+    stats_depth: usize,
+    /// print the --stats as json instead of a table
+    #[clap(long, requires = "stats")]
+    stats_json: bool,
+    /// output format, cbor is written as raw bytes
+    #[clap(long, value_enum, default_value_t = OutputFormat::Json)]
+    format: OutputFormat,
+    /// print a hexdump of the input with each line tagged by the elements starting on it
+    #[clap(long, conflicts_with_all = ["multi", "path", "pretty"])]
+    annotate: bool,
+    /// on malformed input print what was decoded before the error, then where it failed
+    #[clap(long, conflicts_with_all = ["multi", "lenient", "stats", "annotate"])]
+    recover: bool,
+}
+
+/// Number of input bytes per hexdump line
+const ANNOTATE_ROW_BYTES: usize = 8;
+
+/// Byte strings longer than this only have their first and last
+/// `ANNOTATE_EDGE_BYTES` bytes dumped
+const ANNOTATE_ELIDE_THRESHOLD: usize = 48;
+const ANNOTATE_EDGE_BYTES: usize = 16;
+
+/// Short description of a token, like `8:announce → key "announce"`
+fn describe_token(token: &Token) -> String {
+    let text_preview = |bytes: &[u8]| match as_printable_str(bytes) {
+        Some(s) if s.chars().count() > ANNOTATE_EDGE_BYTES => {
+            format!(
+                "{:?}…",
+                s.chars().take(ANNOTATE_EDGE_BYTES).collect::<String>()
+            )
+        }
+        Some(s) => format!("{:?}", s),
+        None => format!("<{} binary bytes>", bytes.len()),
+    };
+    // the encoded form, strings are only spelled out when they are short text
+    let encoded_preview = |bytes: &[u8]| match as_printable_str(bytes) {
+        Some(s) if s.chars().count() <= ANNOTATE_EDGE_BYTES => format!("{}:{}", bytes.len(), s),
+        _ => format!("{}:…", bytes.len()),
+    };
+    let at_path = match token.path.as_str() {
+        "" => String::new(),
+        path => format!(" at {}", path),
+    };
+
+    match &token.kind {
+        TokenKind::DictStart => format!("d → dict start{}", at_path),
+        TokenKind::ListStart => format!("l → list start{}", at_path),
+        TokenKind::End => format!("e → end{}", at_path),
+        TokenKind::DictKey(key) => format!("{} → key {}", encoded_preview(key), text_preview(key)),
+        TokenKind::Bytes(bytes) => {
+            format!(
+                "{}:… → string {}{}",
+                bytes.len(),
+                text_preview(bytes),
+                at_path
+            )
+        }
+        TokenKind::Int(i) => format!("i{}e → int{}", i, at_path),
+    }
+}
+
+/// Renders the input as a hexdump, each line is followed by the tokens that start on it
+fn annotate(encoded: &[u8], options: &DecodeOptions) -> Result<Vec<String>, BencodeError> {
+    let (tokens, consumed) = borrowed::tokenize(encoded, options)?;
+    if consumed != encoded.len() {
+        return Err(BencodeError::TrailingData {
+            consumed,
+            total: encoded.len(),
+        });
+    }
+
+    // the middle of long strings is skipped
+    let elided: Vec<(usize, usize)> = tokens
+        .iter()
+        .filter_map(|token| match token.kind {
+            TokenKind::DictKey(b) | TokenKind::Bytes(b) if b.len() > ANNOTATE_ELIDE_THRESHOLD => {
+                let content_start = token.offset + token.length - b.len();
+                let content_end = token.offset + token.length;
+                Some((
+                    content_start + ANNOTATE_EDGE_BYTES,
+                    content_end - ANNOTATE_EDGE_BYTES,
+                ))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut lines = Vec::new();
+    let mut position = 0;
+    while position < encoded.len() {
+        if let Some(&(_, end)) = elided.iter().find(|(start, _)| *start == position) {
+            lines.push(format!("     … {} bytes elided …", end - position));
+            position = end;
+            continue;
+        }
+
+        let next_elided = elided
+            .iter()
+            .map(|(start, _)| *start)
+            .filter(|start| *start > position)
+            .min()
+            .unwrap_or(encoded.len());
+        let row_end = (position + ANNOTATE_ROW_BYTES)
+            .min(encoded.len())
+            .min(next_elided);
+
+        let hex = encoded[position..row_end]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let annotations = tokens
+            .iter()
+            .filter(|token| (position..row_end).contains(&token.offset))
+            .map(describe_token)
+            .collect::<Vec<String>>()
+            .join("; ");
+        lines.push(
+            format!(
+                "{:04x} {:width$}  {}",
+                position,
+                hex,
+                annotations,
+                width = ANNOTATE_ROW_BYTES * 3 - 1
+            )
+            .trim_end()
+            .to_string(),
+        );
+        position = row_end;
+    }
+
+    Ok(lines)
+}
+
+/// Number of input bytes shown around the failure by `--recover`
+const RECOVER_CONTEXT_BYTES: usize = 32;
+const RECOVER_ROW_BYTES: usize = 16;
+
+/// Hexdump of the bytes around `offset`, the failing byte is marked on the line below its row
+fn failure_hexdump(encoded: &[u8], offset: usize) -> Vec<String> {
+    let start = offset.saturating_sub(RECOVER_CONTEXT_BYTES / 2);
+    let end = (start + RECOVER_CONTEXT_BYTES).min(encoded.len());
+
+    let mut lines = Vec::new();
+    for (row, chunk) in encoded[start..end].chunks(RECOVER_ROW_BYTES).enumerate() {
+        let row_start = start + row * RECOVER_ROW_BYTES;
+        let hex = chunk
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let ascii: String = chunk
+            .iter()
+            .map(|&byte| match byte {
+                0x20..=0x7e => byte as char,
+                _ => '.',
+            })
+            .collect();
+        lines.push(format!(
+            "{:08x}  {:width$}  |{}|",
+            row_start,
+            hex,
+            ascii,
+            width = RECOVER_ROW_BYTES * 3 - 1
+        ));
+
+        if (row_start..row_start + chunk.len()).contains(&offset) {
+            let column = (offset - row_start) * 3;
+            lines.push(format!("{:10}{:column$}^^", "", "", column = column));
+        }
+    }
+    if offset >= encoded.len() {
+        lines.push(format!("({} is the end of the input)", offset));
+    }
+
+    lines
+}
+
+/// Decodes as much of `encoded` as it can, the error (if any) is given back along with
+/// the diagnostic to print once the partial value is written
+fn decode_recover(encoded: &[u8], options: &DecodeOptions) -> (Option<Value>, Option<Vec<String>>) {
+    let (value, error, expected) = match borrowed::parse_partial(encoded, options) {
+        Ok((value, consumed)) if consumed == encoded.len() => return (Some(value.into()), None),
+        Ok((value, consumed)) => {
+            let error = BencodeError::TrailingData {
+                consumed,
+                total: encoded.len(),
+            };
+            (Some(value), error, "the end of input")
+        }
+        Err(partial) => (partial.value, partial.error, partial.expected),
+    };
+
+    let mut diagnostic = vec![
+        error.to_string(),
+        format!("expected {} at byte {}", expected, error.offset()),
+    ];
+    diagnostic.extend(failure_hexdump(encoded, error.offset()));
+
+    (value.map(Value::from), Some(diagnostic))
+}
+
+/// Follows the dot-separated `path`, numeric segments index into lists and
+/// '_' in a segment also matches a ' ' in dictionary keys ("piece_length" -> "piece length")
+pub fn select_path<'v>(value: &'v Value, path: &str) -> anyhow::Result<&'v Value> {
+    let mut selected = value;
+
+    for (index, segment) in path.split('.').enumerate() {
+        let next = match selected.as_list() {
+            Some(l) => segment.parse::<usize>().ok().and_then(|i| l.get(i)),
+            None => selected
+                .get(segment.as_bytes())
+                .or_else(|| selected.get(segment.replace('_', " ").as_bytes())),
+        };
+
+        selected = match next {
+            Some(next) => next,
+            None if index == 0 => bail!("path {:?} not found, no segment resolved", path),
+            None => {
+                let resolved = path.split('.').take(index).collect::<Vec<&str>>().join(".");
+                bail!(
+                    "path {:?} not found, the deepest segment that resolved is {:?}",
+                    path,
+                    resolved
+                )
+            }
+        };
+    }
+
+    Ok(selected)
+}
+
+fn warn_trailing_data(consumed: usize, total: usize) {
+    eprintln!(
+        "warning: ignoring {} bytes of trailing data after byte {}",
+        total - consumed,
+        consumed
+    );
 }
 
+/// The next value of `reader`, printing the duplicate keys it accepted
+fn next_value(reader: &mut BencodeReader<impl Read>) -> Result<Value, BencodeError> {
+    let value = reader.next_value();
+    for warning in reader.take_warnings() {
+        eprintln!("warning: {}", warning);
+    }
+    value
+}
+
+/// Decodes the value(s) from a file, stdin or the argument without loading it all into
+/// memory, with `lenient` trailing data after a single value is only a warning
+fn decode_stream(
+    reader: impl Read,
+    options: &DecodeOptions,
+    multi: bool,
+    lenient: bool,
+) -> Result<Vec<Value>, BencodeError> {
+    let mut reader = BencodeReader::new(reader, options.clone());
+
+    if !multi {
+        let value = next_value(&mut reader)?;
+        if !reader.is_at_end()? {
+            let consumed = reader.bytes_consumed();
+            let total = consumed + reader.skip_to_end()?;
+            if !lenient {
+                return Err(BencodeError::TrailingData { consumed, total });
+            }
+            warn_trailing_data(consumed, total);
+        }
+        return Ok(vec![value]);
+    }
+
+    let mut values = Vec::new();
+    while !reader.is_at_end()? {
+        values.push(next_value(&mut reader)?);
+    }
+    Ok(values)
+}
+
+/// Reads all of the input, from the argument, a file or stdin
+fn read_input(args: &DecodeArgs) -> Vec<u8> {
+    match (&args.file, args.encoded_value.as_deref()) {
+        (Some(file_path), _) => std::fs::read(file_path).unwrap_or_else(|err| {
+            eprintln!("error: could not read {}: {}", file_path.display(), err);
+            std::process::exit(1);
+        }),
+        (None, None | Some("-")) => {
+            let mut encoded = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut encoded)
+                .expect("Could not read stdin");
+            encoded
+        }
+        (None, Some(encoded_value)) => encoded_value.as_bytes().to_vec(),
+    }
+}
+
+/// For command: "decode"
 pub fn execute(args: &DecodeArgs) {
-    // uses serde_bencode for parsing
-    let decoded_value = decode_bencoded_value_serde_bencode(args.encoded_value.as_bytes());
+    let limits = match args.no_limits {
+        true => DecodeLimits::none(),
+        false => DecodeLimits {
+            max_depth: args.max_depth,
+            max_string_length: args.max_string_length,
+            max_elements: args.max_elements,
+            max_total_bytes: args.max_total_bytes,
+        },
+    };
+    let options = DecodeOptions {
+        lenient_integers: args.allow_lenient,
+        strict: args.strict,
+        limits,
+    };
+
+    if args.annotate {
+        match annotate(&read_input(args), &options) {
+            Ok(lines) => lines.iter().for_each(|line| println!("{}", line)),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // printed after the output when `--recover` stopped early
+    let mut diagnostic = None;
+    let values = match (&args.file, args.encoded_value.as_deref()) {
+        _ if args.recover => {
+            let (value, failure) = decode_recover(&read_input(args), &options);
+            diagnostic = failure;
+            Ok(value.into_iter().collect())
+        }
+        // the statistics need all of the input at once
+        _ if args.stats => {
+            let encoded = read_input(args);
+            if let Err(err) =
+                stats::print_stats(&encoded, &options, args.stats_depth, args.stats_json)
+            {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+            decode_stream(encoded.as_slice(), &options, args.multi, args.lenient)
+        }
+        (Some(file_path), _) => match std::fs::File::open(file_path) {
+            Ok(file) => decode_stream(file, &options, args.multi, args.lenient),
+            Err(err) => {
+                eprintln!("error: could not read {}: {}", file_path.display(), err);
+                std::process::exit(1);
+            }
+        },
+        (None, None | Some("-")) => {
+            decode_stream(std::io::stdin().lock(), &options, args.multi, args.lenient)
+        }
+        (None, Some(encoded_value)) => {
+            decode_stream(encoded_value.as_bytes(), &options, args.multi, args.lenient)
+        }
+    };
+    let values = match values {
+        Ok(values) => values,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let bytes_as = match args.lossy {
+        true => BytesFormat::Lossy,
+        false => args.bytes_as,
+    };
+    let json_options = JsonOptions {
+        bytes_as,
+        strict: args.strict,
+        preview: args.pretty && !args.full && !args.tagged,
+        tagged: args.tagged,
+    };
+
+    for value in values {
+        let value = match &args.path {
+            Some(path) => match select_path(&value, path) {
+                Ok(selected) => selected.clone(),
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    std::process::exit(1);
+                }
+            },
+            None => value,
+        };
+
+        // cbor keeps the bencode value as it is, so it skips the json conversion
+        if args.format == OutputFormat::Cbor {
+            std::io::stdout()
+                .write_all(&cbor::to_cbor_bytes(&value))
+                .expect("Could not write to stdout");
+            continue;
+        }
+
+        // a selected string or integer is printed as plain text
+        if args.path.is_some() && args.format == OutputFormat::Json {
+            match value {
+                Value::Bytes(b) => {
+                    let text = json_options.format_owned_bytes(b);
+                    println!("{}", escape::escape_controls(&text));
+                    continue;
+                }
+                Value::Int(i) => {
+                    println!("{}", i);
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        let binary_keys = count_binary_keys(&value, args.tagged);
+        if binary_keys > 0 {
+            let written_as = match args.tagged {
+                true => format!("base64 with a {:?} prefix", BASE64_KEY_PREFIX),
+                false => bytes_as
+                    .to_possible_value()
+                    .map_or_else(String::new, |value| value.get_name().to_string()),
+            };
+            eprintln!(
+                "note: {} dictionary key(s) are not text and were written as {}",
+                binary_keys, written_as
+            );
+        }
+
+        let decoded_value = match transform_bencode_to_json(value, &json_options) {
+            Ok(decoded_value) => decoded_value,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            }
+        };
+
+        if args.format == OutputFormat::Yaml {
+            println!("---\n{}", yaml::to_yaml_string(&decoded_value));
+        } else if args.pretty {
+            println!("{}", escape::to_json_string(&decoded_value, true));
+        } else {
+            println!("{}", decoded_value);
+        }
+    }
+
+    if let Some(diagnostic) = diagnostic {
+        diagnostic.iter().for_each(|line| eprintln!("{}", line));
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!("{}", decoded_value.to_string());
+    const JSON: JsonOptions = JsonOptions {
+        bytes_as: BytesFormat::Hex,
+        strict: false,
+        preview: false,
+        tagged: false,
+    };
+
+    /// The compact json of `encoded`, like `decode` prints it
+    fn to_json(encoded: &[u8], options: &JsonOptions) -> String {
+        let value = crate::bencode::decode_bencoded_value(encoded, &DecodeOptions::default());
+        transform_bencode_to_json(value.unwrap(), options)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn text_stays_text_and_binary_becomes_lowercase_hex() {
+        let mut encoded = b"d4:name5:a.txt6:pieces20:".to_vec();
+        encoded.extend([0x00, 0x01, 0x7f, 0x80, 0xab, 0xcd, 0xef, 0xff]);
+        encoded.extend([0xfe; 12]);
+        encoded.push(b'e');
+        let hex = format!("00017f80abcdefff{}", "fe".repeat(12));
+        assert_eq!(
+            to_json(&encoded, &JSON),
+            format!(r#"{{"name":"a.txt","pieces":"{}"}}"#, hex)
+        );
+    }
+
+    #[test]
+    fn a_large_multi_file_torrent_gives_the_json_of_its_parts() {
+        // 5000 files in 10 directories, every tenth one with a name that isn't UTF-8
+        let mut encoded = b"d8:announce21:http://t.example/anno4:infod5:filesl".to_vec();
+        let mut files_json = Vec::new();
+        for index in 0..5000 {
+            let directory = format!("dir{}", index % 10);
+            let (name, name_json) = match index % 10 {
+                0 => (
+                    vec![0xff, index as u8, 0xfe],
+                    format!("ff{:02x}fe", index as u8),
+                ),
+                _ => {
+                    let name = format!("file {}.txt", index);
+                    (name.clone().into_bytes(), name)
+                }
+            };
+            encoded.extend(format!("d6:lengthi{}e4:pathl", index * 1000).as_bytes());
+            encoded.extend(format!("{}:{}{}:", directory.len(), directory, name.len()).as_bytes());
+            encoded.extend(&name);
+            encoded.extend(b"ee");
+            files_json.push(format!(
+                r#"{{"length":{},"path":["{}","{}"]}}"#,
+                index * 1000,
+                directory,
+                name_json
+            ));
+        }
+        // a 5 MB pieces blob
+        let pieces: Vec<u8> = (0..5_000_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        encoded.extend(b"e4:name3:big12:piece lengthi262144e6:pieces5000000:");
+        encoded.extend(&pieces);
+        encoded.extend(b"ee");
+
+        let expected = format!(
+            r#"{{"announce":"http://t.example/anno","info":{{"files":[{}],"name":"big","piece length":262144,"pieces":"{}"}}}}"#,
+            files_json.join(","),
+            to_hex_string(&pieces)
+        );
+        assert_eq!(to_json(&encoded, &JSON), expected);
+    }
+
+    #[test]
+    fn duplicate_keys_keep_the_last_value() {
+        assert_eq!(to_json(b"d3:fooi1e3:fooi2ee", &JSON), r#"{"foo":2}"#);
+    }
+
+    /// Bencode of the `--tagged` json, like `encode --tagged` writes it
+    fn encode_tagged(json: &str) -> Vec<u8> {
+        let json: serde_json::Value = serde_json::from_str(json).unwrap();
+        let value = crate::encode::transform_json_to_bencode(&json, "$", true).unwrap();
+        crate::bencode::ser::to_bytes(&value).unwrap()
+    }
+
+    #[test]
+    fn tagged_json_encodes_back_byte_for_byte() {
+        let tagged = JsonOptions {
+            tagged: true,
+            ..JSON
+        };
+        let sample = include_bytes!("../sample.torrent");
+        assert_eq!(encode_tagged(&to_json(sample, &tagged)), sample);
+
+        // a binary pieces blob, keys that are not UTF-8 or look like tags, big integers
+        let mut encoded = b"d4:b64:i1e3:bigi-123456789012345678901234e".to_vec();
+        encoded.extend_from_slice(b"4:infod3:intd3:b64i3eee6:pieces20:");
+        encoded.extend((0..20).map(|i: u8| i.wrapping_mul(47) ^ 0x80));
+        encoded.extend_from_slice(b"4:textl0:2:\xc3\xa9e2:\xff\xfei2ee");
+        let json = to_json(&encoded, &tagged);
+        assert!(json.contains(r#""b64://4=":2"#));
+        assert_eq!(encode_tagged(&json), encoded);
+    }
+
+    #[test]
+    fn keys_that_are_not_utf8_are_kept() {
+        let encoded = b"d1:ai1e2:\xff\xfe2:hie";
+        assert_eq!(to_json(encoded, &JSON), r#"{"a":1,"fffe":"hi"}"#);
+        let base64 = JsonOptions {
+            bytes_as: BytesFormat::Base64,
+            ..JSON
+        };
+        assert_eq!(to_json(encoded, &base64), r#"{"a":1,"//4=":"hi"}"#);
+
+        let value = crate::bencode::decode_bencoded_value(encoded, &DecodeOptions::default());
+        assert_eq!(count_binary_keys(&value.unwrap(), false), 1);
+    }
+
+    #[test]
+    fn control_characters_and_invalid_utf8_are_escaped() {
+        let encoded = b"l3:a\x00b8:\x1b[31mred3:ab\x8012:tab\tnew\nlinee";
+        assert_eq!(
+            to_json(encoded, &JSON),
+            r#"["610062","1b5b33316d726564","616280","tab\tnew\nline"]"#
+        );
+        let lossy = JsonOptions {
+            bytes_as: BytesFormat::Lossy,
+            ..JSON
+        };
+        assert_eq!(
+            to_json(encoded, &lossy),
+            "[\"a\\u0000b\",\"\\u001b[31mred\",\"ab\u{fffd}\",\"tab\\tnew\\nline\"]"
+        );
+        // a C1 control, which terminals read as the start of an escape sequence
+        assert_eq!(to_json(b"2:\xc2\x9b", &lossy), r#""\u009b""#);
+    }
+
+    #[test]
+    fn keys_keep_the_order_of_the_input() {
+        assert_eq!(
+            to_json(b"d5:zebrai1e5:apple2:ok5:mangod1:yi0e1:xi0eee", &JSON),
+            r#"{"zebra":1,"apple":"ok","mango":{"y":0,"x":0}}"#
+        );
+    }
 }
@@ -0,0 +1,120 @@
+use crate::bencode::{Integer, Value};
+
+/// CBOR major types (RFC 8949, section 3.1)
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+
+/// Tags for integers that don't fit in 64 bits
+const TAG_POSITIVE_BIGNUM: u64 = 2;
+const TAG_NEGATIVE_BIGNUM: u64 = 3;
+
+fn write_head(major: u8, argument: u64, encoded: &mut Vec<u8>) {
+    let major = major << 5;
+    match argument {
+        0..=23 => encoded.push(major | argument as u8),
+        24..=0xff => encoded.extend([major | 24, argument as u8]),
+        0x100..=0xffff => {
+            encoded.push(major | 25);
+            encoded.extend((argument as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            encoded.push(major | 26);
+            encoded.extend((argument as u32).to_be_bytes());
+        }
+        _ => {
+            encoded.push(major | 27);
+            encoded.extend(argument.to_be_bytes());
+        }
+    }
+}
+
+/// Valid UTF-8 is written as a text string and everything else as a byte string
+fn write_string(bytes: &[u8], encoded: &mut Vec<u8>) {
+    let major = match std::str::from_utf8(bytes) {
+        Ok(_) => MAJOR_TEXT,
+        Err(_) => MAJOR_BYTES,
+    };
+    write_head(major, bytes.len() as u64, encoded);
+    encoded.extend(bytes);
+}
+
+/// Converts decimal digits to big-endian bytes without leading zeros
+fn decimal_to_bytes(digits: &str) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    for digit in digits.bytes() {
+        // bytes = bytes * 10 + digit, least significant byte last
+        let mut carry = u32::from(digit - b'0');
+        for byte in bytes.iter_mut().rev() {
+            let value = u32::from(*byte) * 10 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+    bytes
+}
+
+/// Subtracts one from a big-endian number that is greater than zero
+fn decrement(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut().rev() {
+        if *byte > 0 {
+            *byte -= 1;
+            return;
+        }
+        *byte = 0xff;
+    }
+}
+
+fn write_integer(integer: &Integer, encoded: &mut Vec<u8>) {
+    match integer {
+        Integer::I64(i) if *i >= 0 => write_head(MAJOR_UNSIGNED, *i as u64, encoded),
+        // negative integers are stored as -1 - n
+        Integer::I64(i) => write_head(MAJOR_NEGATIVE, !(*i as u64), encoded),
+        Integer::U64(u) => write_head(MAJOR_UNSIGNED, *u, encoded),
+        Integer::Big(text) => {
+            let (tag, mut bytes) = match text.strip_prefix('-') {
+                Some(digits) => (TAG_NEGATIVE_BIGNUM, decimal_to_bytes(digits)),
+                None => (TAG_POSITIVE_BIGNUM, decimal_to_bytes(text)),
+            };
+            if tag == TAG_NEGATIVE_BIGNUM {
+                decrement(&mut bytes);
+            }
+            write_head(MAJOR_TAG, tag, encoded);
+            write_head(MAJOR_BYTES, bytes.len() as u64, encoded);
+            encoded.extend(bytes);
+        }
+    }
+}
+
+fn write_value(value: &Value, encoded: &mut Vec<u8>) {
+    match value {
+        Value::Bytes(b) => write_string(b, encoded),
+        Value::Int(i) => write_integer(i, encoded),
+        Value::List(l) => {
+            write_head(MAJOR_ARRAY, l.len() as u64, encoded);
+            l.iter().for_each(|value| write_value(value, encoded));
+        }
+        Value::Dict(d) => {
+            write_head(MAJOR_MAP, d.len() as u64, encoded);
+            d.iter().for_each(|(key, value)| {
+                write_string(key, encoded);
+                write_value(value, encoded);
+            });
+        }
+    }
+}
+
+/// Encodes the value as CBOR, byte strings stay bytes so nothing needs hex or base64
+pub fn to_cbor_bytes(value: &Value) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    write_value(value, &mut encoded);
+    encoded
+}
@@ -0,0 +1,82 @@
+use super::Json;
+
+/// Words a yaml parser would read as something other than a string
+const RESERVED_WORDS: [&str; 11] = [
+    "true", "false", "null", "yes", "no", "on", "off", "y", "n", "~", "",
+];
+
+/// Plain (unquoted) scalars are only used when they can't be mistaken for
+/// anything else, everything else is written as a double-quoted (json) string
+fn format_string(s: &str) -> String {
+    let is_plain = s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '/')
+        && !s.ends_with(' ')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | ' '))
+        && !RESERVED_WORDS.contains(&s.to_ascii_lowercase().as_str());
+
+    if is_plain {
+        s.to_string()
+    } else {
+        super::escape::to_json_string(s, false)
+    }
+}
+
+fn format_scalar(value: &Json) -> String {
+    match value {
+        Json::String(s) => format_string(s),
+        Json::Number(n) => n.to_string(),
+        Json::Array(_) => "[]".to_string(),
+        Json::Object(_) => "{}".to_string(),
+    }
+}
+
+fn is_nested(value: &Json) -> bool {
+    match value {
+        Json::Array(values) => !values.is_empty(),
+        Json::Object(entries) => !entries.is_empty(),
+        _ => false,
+    }
+}
+
+fn write_lines(value: &Json, indent: usize, lines: &mut Vec<String>) {
+    let pad = " ".repeat(indent);
+
+    match value {
+        Json::Object(entries) if !entries.is_empty() => {
+            for (key, value) in entries {
+                if is_nested(value) {
+                    lines.push(format!("{}{}:", pad, format_string(key)));
+                    write_lines(value, indent + 2, lines);
+                } else {
+                    lines.push(format!(
+                        "{}{}: {}",
+                        pad,
+                        format_string(key),
+                        format_scalar(value)
+                    ));
+                }
+            }
+        }
+        Json::Array(values) if !values.is_empty() => {
+            for value in values {
+                if !is_nested(value) {
+                    lines.push(format!("{}- {}", pad, format_scalar(value)));
+                    continue;
+                }
+
+                // the first line of the nested value goes on the same line as the "- "
+                let first_line = lines.len();
+                write_lines(value, indent + 2, lines);
+                lines[first_line] = format!("{}- {}", pad, &lines[first_line][indent + 2..]);
+            }
+        }
+        value => lines.push(format!("{}{}", pad, format_scalar(value))),
+    }
+}
+
+/// Formats the value as a block style yaml document (without the "---" marker)
+pub fn to_yaml_string(value: &Json) -> String {
+    let mut lines = Vec::new();
+    write_lines(value, 0, &mut lines);
+    lines.join("\n")
+}
@@ -0,0 +1,141 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use crate::bencode::{decode_bencoded_value, DecodeOptions, Value};
+use crate::decode::select_path;
+use crate::{calculate_hash, to_hex_string};
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// first bencoded file
+    first_file_path: PathBuf,
+    /// second bencoded file
+    second_file_path: PathBuf,
+    /// only compare the values at this dot-separated path, e.g. "info"
+    #[clap(long)]
+    path: Option<String>,
+}
+
+/// Exit code when the files could not be read or decoded,
+/// 0 and 1 mean identical and different like the unix `diff`
+const EXIT_CODE_ERROR: i32 = 2;
+
+fn read_value(file_path: &Path, path: Option<&str>) -> Value {
+    let encoded = match std::fs::read(file_path) {
+        Ok(encoded) => encoded,
+        Err(err) => {
+            eprintln!("error: could not read {}: {}", file_path.display(), err);
+            std::process::exit(EXIT_CODE_ERROR);
+        }
+    };
+
+    let value = match decode_bencoded_value(&encoded, &DecodeOptions::default()) {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{}: {}", file_path.display(), err);
+            std::process::exit(EXIT_CODE_ERROR);
+        }
+    };
+
+    match path {
+        Some(path) => match select_path(&value, path) {
+            Ok(selected) => selected.clone(),
+            Err(err) => {
+                eprintln!("{}: error: {}", file_path.display(), err);
+                std::process::exit(EXIT_CODE_ERROR);
+            }
+        },
+        None => value,
+    }
+}
+
+/// Short single line representation, binary strings are shown by their hash
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Bytes(b) => match std::str::from_utf8(b) {
+            Ok(s) if !s.chars().any(char::is_control) => format!("{:?}", s),
+            _ => format!("sha1:{}", to_hex_string(&calculate_hash(b))),
+        },
+        Value::Int(i) => i.to_string(),
+        Value::List(l) => format!("[{} items]", l.len()),
+        Value::Dict(d) => format!("{{{} keys}}", d.len()),
+    }
+}
+
+fn child_path(path: &str, segment: &str) -> String {
+    match path {
+        "" => segment.to_string(),
+        path => format!("{}.{}", path, segment),
+    }
+}
+
+fn key_segment(key: &[u8]) -> String {
+    match std::str::from_utf8(key) {
+        Ok(s) if !s.chars().any(char::is_control) => s.to_string(),
+        _ => to_hex_string(&key.to_vec()),
+    }
+}
+
+/// Collects the differences between `first` and `second` as lines like
+/// "- path = value", "+ path = value" and "~ path: old -> new"
+fn diff_values(path: &str, first: &Value, second: &Value, differences: &mut Vec<String>) {
+    let shown_path = if path.is_empty() { "(root)" } else { path };
+
+    match (first, second) {
+        (Value::Dict(d1), Value::Dict(d2)) => {
+            for (key, value) in d1 {
+                let key_path = child_path(path, &key_segment(key));
+                match second.get(key) {
+                    Some(other) => diff_values(&key_path, value, other, differences),
+                    None => differences.push(format!("- {} = {}", key_path, describe(value))),
+                }
+            }
+            for (key, value) in d2 {
+                if first.get(key).is_none() {
+                    let key_path = child_path(path, &key_segment(key));
+                    differences.push(format!("+ {} = {}", key_path, describe(value)));
+                }
+            }
+        }
+        (Value::List(l1), Value::List(l2)) => {
+            for index in 0..l1.len().max(l2.len()) {
+                let index_path = child_path(path, &index.to_string());
+                match (l1.get(index), l2.get(index)) {
+                    (Some(v1), Some(v2)) => diff_values(&index_path, v1, v2, differences),
+                    (Some(v1), None) => {
+                        differences.push(format!("- {} = {}", index_path, describe(v1)))
+                    }
+                    (None, Some(v2)) => {
+                        differences.push(format!("+ {} = {}", index_path, describe(v2)))
+                    }
+                    (None, None) => unreachable!(),
+                }
+            }
+        }
+        (first, second) if first != second => differences.push(format!(
+            "~ {}: {} -> {}",
+            shown_path,
+            describe(first),
+            describe(second)
+        )),
+        _ => {}
+    }
+}
+
+/// For command: "diff"
+pub fn execute(args: &DiffArgs) {
+    let path = args.path.as_deref();
+    let first = read_value(&args.first_file_path, path);
+    let second = read_value(&args.second_file_path, path);
+
+    let mut differences: Vec<String> = Vec::new();
+    diff_values(path.unwrap_or(""), &first, &second, &mut differences);
+
+    differences
+        .iter()
+        .for_each(|difference| println!("{}", difference));
+    if !differences.is_empty() {
+        std::process::exit(1);
+    }
+}
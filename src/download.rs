@@ -1,67 +1,316 @@
+use std::collections::VecDeque;
 use std::path::PathBuf;
+use std::pin::pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use std::{fs, os::unix::prelude::FileExt};
 
 use clap::Args;
+use tokio::sync::oneshot;
 
-use crate::{to_hex_string, Connection, PeerMessageType, TorrentMetadata, TrackerResponse};
+use crate::announce_timer::{self, AnnounceTimer};
+use crate::fetch::FetchArgs;
+use crate::peer_id;
+use crate::peers::FamilyArgs;
+use crate::torrent::TorrentContent;
+use crate::tracker::{AddressFamily, PeerAddress};
+use crate::udp_tracker::{AnnounceRequest, Event};
+use crate::{
+    read_usable_torrent, shuffled_trackers, to_hex_string, Connection, PeerMessageType,
+    TrackerList, TrackerResponse,
+};
+
+/// Fewer peers left to try than this bring the next announce forward, to the tracker's
+/// min interval
+const LOW_PEERS: usize = 5;
+
+/// Peers asked of the trackers by default
+const DEFAULT_NUM_WANT: i32 = 50;
+
+/// With this many peers left to try the announces ask for no more
+const SATURATED_PEERS: usize = 200;
+
+/// How often the announcer checks whether an announce is due
+const ANNOUNCE_CHECK_PERIOD: Duration = Duration::from_secs(1);
+
+/// How long the "completed" and "stopped" announces can take
+const EVENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the download waits for a re-announce to bring peers when none are left to
+/// try, twice the min interval of trackers that don't send one
+const PEER_WAIT: Duration = Duration::from_secs(120);
 
 #[derive(Args, Debug)]
 pub struct DownloadArgs {
     /// .torrent file path
     torrent_file_path: PathBuf,
+    #[command(flatten)]
+    fetch: FetchArgs,
+    #[command(flatten)]
+    family: FamilyArgs,
     /// output file path
     #[clap(short, long)]
     output: PathBuf,
+    /// show how each tracker answered
+    #[arg(short, long)]
+    verbose: bool,
+    /// ask the trackers for this many peers
+    #[arg(long, default_value_t = DEFAULT_NUM_WANT, value_parser = clap::value_parser!(i32).range(0..))]
+    numwant: i32,
+}
+
+/// The peers the trackers gave, in the order the download tries them
+#[derive(Default)]
+struct PeerPool {
+    /// not tried yet
+    waiting: VecDeque<PeerAddress>,
+    /// tried already, a tracker that gives them again doesn't add them back
+    taken: Vec<PeerAddress>,
+}
+
+/// What the download shares with its announcer: the peers the trackers gave and the
+/// progress the next announce reports
+struct Swarm {
+    peers: Mutex<PeerPool>,
+    downloaded: AtomicU64,
+    left: AtomicU64,
+}
+
+impl Swarm {
+    fn new(peers: Vec<PeerAddress>, left: u64) -> Self {
+        let swarm = Swarm {
+            peers: Mutex::default(),
+            downloaded: AtomicU64::new(0),
+            left: AtomicU64::new(left),
+        };
+        swarm.merge_peers(peers);
+        swarm
+    }
+
+    /// Adds the peers that aren't known yet, how many there were
+    fn merge_peers(&self, peers: Vec<PeerAddress>) -> usize {
+        let mut pool = self.peers.lock().expect("the peers lock is not poisoned");
+        let before = pool.waiting.len();
+        for peer in peers {
+            if !pool.waiting.contains(&peer) && !pool.taken.contains(&peer) {
+                pool.waiting.push_back(peer);
+            }
+        }
+        pool.waiting.len() - before
+    }
+
+    /// The next peer to try, it won't be given again
+    fn take_peer(&self) -> Option<PeerAddress> {
+        let mut pool = self.peers.lock().expect("the peers lock is not poisoned");
+        let peer = pool.waiting.pop_front()?;
+        pool.taken.push(peer.clone());
+        Some(peer)
+    }
+
+    /// How many peers are left to try
+    fn peers_left(&self) -> usize {
+        let pool = self.peers.lock().expect("the peers lock is not poisoned");
+        pool.waiting.len()
+    }
+
+    /// The next peer to try, when none is left this waits up to `wait` for a re-announce
+    /// to bring some
+    fn wait_for_peer(&self, wait: Duration) -> Option<PeerAddress> {
+        let deadline = Instant::now() + wait;
+        loop {
+            if let Some(peer) = self.take_peer() {
+                return Some(peer);
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            std::thread::sleep(ANNOUNCE_CHECK_PERIOD);
+        }
+    }
+
+    fn piece_downloaded(&self, length: u64) {
+        self.downloaded.fetch_add(length, Ordering::Relaxed);
+        self.left.fetch_sub(length, Ordering::Relaxed);
+    }
+}
+
+/// The timer of an announce answered just now
+fn announce_timer(response: &TrackerResponse) -> AnnounceTimer {
+    let interval = Duration::from_secs(response.interval);
+    AnnounceTimer::new(
+        Instant::now(),
+        interval,
+        response.min_interval.map(Duration::from_secs),
+        announce_timer::random_jitter(interval).unwrap_or_default(),
+    )
+}
+
+/// Announces of the download after the "started" one, to the trackers of the torrent
+struct Announcer {
+    request: AnnounceRequest,
+    /// `--numwant`, unless the peers are saturated
+    num_want: i32,
+    trackers: TrackerList,
+    /// the tracker that answered last, it gets the "completed" and "stopped" events
+    tracker: String,
+    preferred: Option<AddressFamily>,
+    verbose: bool,
+    swarm: Arc<Swarm>,
+    timer: AnnounceTimer,
+}
+
+impl Announcer {
+    /// Announces again whenever the timer says so until `finished` tells whether the
+    /// download completed (then "completed" when something was downloaded), and then
+    /// "stopped". Ctrl-C stops the tracker too, and then the process
+    async fn run(mut self, mut finished: oneshot::Receiver<bool>) {
+        let mut interrupted = pin!(tokio::signal::ctrl_c());
+        let is_complete = loop {
+            tokio::select! {
+                _ = tokio::time::sleep(ANNOUNCE_CHECK_PERIOD) => self.reannounce_if_due().await,
+                // a download that went away without saying is not complete
+                is_complete = &mut finished => break is_complete.unwrap_or(false),
+                _ = &mut interrupted => {
+                    self.send_event(Event::Stopped).await;
+                    std::process::exit(130);
+                }
+            }
+        };
+
+        if is_complete && self.swarm.downloaded.load(Ordering::Relaxed) > 0 {
+            self.send_event(Event::Completed).await;
+        }
+        self.send_event(Event::Stopped).await;
+    }
+
+    /// A failed announce is tried again after the same interval
+    async fn reannounce_if_due(&mut self) {
+        let peers_left = self.swarm.peers_left();
+        if !self.timer.is_due(Instant::now(), peers_left < LOW_PEERS) {
+            return;
+        }
+
+        self.update_progress();
+        self.request.num_want = match peers_left >= SATURATED_PEERS {
+            true => 0,
+            false => self.num_want,
+        };
+        let answer = TrackerResponse::announce(
+            &self.request,
+            &mut self.trackers,
+            self.preferred,
+            self.verbose,
+        )
+        .await;
+        match answer {
+            Ok((tracker, response)) => {
+                self.tracker = tracker;
+                self.timer = announce_timer(&response);
+                let added = self.swarm.merge_peers(response.peers);
+                if self.verbose {
+                    eprintln!("re-announce: {} new peers", added);
+                }
+            }
+            Err(failures) => {
+                // verbose output already showed every failure
+                if !self.verbose {
+                    for (announce, err) in failures {
+                        eprintln!("warning: re-announce to {} failed: {}", announce, err);
+                    }
+                }
+                self.timer.restart(Instant::now());
+            }
+        }
+    }
+
+    /// Best effort, the download is over so the tracker isn't waited for long (and no
+    /// peers are asked for)
+    async fn send_event(&mut self, event: Event) {
+        self.update_progress();
+        self.request.event = event;
+        self.request.num_want = 0;
+        let name = event.name().unwrap_or_default();
+        let announce = TrackerResponse::announce_to(&self.tracker, &self.request);
+        match tokio::time::timeout(EVENT_TIMEOUT, announce).await {
+            Ok(Ok(_)) if self.verbose => eprintln!("announce to {}: {}", self.tracker, name),
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => eprintln!(
+                "warning: {} announce to {} failed: {}",
+                name, self.tracker, err
+            ),
+            Err(_) => eprintln!("warning: {} announce to {} timed out", name, self.tracker),
+        }
+    }
+
+    fn update_progress(&mut self) {
+        self.request.downloaded = self.swarm.downloaded.load(Ordering::Relaxed);
+        self.request.left = self.swarm.left.load(Ordering::Relaxed);
+    }
 }
 
 pub async fn execute(args: &DownloadArgs) {
     // println!("args: {:?}", args);
 
     // Get torrent metadata
-    let torrent_metadata = TorrentMetadata::from_file(args.torrent_file_path.clone());
-
-    // get torrent tracker
-    let tracker_response = TrackerResponse::from(&torrent_metadata).await;
-
-    // get peers
-    let peers = tracker_response.get_peers();
+    let torrent_metadata = read_usable_torrent(&args.torrent_file_path, &args.fetch);
+    // symlinks are checked before anything is written, one pointing outside the output
+    // directory would let the torrent reach other files
+    if let TorrentContent::MultiFile { files } = torrent_metadata.info.content() {
+        if let Some(err) = files.iter().find_map(|file| file.check_symlink().err()) {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    }
 
-    // choose a peer
-    let peer = peers.get(1).expect("Could not fetch peer at that index");
+    // checked before the tracker is asked for peers
+    let TorrentContent::SingleFile {
+        length: torrent_file_length,
+    } = torrent_metadata.info.content()
+    else {
+        eprintln!("error: only single file torrents can be downloaded");
+        std::process::exit(1);
+    };
 
-    // Setup connection with the peer
-    let mut connection = Connection::new(peer.clone());
+    // get torrent tracker
+    let mut trackers = shuffled_trackers(&torrent_metadata);
+    let mut request = TrackerResponse::first_request(&torrent_metadata);
+    request.event = Event::Started;
+    request.num_want = args.numwant;
+    let (tracker, tracker_response) = TrackerResponse::from_request(
+        &request,
+        &mut trackers,
+        args.family.preferred(),
+        args.verbose,
+    )
+    .await;
+    request.event = Event::None;
 
-    let peer_id = "00112233445566778899".to_string();
+    // the trackers are asked again while the pieces download, for more peers
+    let swarm = Arc::new(Swarm::new(tracker_response.peers.clone(), request.left));
+    let announcer = Announcer {
+        num_want: request.num_want,
+        request,
+        trackers,
+        tracker,
+        preferred: args.family.preferred(),
+        verbose: args.verbose,
+        swarm: Arc::clone(&swarm),
+        timer: announce_timer(&tracker_response),
+    };
+    let (finished, finished_receiver) = oneshot::channel();
+    let announcer = tokio::spawn(announcer.run(finished_receiver));
 
-    let info_hash = torrent_metadata.info.hash_bytes().to_vec();
+    let info_hash = torrent_metadata.info_hash().to_vec();
 
-    let torrent_file_length = torrent_metadata
-        .info
-        .length
-        .expect("Could not get length of torrent file");
     println!("torrent_file_length: {}", torrent_file_length);
 
     let pieces_count = torrent_metadata.info.get_pieces_count() as u32;
     println!("pieces_count: {}", pieces_count);
 
-    let piece_length = torrent_metadata.info.piece_length as u32;
+    let piece_length = torrent_metadata.info.piece_length;
     println!("piece_length: {}", piece_length);
 
-    // Perform handshake
-    let res_peer_id = connection.handshake(info_hash, peer_id);
-    println!("res_peer_id: {}", to_hex_string(&res_peer_id));
-
-    // Send and wait for peer messages
-    // 1. Wait for `bitfield`
-    connection.wait(PeerMessageType::BitField);
-
-    // 2. Send an `interested` message
-    connection.send(PeerMessageType::Interested, vec![]);
-
-    // 3. Wait until `unchoke` is received
-    connection.wait(PeerMessageType::Unchoke);
-
     // get piece hashes
     let piece_hashes_str = torrent_metadata.info.get_piece_hashes_str();
 
@@ -74,61 +323,101 @@ pub async fn execute(args: &DownloadArgs) {
     fs::create_dir_all(parent_directory).expect("Failed to recursively create parent directories");
 
     // Create the output file if not exists
-    let file = fs::File::create(args.output.clone()).expect(
-        format!(
+    let file = fs::File::create(args.output.clone()).unwrap_or_else(|_| {
+        panic!(
             "Could not create file {}",
             args.output
                 .to_str()
                 .expect("Failed Pathbuf to str conversion")
         )
-        .as_str(),
-    );
+    });
 
-    // download each piece
-    for piece_index in 0..pieces_count {
-        println!(
-            "> Downloading piece {} (total {} pieces)",
-            piece_index, pieces_count
-        );
+    let geometry = torrent_metadata.info.piece_geometry();
 
-        // calculate actual piece length
-        let is_last_piece = torrent_file_length - piece_index * piece_length < piece_length;
-        let actual_piece_length = if is_last_piece {
-            torrent_file_length - piece_index * piece_length
-        } else {
-            piece_length
-        };
-        println!("actual_piece_length: {}", actual_piece_length);
+    // download each piece, on a blocking connection so the announcer moves to another
+    // worker meanwhile. A peer that fails leaves the rest of the pieces to the next one
+    let downloaded = tokio::task::block_in_place(|| {
+        let mut piece_index = 0;
+        let mut connection = None;
+        while piece_index < pieces_count {
+            let peer_connection = match &mut connection {
+                Some(connection) => connection,
+                None => {
+                    let Some(peer) = swarm.wait_for_peer(PEER_WAIT) else {
+                        return Err("no peer left to download from".to_string());
+                    };
+                    match connect(&peer, &info_hash) {
+                        Ok(peer_connection) => connection.insert(peer_connection),
+                        Err(err) => {
+                            eprintln!("warning: peer {} failed: {}", peer, err);
+                            continue;
+                        }
+                    }
+                }
+            };
 
-        // download the piece
-        let piece = connection.download_piece_pipelined(5, piece_index, actual_piece_length);
+            println!(
+                "> Downloading piece {} (total {} pieces)",
+                piece_index, pieces_count
+            );
 
-        // verify piece hash
-        let actual_piece_hash_str = piece_hashes_str
-            .get(piece_index as usize)
-            .expect(format!("Could not get piece hash at index {}", piece_index).as_str())
-            .clone();
+            // calculate actual piece length, the last piece is shorter. The offset is a
+            // u64, pieces of files over 4 GiB start past what a u32 holds
+            let Some((offset, actual_piece_length)) = geometry.piece(piece_index as usize) else {
+                panic!("Piece {} is past the end of the torrent", piece_index);
+            };
+            // at most a piece length, which is a u32
+            let actual_piece_length = actual_piece_length as u32;
+            println!("actual_piece_length: {}", actual_piece_length);
 
-        let calculated_piece_hash = piece.get_hash();
-        let calculated_piece_hash_str = to_hex_string(&calculated_piece_hash);
+            // download the piece
+            let piece =
+                match peer_connection.download_piece_pipelined(5, piece_index, actual_piece_length)
+                {
+                    Ok(piece) => piece,
+                    Err(err) => {
+                        eprintln!("warning: peer failed on piece {}: {}", piece_index, err);
+                        connection = None;
+                        continue;
+                    }
+                };
 
-        assert_eq!(
-            calculated_piece_hash_str, actual_piece_hash_str,
-            "Piece {} hash does not match, expected {:?} but calculated {:?}",
-            piece_index, actual_piece_hash_str, calculated_piece_hash_str
-        );
+            // verify piece hash
+            let actual_piece_hash_str = piece_hashes_str
+                .get(piece_index as usize)
+                .unwrap_or_else(|| panic!("Could not get piece hash at index {}", piece_index))
+                .clone();
 
-        // write the piece to file
-        file.write_all_at(&piece.piece_data, (piece_index * piece_length).into())
-            .expect(
-                format!(
-                    "Could not write piece {} to the file at offset {}",
-                    piece_index,
-                    piece_index * piece_length
-                )
-                .as_str(),
-            );
-        println!("Piece {} written to file at offset {}", piece_index, piece_index * piece_length);
+            let calculated_piece_hash = piece.get_hash();
+            let calculated_piece_hash_str = to_hex_string(&calculated_piece_hash);
+
+            if calculated_piece_hash_str != actual_piece_hash_str {
+                return Err(format!(
+                    "piece {} hash does not match, expected {} but calculated {}",
+                    piece_index, actual_piece_hash_str, calculated_piece_hash_str
+                ));
+            }
+
+            // write the piece to file
+            file.write_all_at(&piece.piece_data, offset)
+                .unwrap_or_else(|_| {
+                    panic!(
+                        "Could not write piece {} to the file at offset {}",
+                        piece_index, offset
+                    )
+                });
+            println!("Piece {} written to file at offset {}", piece_index, offset);
+            swarm.piece_downloaded(actual_piece_length.into());
+            piece_index += 1;
+        }
+        Ok(())
+    });
+    // the announcer is waited for, it tells the tracker the download is over
+    let _ = finished.send(downloaded.is_ok());
+    announcer.await.expect("the announcer does not panic");
+    if let Err(err) = downloaded {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
     }
 
     println!(
@@ -141,3 +430,286 @@ pub async fn execute(args: &DownloadArgs) {
             .expect("Failed PathBuf to str conversion for args.output")
     );
 }
+
+/// Connects to `peer` and gets it ready to send pieces
+fn connect(peer: &PeerAddress, info_hash: &[u8]) -> std::io::Result<Connection> {
+    // Setup connection with the peer
+    let mut connection = Connection::new(peer.to_string())?;
+
+    // Perform handshake
+    let res_peer_id = connection.handshake(info_hash.to_vec(), peer_id::local())?;
+    println!("res_peer_id: {}", to_hex_string(&res_peer_id));
+
+    // Send and wait for peer messages
+    // 1. Wait for `bitfield`
+    connection.wait(PeerMessageType::BitField)?;
+
+    // 2. Send an `interested` message
+    connection.send(PeerMessageType::Interested, vec![])?;
+
+    // 3. Wait until `unchoke` is received
+    connection.wait(PeerMessageType::Unchoke)?;
+
+    Ok(connection)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    use super::*;
+
+    /// Tracker on a local port that answers every announce with the compact `peers`, the
+    /// path and query of each announce goes to the receiver
+    fn mock_tracker(peers: &'static [u8]) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/announce", listener.local_addr().unwrap());
+        let (paths, received) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 4096];
+                let read = stream.read(&mut request).unwrap();
+                let request = String::from_utf8_lossy(&request[..read]);
+                let path = request.split_whitespace().nth(1).unwrap_or_default();
+                paths.send(path.to_string()).unwrap();
+
+                let mut body = format!("d8:intervali1800e5:peers{}:", peers.len()).into_bytes();
+                body.extend(peers);
+                body.push(b'e');
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                stream.write_all(head.as_bytes()).unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    /// The value of the query parameter `name` of an announce `path`
+    fn query_param<'a>(path: &'a str, name: &str) -> Option<&'a str> {
+        path.split(['?', '&'])
+            .find_map(|param| param.strip_prefix(name)?.strip_prefix('='))
+    }
+
+    /// The event of each announce of `paths`, "none" for the regular ones, with its
+    /// numwant
+    fn events(paths: &mpsc::Receiver<String>) -> Vec<(String, String)> {
+        paths
+            .try_iter()
+            .map(|path| {
+                let event = query_param(&path, "event").unwrap_or("none");
+                let num_want = query_param(&path, "numwant").unwrap_or_default();
+                (event.to_string(), num_want.to_string())
+            })
+            .collect()
+    }
+
+    /// An announce of a download with `left` bytes to go
+    fn request(left: u64, event: Event) -> AnnounceRequest {
+        AnnounceRequest {
+            info_hash: [1; 20],
+            peer_id: [2; 20],
+            downloaded: 0,
+            left,
+            uploaded: 0,
+            event,
+            key: 0,
+            num_want: DEFAULT_NUM_WANT,
+            port: 6881,
+        }
+    }
+
+    /// Events the tracker gets for a download that had `left` bytes to go when it started
+    /// and got `downloaded` of them before it was over, `is_complete` or not
+    async fn announced_events(left: u64, downloaded: u64, is_complete: bool) -> Vec<String> {
+        let (url, paths) = mock_tracker(&[]);
+        let mut trackers = TrackerList::new(vec![vec![url]]);
+        let mut request = request(left, Event::Started);
+        let (tracker, response) =
+            TrackerResponse::from_request(&request, &mut trackers, None, false).await;
+        request.event = Event::None;
+
+        let swarm = Arc::new(Swarm::new(response.peers.clone(), left));
+        swarm.piece_downloaded(downloaded);
+        let announcer = Announcer {
+            num_want: request.num_want,
+            request,
+            trackers,
+            tracker,
+            preferred: None,
+            verbose: false,
+            swarm,
+            timer: announce_timer(&response),
+        };
+        let (finished, finished_receiver) = oneshot::channel();
+        finished.send(is_complete).unwrap();
+        announcer.run(finished_receiver).await;
+
+        events(&paths).into_iter().map(|(event, _)| event).collect()
+    }
+
+    #[tokio::test]
+    async fn a_finished_download_is_completed_then_stopped() {
+        assert_eq!(
+            announced_events(100, 100, true).await,
+            ["started", "completed", "stopped"]
+        );
+    }
+
+    #[tokio::test]
+    async fn a_failed_download_is_only_stopped() {
+        assert_eq!(
+            announced_events(100, 40, false).await,
+            ["started", "stopped"]
+        );
+    }
+
+    #[tokio::test]
+    async fn nothing_left_to_download_is_not_completed() {
+        assert_eq!(announced_events(0, 0, true).await, ["started", "stopped"]);
+    }
+
+    fn peer(address: &str) -> PeerAddress {
+        PeerAddress::Ip(address.parse().unwrap())
+    }
+
+    #[test]
+    fn peers_are_taken_in_order_and_not_added_back() {
+        let swarm = Swarm::new(vec![peer("10.0.0.1:80"), peer("10.0.0.2:80")], 0);
+        assert_eq!(swarm.take_peer(), Some(peer("10.0.0.1:80")));
+
+        // the one taken and the one waiting are both known
+        let added = swarm.merge_peers(vec![
+            peer("10.0.0.1:80"),
+            peer("10.0.0.2:80"),
+            peer("10.0.0.3:80"),
+        ]);
+        assert_eq!(added, 1);
+        assert_eq!(swarm.peers_left(), 2);
+        assert_eq!(swarm.take_peer(), Some(peer("10.0.0.2:80")));
+        assert_eq!(swarm.take_peer(), Some(peer("10.0.0.3:80")));
+        assert_eq!(swarm.take_peer(), None);
+    }
+
+    /// The announces of an announcer whose last announce was `ago`, with a min interval
+    /// of one minute and an interval of half an hour, when it has `waiting` peers left to
+    /// try, and the peers it has left after
+    async fn reannounce(waiting: usize, ago: Duration) -> (Vec<(String, String)>, usize) {
+        // one peer the announcer knows and one it only knows with 100 or more
+        let (url, paths) = mock_tracker(&[10, 0, 0, 1, 0, 80, 10, 0, 0, 100, 0, 80]);
+        let peers = (1..=waiting)
+            .map(|index| peer(&format!("10.0.{}.{}:80", index / 256, index % 256)))
+            .collect();
+        let swarm = Arc::new(Swarm::new(peers, 100));
+        let mut announcer = Announcer {
+            request: request(100, Event::None),
+            num_want: DEFAULT_NUM_WANT,
+            trackers: TrackerList::new(vec![vec![url.clone()]]),
+            tracker: url,
+            preferred: None,
+            verbose: false,
+            swarm: Arc::clone(&swarm),
+            timer: AnnounceTimer::new(
+                Instant::now() - ago,
+                Duration::from_secs(1800),
+                Some(Duration::from_secs(60)),
+                Duration::ZERO,
+            ),
+        };
+        announcer.reannounce_if_due().await;
+
+        (events(&paths), swarm.peers_left())
+    }
+
+    fn minutes(count: u64) -> Duration {
+        Duration::from_secs(count * 60)
+    }
+
+    #[tokio::test]
+    async fn low_peers_bring_the_announce_forward_and_merge_the_new_ones() {
+        let regular = vec![("none".to_string(), DEFAULT_NUM_WANT.to_string())];
+        assert_eq!(reannounce(1, minutes(2)).await, (regular, 2));
+    }
+
+    #[tokio::test]
+    async fn enough_peers_wait_for_the_interval() {
+        assert_eq!(reannounce(LOW_PEERS, minutes(2)).await, (vec![], LOW_PEERS));
+    }
+
+    #[tokio::test]
+    async fn saturated_peers_ask_for_none() {
+        let below = vec![("none".to_string(), DEFAULT_NUM_WANT.to_string())];
+        assert_eq!(
+            reannounce(SATURATED_PEERS - 1, minutes(31)).await,
+            (below, SATURATED_PEERS - 1)
+        );
+
+        let saturated = vec![("none".to_string(), "0".to_string())];
+        assert_eq!(
+            reannounce(SATURATED_PEERS, minutes(31)).await,
+            (saturated, SATURATED_PEERS)
+        );
+    }
+
+    #[tokio::test]
+    async fn completed_and_stopped_ask_for_no_peers() {
+        let (url, paths) = mock_tracker(&[]);
+        let mut announcer = Announcer {
+            request: request(100, Event::None),
+            num_want: DEFAULT_NUM_WANT,
+            trackers: TrackerList::new(vec![vec![url.clone()]]),
+            tracker: url,
+            preferred: None,
+            verbose: false,
+            swarm: Arc::new(Swarm::new(vec![], 100)),
+            timer: AnnounceTimer::new(Instant::now(), minutes(30), None, Duration::ZERO),
+        };
+        announcer.send_event(Event::Completed).await;
+        announcer.send_event(Event::Stopped).await;
+
+        let events = events(&paths);
+        assert_eq!(
+            events,
+            [
+                ("completed".to_string(), "0".to_string()),
+                ("stopped".to_string(), "0".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn udp_re_announces_reuse_the_connection_id() {
+        // a tracker that counts the connects and answers announces with no peers
+        let mock = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let url = format!("udp://{}/announce", mock.local_addr().unwrap());
+        let tracker = std::thread::spawn(move || {
+            let mut connects = 0;
+            for _ in 0..3 {
+                let mut packet = [0; 2048];
+                let (_, client) = mock.recv_from(&mut packet).unwrap();
+                let (action, transaction) = (&packet[8..12], &packet[12..16]);
+                let answer = match action {
+                    [0, 0, 0, 0] => {
+                        connects += 1;
+                        [b"\0\0\0\0", transaction, &42u64.to_be_bytes()].concat()
+                    }
+                    _ => [b"\0\0\0\x01", transaction, &[0, 0, 7, 8], &[0; 8]].concat(),
+                };
+                mock.send_to(&answer, client).unwrap();
+            }
+            connects
+        });
+
+        let request = request(100, Event::None);
+        for _ in 0..2 {
+            let response = TrackerResponse::announce_to(&url, &request).await.unwrap();
+            assert_eq!(response.interval, 1800);
+        }
+        assert_eq!(tracker.join().unwrap(), 1);
+    }
+}
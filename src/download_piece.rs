@@ -4,13 +4,24 @@ use std::path::PathBuf;
 
 use clap::Args;
 
-use crate::{to_hex_string, Connection, PeerMessageType, TorrentMetadata, TrackerResponse};
+use crate::fetch::FetchArgs;
+use crate::peer_id;
+use crate::peers::FamilyArgs;
+use crate::torrent::TorrentContent;
+use crate::{
+    read_usable_torrent, shuffled_trackers, to_hex_string, Connection, PeerMessageType,
+    TrackerResponse,
+};
 
 #[allow(dead_code)]
 #[derive(Args, Debug)]
 pub struct DownloadPieceArgs {
     /// torrent file path
     torrent_file_path: PathBuf,
+    #[command(flatten)]
+    fetch: FetchArgs,
+    #[command(flatten)]
+    family: FamilyArgs,
     /// output file path
     #[clap(short, long)]
     output: PathBuf,
@@ -23,10 +34,25 @@ pub async fn execute(args: &DownloadPieceArgs) {
     // println!("args: {:?}", args);
 
     // Get torrent metadata
-    let torrent_metadata = TorrentMetadata::from_file(args.torrent_file_path.clone());
+    let torrent_metadata = read_usable_torrent(&args.torrent_file_path, &args.fetch);
+    // checked before the tracker is asked for peers
+    let TorrentContent::SingleFile {
+        length: torrent_file_length,
+    } = torrent_metadata.info.content()
+    else {
+        eprintln!("error: only single file torrents can be downloaded");
+        std::process::exit(1);
+    };
 
     // get torrent tracker
-    let tracker_response = TrackerResponse::from(&torrent_metadata).await;
+    let mut trackers = shuffled_trackers(&torrent_metadata);
+    let tracker_response = TrackerResponse::from(
+        &torrent_metadata,
+        &mut trackers,
+        args.family.preferred(),
+        false,
+    )
+    .await;
 
     // get peers
     let peers = tracker_response.get_peers();
@@ -35,58 +61,62 @@ pub async fn execute(args: &DownloadPieceArgs) {
     let peer = peers.get(1).expect("Could not fetch peer at that index");
 
     // Setup connection with the peer
-    let mut connection = Connection::new(peer.clone());
-
-    let peer_id = "00112233445566778899".to_string();
+    let mut connection = Connection::new(peer.clone()).expect("tcp connection failed!");
 
-    let info_hash = torrent_metadata.info.hash_bytes().to_vec();
+    let info_hash = torrent_metadata.info_hash().to_vec();
 
-    let torrent_file_length = torrent_metadata
-        .info
-        .length
-        .expect("Could not get length of torrent file");
     println!("torrent_file_length: {}", torrent_file_length);
     let pieces_count = torrent_metadata.info.get_pieces_count() as u32;
     println!("pieces_count: {}", pieces_count);
     let piece_index = args.piece as u32;
     println!("piece_index: {}", piece_index);
-    let piece_length = torrent_metadata.info.piece_length as u32;
+    let piece_length = torrent_metadata.info.piece_length;
     println!("piece_length: {}", piece_length);
 
     // check if the piece index exists
+    let geometry = torrent_metadata.info.piece_geometry();
     assert!(
-        piece_index < pieces_count,
+        (piece_index as usize) < geometry.count().min(pieces_count as usize),
         "This piece does not exists, max pieces: {}",
         pieces_count
     );
 
     // perform handshake
     // let res_peer_id =
-    connection.handshake(info_hash, peer_id);
+    connection
+        .handshake(info_hash, peer_id::local())
+        .expect("handshake failed");
     // println!("res_peer_id: {}", to_hex_string(&res_peer_id));
 
     // Send and wait for peer messages
     // 1. Wait for `bitfield`
-    connection.wait(PeerMessageType::BitField);
+    connection
+        .wait(PeerMessageType::BitField)
+        .expect("Could not receive bitfield");
 
     // 2. Send an `interested` message
-    connection.send(PeerMessageType::Interested, vec![]);
+    connection
+        .send(PeerMessageType::Interested, vec![])
+        .expect("Could not send interested");
 
     // 3. Wait until `unchoke` is received
-    connection.wait(PeerMessageType::Unchoke);
-
-    // calculate actual piece length
-    let is_last_piece = torrent_file_length - piece_index * piece_length < piece_length;
-    let actual_piece_length = if is_last_piece {
-        torrent_file_length - piece_index * piece_length
-    } else {
-        piece_length
-    };
+    connection
+        .wait(PeerMessageType::Unchoke)
+        .expect("Could not receive unchoke");
+
+    // calculate actual piece length, the last piece is shorter
+    let (_, actual_piece_length) = geometry
+        .piece(piece_index as usize)
+        .expect("the piece index was checked");
+    // at most a piece length, which is a u32
+    let actual_piece_length = actual_piece_length as u32;
     println!("actual_piece_length: {}", actual_piece_length);
 
     // download a piece
     // let piece = connection.download_piece(piece_index, actual_piece_length);
-    let piece = connection.download_piece_pipelined(5, piece_index, actual_piece_length);
+    let piece = connection
+        .download_piece_pipelined(5, piece_index, actual_piece_length)
+        .expect("Could not download the piece");
 
     // verify piece hash
     let piece_hashes_str = torrent_metadata.info.get_piece_hashes_str();
@@ -96,7 +126,8 @@ pub async fn execute(args: &DownloadPieceArgs) {
 
     let actual_piece_hash_str = piece_hashes_str
         .get(piece_index as usize)
-        .expect(format!("Could not get piece hash at piece index {}", piece_index).as_str()).clone();
+        .unwrap_or_else(|| panic!("Could not get piece hash at piece index {}", piece_index))
+        .clone();
     println!("actual_piece_hash_str: {}", actual_piece_hash_str);
 
     let calculated_piece_hash = piece.get_hash();
@@ -119,15 +150,14 @@ pub async fn execute(args: &DownloadPieceArgs) {
     fs::create_dir_all(parent_directory).expect("Failed to recursively create parent directories");
 
     // Create file if not exists
-    let mut file = fs::File::create(args.output.clone()).expect(
-        format!(
+    let mut file = fs::File::create(args.output.clone()).unwrap_or_else(|_| {
+        panic!(
             "Could not create file {}",
             args.output
                 .to_str()
                 .expect("Failed Pathbuf to str conversion")
         )
-        .as_str(),
-    );
+    });
 
     // write the piece to the output file
     file.write_all(&piece.piece_data)
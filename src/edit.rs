@@ -0,0 +1,161 @@
+use std::cmp::Ordering;
+use std::path::PathBuf;
+
+use clap::Args;
+use serde_bytes::ByteBuf;
+
+use crate::fetch::{self, FetchArgs};
+use crate::torrent::TorrentMetadata;
+use crate::{fail, to_hex_string};
+
+#[derive(Args, Debug)]
+pub struct EditArgs {
+    /// torrent file path
+    torrent_file_path: PathBuf,
+    /// new tracker url
+    #[arg(long, conflicts_with_all = ["add_tracker", "remove_tracker"])]
+    announce: Option<String>,
+    /// remove the backup trackers of "announce-list"
+    #[arg(long)]
+    clear_announce_list: bool,
+    /// tracker url to add to "announce-list", can be repeated
+    #[arg(long)]
+    add_tracker: Vec<String>,
+    /// tier (from 1) the added trackers go to, the last one by default
+    #[arg(long, requires = "add_tracker", value_parser = clap::value_parser!(u32).range(1..))]
+    tier: Option<u32>,
+    /// tracker url to remove from "announce" and "announce-list", can be repeated
+    #[arg(long)]
+    remove_tracker: Vec<String>,
+    /// new "source" of the info dictionary, "" removes it. This changes the info
+    /// hash, the info dictionary is encoded again
+    #[arg(long)]
+    source: Option<String>,
+    /// where the edited torrent is written
+    #[arg(short, long)]
+    output: PathBuf,
+    /// write the torrent even when nothing changed
+    #[arg(long)]
+    force: bool,
+}
+
+/// Adds and removes the trackers of `args` in the tiers of "announce-list", which is
+/// made from "announce" when the torrent doesn't have one
+fn edit_trackers(args: &EditArgs, torrent_metadata: &mut TorrentMetadata) {
+    let mut tiers = match (&torrent_metadata.announce_list, &torrent_metadata.announce) {
+        (Some(tiers), _) => tiers.clone(),
+        (None, Some(announce)) => vec![vec![announce.clone()]],
+        (None, None) => Vec::new(),
+    };
+
+    for url in &args.remove_tracker {
+        let found = tiers.iter().any(|tier| tier.contains(url))
+            || torrent_metadata.announce.as_ref() == Some(url);
+        if !found {
+            fail(&format!("{} is not a tracker of the torrent", url));
+        }
+        tiers
+            .iter_mut()
+            .for_each(|tier| tier.retain(|tracker| tracker != url));
+        tiers.retain(|tier| !tier.is_empty());
+    }
+
+    for url in &args.add_tracker {
+        if tiers.iter().any(|tier| tier.contains(url)) {
+            fail(&format!("{} is already a tracker of the torrent", url));
+        }
+        let index = match args.tier {
+            Some(tier) => tier as usize - 1,
+            None => tiers.len().saturating_sub(1),
+        };
+        match index.cmp(&tiers.len()) {
+            Ordering::Less => tiers[index].push(url.clone()),
+            Ordering::Equal => tiers.push(vec![url.clone()]),
+            Ordering::Greater => fail(&format!(
+                "tier {} doesn't exist, the torrent has {} tiers",
+                index + 1,
+                tiers.len()
+            )),
+        }
+    }
+
+    // clients that don't know "announce-list" only use "announce"
+    torrent_metadata.announce = tiers.first().map(|tier| tier[0].clone());
+    if tiers.is_empty() {
+        eprintln!(
+            "warning: the torrent has no trackers left, its peers can only come from the DHT"
+        );
+        torrent_metadata.announce_list = None;
+    } else {
+        torrent_metadata.announce_list = Some(tiers);
+    }
+}
+
+/// For command: "edit"
+pub fn execute(args: &EditArgs) {
+    let path = fetch::source_name(&args.torrent_file_path);
+    let file_contents = fetch::read_torrent_input(&args.torrent_file_path, &FetchArgs::default());
+    let mut torrent_metadata = TorrentMetadata::from_bytes(&file_contents)
+        .unwrap_or_else(|err| fail(&format!("{} is not a valid torrent: {}", path, err)));
+    for skipped in &torrent_metadata.skipped {
+        eprintln!("warning: {}", skipped);
+    }
+    let edits_trackers = !args.add_tracker.is_empty() || !args.remove_tracker.is_empty();
+    if args.announce.is_none()
+        && !args.clear_announce_list
+        && !edits_trackers
+        && args.source.is_none()
+    {
+        fail("nothing to edit, give --announce, --add-tracker, --remove-tracker, --clear-announce-list or --source");
+    }
+
+    let old_announce = torrent_metadata.announce.clone();
+    if let Some(announce) = &args.announce {
+        torrent_metadata.announce = Some(announce.clone());
+    }
+    if args.clear_announce_list {
+        torrent_metadata.announce_list = None;
+    }
+    if edits_trackers {
+        edit_trackers(args, &mut torrent_metadata);
+    }
+
+    let old_info_hash = torrent_metadata.info_hash();
+    let source = args
+        .source
+        .clone()
+        .filter(|source| !source.is_empty())
+        .map(ByteBuf::from);
+    if args.source.is_some() && source != torrent_metadata.info.source {
+        torrent_metadata.info.source = source;
+        torrent_metadata.reencode_info();
+    }
+
+    // only the top level keys are encoded again, the info dictionary is copied as it is
+    // unless its source changed
+    let edited = torrent_metadata.to_bytes();
+    if edited == file_contents && !args.force {
+        fail(&format!(
+            "{} would not change, use --force to write it anyway",
+            path
+        ));
+    }
+    if let Err(err) = std::fs::write(&args.output, &edited) {
+        fail(&format!(
+            "could not write {}: {}",
+            args.output.display(),
+            err
+        ));
+    }
+
+    let tracker = |announce: &Option<String>| announce.clone().unwrap_or("(none)".to_string());
+    println!("Old Tracker URL: {}", tracker(&old_announce));
+    println!("New Tracker URL: {}", tracker(&torrent_metadata.announce));
+    if torrent_metadata.info_hash() != old_info_hash {
+        println!("Old Info Hash: {}", to_hex_string(&old_info_hash.to_vec()));
+        println!(
+            "New Info Hash: {}",
+            to_hex_string(&torrent_metadata.info_hash().to_vec())
+        );
+    }
+}
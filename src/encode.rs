@@ -0,0 +1,140 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context};
+use clap::Args;
+
+use crate::bencode::{ser, Integer, Value};
+use crate::{from_base64_string, from_hex_string};
+
+/// Key of the single-entry json object used to represent binary byte strings,
+/// e.g. `{"$hex": "0102ff"}`
+pub const HEX_TAG: &str = "$hex";
+
+/// Tags of the lossless representation written by `decode --tagged`,
+/// `{"b64": "..."}` for byte strings that are not UTF-8 and `{"int": "..."}`
+/// for integers that don't fit in a json number
+pub const BASE64_TAG: &str = "b64";
+pub const INT_TAG: &str = "int";
+
+/// With `--tagged`, dictionary keys starting with this are base64-encoded bytes
+pub const BASE64_KEY_PREFIX: &str = "b64:";
+
+/// Dictionary key in its raw form, `tagged` decodes keys written with `BASE64_KEY_PREFIX`
+fn transform_json_key(key: &str, path: &str, tagged: bool) -> anyhow::Result<Vec<u8>> {
+    match key.strip_prefix(BASE64_KEY_PREFIX) {
+        Some(encoded) if tagged => from_base64_string(encoded)
+            .ok_or_else(|| anyhow!("{}: invalid base64 key {:?}", path, key)),
+        _ => Ok(key.as_bytes().to_vec()),
+    }
+}
+
+/// Converts json into a bencode value, `path` is only used for error messages.
+/// With `tagged` the objects written by `decode --tagged` are turned back into
+/// byte strings and integers
+pub fn transform_json_to_bencode(
+    json: &serde_json::Value,
+    path: &str,
+    tagged: bool,
+) -> anyhow::Result<Value> {
+    match json {
+        serde_json::Value::String(s) => Ok(Value::Bytes(s.as_bytes().to_vec())),
+        serde_json::Value::Number(n) => n
+            .as_i64()
+            .map(Integer::I64)
+            .or_else(|| n.as_u64().map(Integer::U64))
+            .map(Value::Int)
+            .ok_or_else(|| anyhow!("{}: only integers can be encoded, found {}", path, n)),
+        serde_json::Value::Array(a) => {
+            let values = a
+                .iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    transform_json_to_bencode(value, &format!("{}[{}]", path, index), tagged)
+                })
+                .collect::<anyhow::Result<Vec<Value>>>()?;
+            Ok(Value::List(values))
+        }
+        serde_json::Value::Object(o) => {
+            // {"$hex": "..."} represents a binary byte string
+            if let (1, Some(hex)) = (o.len(), o.get(HEX_TAG)) {
+                let hex = hex
+                    .as_str()
+                    .and_then(from_hex_string)
+                    .ok_or_else(|| anyhow!("{}: invalid {} value", path, HEX_TAG))?;
+                return Ok(Value::Bytes(hex));
+            }
+            if let (true, 1, Some(b64)) = (tagged, o.len(), o.get(BASE64_TAG)) {
+                let bytes = b64
+                    .as_str()
+                    .and_then(from_base64_string)
+                    .ok_or_else(|| anyhow!("{}: invalid {} value", path, BASE64_TAG))?;
+                return Ok(Value::Bytes(bytes));
+            }
+            if let (true, 1, Some(int)) = (tagged, o.len(), o.get(INT_TAG)) {
+                let integer = int
+                    .as_str()
+                    .and_then(Integer::parse)
+                    .ok_or_else(|| anyhow!("{}: invalid {} value", path, INT_TAG))?;
+                return Ok(Value::Int(integer));
+            }
+
+            let mut entries = o
+                .iter()
+                .map(|(key, value)| {
+                    let key_path = format!("{}.{}", path, key);
+                    let value = transform_json_to_bencode(value, &key_path, tagged)?;
+                    Ok((transform_json_key(key, &key_path, tagged)?, value))
+                })
+                .collect::<anyhow::Result<Vec<(Vec<u8>, Value)>>>()?;
+            // bencode dictionaries are sorted by the raw bytes of the keys
+            entries.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+            Ok(Value::Dict(entries))
+        }
+        serde_json::Value::Bool(_) | serde_json::Value::Null => {
+            bail!("{}: bencode has no representation for {}", path, json)
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct EncodeArgs {
+    /// json value (String)
+    #[clap(required_unless_present = "file")]
+    json_value: Option<String>,
+    /// read the json value from a file instead
+    #[clap(long, conflicts_with = "json_value")]
+    file: Option<PathBuf>,
+    /// read the lossless representation written by `decode --tagged`
+    #[clap(long)]
+    tagged: bool,
+}
+
+fn encode(args: &EncodeArgs) -> anyhow::Result<Vec<u8>> {
+    let json_str = match &args.file {
+        Some(file_path) => std::fs::read_to_string(file_path)
+            .with_context(|| format!("Could not read {}", file_path.display()))?,
+        None => args.json_value.clone().unwrap_or_default(),
+    };
+
+    let json: serde_json::Value = serde_json::from_str(&json_str).context("Invalid json")?;
+    let value = transform_json_to_bencode(&json, "$", args.tagged)?;
+
+    Ok(ser::to_bytes(&value)?)
+}
+
+/// For command: "encode"
+pub fn execute(args: &EncodeArgs) {
+    let encoded = match encode(args) {
+        Ok(encoded) => encoded,
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            std::process::exit(1);
+        }
+    };
+
+    // raw bencode so that the output can be redirected into a file
+    std::io::stdout()
+        .write_all(&encoded)
+        .expect("Could not write to stdout");
+}
@@ -0,0 +1,161 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use thiserror::Error;
+
+use crate::bencode::{events, DecodeOptions};
+use crate::fail;
+use crate::torrent::{LimitError, DEFAULT_MAX_PIECES};
+
+/// Torrents fetched over http are at most this many bytes, unless `--max-size` is given
+const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Redirects followed before giving up, tracker sites often send one to a mirror
+const MAX_REDIRECTS: usize = 10;
+
+/// Options of the commands that take a torrent file path, "-" or an http(s) url
+#[derive(Args, Debug)]
+pub struct FetchArgs {
+    /// write the torrent there as it was read, like one fetched from a url
+    #[arg(long)]
+    save: Option<PathBuf>,
+    /// most bytes a torrent fetched from a url can have
+    #[arg(long, default_value_t = DEFAULT_MAX_SIZE, value_parser = clap::value_parser!(u64).range(1..))]
+    max_size: u64,
+    /// most pieces a torrent can have, ones with more are refused before anything is
+    /// allocated for their pieces
+    #[arg(long, default_value_t = DEFAULT_MAX_PIECES, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_pieces: u64,
+}
+
+impl Default for FetchArgs {
+    fn default() -> Self {
+        Self {
+            save: None,
+            max_size: DEFAULT_MAX_SIZE,
+            max_pieces: DEFAULT_MAX_PIECES,
+        }
+    }
+}
+
+/// Why a torrent could not be fetched, the server's answer isn't a torrent or the
+/// connection failed
+#[derive(Error, Debug)]
+enum FetchError {
+    #[error("network error: {0}")]
+    Network(#[from] reqwest::Error),
+    #[error("network error: {0}")]
+    Read(#[from] std::io::Error),
+    #[error("the server answered {0}")]
+    Status(reqwest::StatusCode),
+    #[error("the response is larger than {0} bytes (see --max-size)")]
+    TooLarge(u64),
+    #[error(
+        "the response is not a torrent, it starts with {0:?} instead of 'd' (Content-Type is {1})"
+    )]
+    NotATorrent(String, String),
+    #[error("the response is not valid bencode: {0}")]
+    Bencode(String),
+}
+
+/// The url when the torrent path is one, "http://" or "https://"
+fn url_of(source: &Path) -> Option<&str> {
+    source
+        .to_str()
+        .filter(|source| source.starts_with("http://") || source.starts_with("https://"))
+}
+
+/// Fetches `url` with a blocking client, which can't run on the runtime's threads
+fn fetch(url: &str, max_size: u64) -> Result<Vec<u8>, FetchError> {
+    let url = url.to_string();
+    let request = move || -> Result<Vec<u8>, FetchError> {
+        let client = reqwest::blocking::Client::builder()
+            .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
+            .build()?;
+        let response = client.get(url).send()?;
+        if !response.status().is_success() {
+            return Err(FetchError::Status(response.status()));
+        }
+        if response
+            .content_length()
+            .is_some_and(|length| length > max_size)
+        {
+            return Err(FetchError::TooLarge(max_size));
+        }
+
+        // the header is only shown in errors, what the server calls it is often wrong
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("not given")
+            .to_string();
+
+        // a missing or wrong Content-Length can't make it read more than the limit
+        let mut body = Vec::new();
+        response.take(max_size + 1).read_to_end(&mut body)?;
+        if body.len() as u64 > max_size {
+            return Err(FetchError::TooLarge(max_size));
+        }
+
+        if body.first() != Some(&b'd') {
+            let start = String::from_utf8_lossy(&body[..body.len().min(16)]).into_owned();
+            return Err(FetchError::NotATorrent(start, content_type));
+        }
+        events::parse_events(&body, &DecodeOptions::default(), |_| {})
+            .map_err(|err| FetchError::Bencode(err.to_string()))?;
+        Ok(body)
+    };
+
+    std::thread::spawn(request)
+        .join()
+        .expect("the request thread does not panic")
+}
+
+/// The error with the option that raises the limit, if it has one
+pub fn limit_message(err: &LimitError) -> String {
+    match err {
+        LimitError::TooManyPieces { .. } => format!("{} (see --max-pieces)", err),
+        _ => err.to_string(),
+    }
+}
+
+/// What errors call the source of a torrent: "stdin", the url or the path
+pub fn source_name(source: &Path) -> String {
+    match url_of(source) {
+        _ if source == Path::new("-") => "stdin".to_string(),
+        Some(url) => url.to_string(),
+        None => source.display().to_string(),
+    }
+}
+
+/// The bytes of a torrent file, of stdin for "-" or of the torrent at an http(s) url,
+/// written to `--save` when it is given. Every command that reads a torrent goes
+/// through here
+pub fn read_torrent_input(source: &Path, args: &FetchArgs) -> Vec<u8> {
+    let name = source_name(source);
+    let bytes = match url_of(source) {
+        _ if source == Path::new("-") => {
+            let mut bytes = Vec::new();
+            if let Err(err) = std::io::stdin().lock().read_to_end(&mut bytes) {
+                fail(&format!("could not read stdin: {}", err));
+            }
+            bytes
+        }
+        Some(url) => fetch(url, args.max_size)
+            .unwrap_or_else(|err| fail(&format!("could not fetch {}: {}", url, err))),
+        None => std::fs::read(source)
+            .unwrap_or_else(|err| fail(&format!("could not read {}: {}", name, err))),
+    };
+    if bytes.is_empty() {
+        fail(&format!("{} is empty", name));
+    }
+
+    if let Some(save) = &args.save {
+        if let Err(err) = std::fs::write(save, &bytes) {
+            fail(&format!("could not write {}: {}", save.display(), err));
+        }
+    }
+    bytes
+}
@@ -1,6 +1,7 @@
 use clap::Args;
 
-use crate::{to_hex_string, Connection, TorrentMetadata};
+use crate::peer_id;
+use crate::{read_torrent_file, to_hex_string, Connection};
 use std::path::PathBuf;
 
 #[derive(Args, Debug)]
@@ -9,17 +10,24 @@ pub struct HandshakeArgs {
     torrent_file_path: PathBuf,
     /// peer address (String) <IP_ADDR>:<PORT>
     peer_address: String,
+    /// also print the client the peer id names, if it is a known one
+    #[arg(short, long)]
+    verbose: bool,
 }
 
 /// For command: "handshake"
 pub fn execute(args: &HandshakeArgs) {
-    let torrent_metadata = TorrentMetadata::from_file(args.torrent_file_path.clone());
+    let torrent_metadata = read_torrent_file(args.torrent_file_path.clone());
 
-    let mut connection = Connection::new(args.peer_address.clone());
-    let res_peer_id = connection.handshake(
-        torrent_metadata.info.hash_bytes().to_vec(),
-        "00112233445566778899".to_string(),
-    );
+    let mut connection =
+        Connection::new(args.peer_address.clone()).expect("tcp connection failed!");
+    let res_peer_id = connection
+        .handshake(torrent_metadata.info_hash().to_vec(), peer_id::local())
+        .expect("handshake failed");
 
     println!("Peer ID: {}", to_hex_string(&res_peer_id));
+    if args.verbose {
+        let client = peer_id::client_name(&res_peer_id);
+        println!("Client: {}", client.as_deref().unwrap_or("unknown"));
+    }
 }
@@ -0,0 +1,99 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+
+use crate::fetch::FetchArgs;
+use crate::tracker::urlencode_bytes;
+use crate::{fail, read_torrent_source, to_base32_string, to_hex_string};
+
+/// How the info hash is printed
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum HashFormat {
+    /// lowercase hex string
+    #[default]
+    Hex,
+    /// base32, as used by magnet links
+    Base32,
+    /// percent-encoded, as sent in tracker urls (a v2 hash is truncated to 20 bytes)
+    Urlencoded,
+    /// the bytes without a newline, for piping into other tools
+    Raw,
+}
+
+/// Which info hash is printed
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum HashVersion {
+    /// SHA-1, of v1 and hybrid torrents
+    #[default]
+    #[value(name = "1")]
+    V1,
+    /// SHA-256, of v2 and hybrid torrents
+    #[value(name = "2")]
+    V2,
+    /// both, one per line
+    Both,
+}
+
+#[derive(Args, Debug)]
+pub struct HashArgs {
+    /// torrent file path, or an http(s) url to fetch it from
+    torrent_file_path: PathBuf,
+    #[command(flatten)]
+    fetch: FetchArgs,
+    /// encoding of the info hash
+    #[arg(long, value_enum, default_value_t = HashFormat::Hex)]
+    format: HashFormat,
+    /// info hash of BitTorrent v1, v2 (BEP 52) or both
+    #[arg(long, value_enum, default_value_t = HashVersion::V1)]
+    version: HashVersion,
+}
+
+/// Prints the hash in `format`, after "v1: " or "v2: " when both are printed
+fn print_hash(info_hash: &[u8], format: HashFormat, label: Option<&str>) {
+    let label = label
+        .map(|label| format!("{}: ", label))
+        .unwrap_or_default();
+    match format {
+        HashFormat::Hex => println!("{}{}", label, to_hex_string(&info_hash.to_vec())),
+        HashFormat::Base32 => println!("{}{}", label, to_base32_string(info_hash)),
+        HashFormat::Urlencoded => {
+            let truncated: [u8; 20] = info_hash[..20].try_into().expect("hashes are 20+ bytes");
+            println!("{}{}", label, urlencode_bytes(&truncated))
+        }
+        HashFormat::Raw => {
+            let mut stdout = std::io::stdout();
+            if let Err(err) = stdout.write_all(info_hash).and_then(|_| stdout.flush()) {
+                fail(&format!("could not write the info hash: {}", err));
+            }
+        }
+    }
+}
+
+/// For command: "hash"
+pub fn execute(args: &HashArgs) {
+    let torrent_metadata = read_torrent_source(&args.torrent_file_path, &args.fetch);
+    let info = &torrent_metadata.info;
+
+    if args.version == HashVersion::Both && args.format == HashFormat::Raw {
+        fail("--format raw writes a single hash, use --version 1 or 2");
+    }
+    if args.version != HashVersion::V2 && info.is_v2_only() {
+        fail("the torrent is v2 only, it has no v1 info hash (use --version 2)");
+    }
+    if args.version != HashVersion::V1 && !info.is_v2() {
+        fail("not a v2/hybrid torrent, it has no v2 info hash (use --version 1)");
+    }
+
+    // both are taken over the raw "info" span, so they agree with other clients
+    let labels = match args.version {
+        HashVersion::Both => (Some("v1"), Some("v2")),
+        _ => (None, None),
+    };
+    if args.version != HashVersion::V2 {
+        print_hash(&torrent_metadata.info_hash(), args.format, labels.0);
+    }
+    if args.version != HashVersion::V1 {
+        print_hash(&torrent_metadata.info_hash_v2(), args.format, labels.1);
+    }
+}
@@ -1,33 +1,391 @@
 use std::path::PathBuf;
 
 use clap::Args;
+use serde::Serialize;
+use serde_bytes::ByteBuf;
 
-use crate::TorrentMetadata;
+use crate::fetch::FetchArgs;
+use crate::torrent::{
+    format_size, format_utc_timestamp, parse_md5sum, PieceGeometry, TextEncoding, TorrentContent,
+};
+use crate::{read_strict_torrent, read_torrent_source, to_hex_string};
 
 #[derive(Args, Debug)]
 pub struct InfoArgs {
-    /// torrent file path
+    /// torrent file path, or an http(s) url to fetch it from
     torrent_file_path: PathBuf,
+    #[command(flatten)]
+    fetch: FetchArgs,
+    /// fail instead of warning when the piece hashes don't match the length, the piece
+    /// length or count is out of range, the layouts of a hybrid torrent differ or a path
+    /// is unsafe
+    #[arg(long)]
+    strict: bool,
+    /// print a single json object instead of lines
+    #[arg(long)]
+    json: bool,
+    /// list the padding files (BEP 47) as well, they only align the other files to pieces
+    #[arg(long)]
+    show_padding: bool,
+}
+
+#[derive(Serialize)]
+struct FileOutput {
+    /// components joined with "/"
+    path: String,
+    length: u64,
+    /// hex merkle root of v2 files
+    pieces_root: Option<String>,
+    padding: bool,
+    /// lower case hex MD5 of the whole file
+    md5sum: Option<String>,
+    /// BEP 47 letters as they are in the torrent, unknown ones included
+    attr: Option<String>,
+    /// target of a symlink, components joined with "/"
+    symlink_path: Option<String>,
+    /// where the file starts and ends in the payload, `None` for the files of the v2
+    /// file tree, which each start a piece
+    offset: Option<u64>,
+    end: Option<u64>,
+}
+
+impl FileOutput {
+    /// Like " [xh]", the letters of `attr` that mean something (padding is shown apart)
+    fn attr_marker(&self) -> String {
+        let letters: String = self
+            .attr
+            .iter()
+            .flat_map(|attr| attr.chars())
+            .filter(|letter| matches!(letter, 'x' | 'l' | 'h'))
+            .collect();
+        match letters.is_empty() {
+            true => String::new(),
+            false => format!(" [{}]", letters),
+        }
+    }
+}
+
+/// Everything the command prints, the shape of `--json` is stable so scripts can use it.
+/// Every key is always present, `null` (or an empty list) when the torrent doesn't have it:
+///
+/// - `announce`: tracker url, `null` for trackerless torrents
+/// - `length`: bytes, the sum of the files for a multi-file torrent (without padding files)
+/// - `padding_length`: bytes of padding files, they are hashed but never written to disk
+/// - `info_hash`: hex SHA-1 of the info dictionary, `null` for v2 only torrents
+/// - `meta_version`: 2 for BitTorrent v2 torrents
+/// - `info_hash_v2`: hex SHA-256 of the info dictionary of v2 torrents
+/// - `piece_length`: bytes per piece
+/// - `piece_hashes`: hex SHA-1 of each piece, empty for v2 only torrents
+/// - `comment`, `created_by`, `encoding`, `source` (of private trackers): text, invalid UTF-8 is replaced by U+FFFD
+/// - `md5sum`: hex MD5 of the file of a single file torrent
+/// - `creation_date`: unix timestamp
+/// - `private`: `true` when the info dictionary has "private" set to 1
+/// - `trackers`: tiers of urls from "announce-list"
+/// - `webseeds`, `httpseeds`: urls from "url-list" and "httpseeds"
+/// - `nodes`: DHT bootstrap nodes as "host:port"
+/// - `files`: `{"path": "a/b.txt", "length": 3, "pieces_root": null, "padding": false,
+///   "md5sum": null, "attr": null, "symlink_path": null, "offset": 0, "end": 3}` for each
+///   file (the root is the hex merkle root of v2 files, `attr` the BEP 47 letters like
+///   "x", `offset` and `end` the byte range in the payload, `null` for v2 only torrents),
+///   `null` for single file torrents. Padding files are only listed with `--show-padding`
+///
+/// An "md5sum" that isn't 32 hex characters is `null`, with a warning.
+#[derive(Serialize)]
+struct InfoOutput<'a> {
+    announce: Option<&'a str>,
+    length: u64,
+    padding_length: u64,
+    info_hash: Option<String>,
+    meta_version: Option<i64>,
+    info_hash_v2: Option<String>,
+    piece_length: u32,
+    piece_hashes: Vec<String>,
+    comment: Option<String>,
+    created_by: Option<String>,
+    encoding: Option<String>,
+    source: Option<String>,
+    md5sum: Option<String>,
+    creation_date: Option<i64>,
+    private: Option<bool>,
+    trackers: Option<&'a [Vec<String>]>,
+    webseeds: &'a [String],
+    httpseeds: &'a [String],
+    nodes: Vec<String>,
+    files: Option<Vec<FileOutput>>,
+    /// how the v1 pieces cut the payload, `None` when there are no v1 pieces
+    #[serde(skip)]
+    piece_geometry: Option<PieceGeometry>,
+}
+
+impl InfoOutput<'_> {
+    /// Like "3 × 32 KiB (last piece 25.9 KiB)"
+    fn pieces_summary(&self) -> Option<String> {
+        let geometry = self.piece_geometry?;
+        Some(format!(
+            "{} × {} (last piece {})",
+            geometry.count(),
+            format_size(geometry.piece_length),
+            format_size(geometry.last_piece_size())
+        ))
+    }
+
+    /// The line oriented output, the first lines are the ones the codecrafters tests read
+    fn print(&self) {
+        println!("Tracker URL: {}", self.announce.unwrap_or("(none)"));
+        println!("Length: {}", self.length);
+        if self.padding_length > 0 {
+            println!("Padding: {}", self.padding_length);
+        }
+        // hybrid torrents have both, trackers and the DHT can be asked with either
+        match (&self.info_hash, &self.info_hash_v2) {
+            (Some(info_hash), Some(_)) => println!("Info Hash (v1): {}", info_hash),
+            (Some(info_hash), None) => println!("Info Hash: {}", info_hash),
+            (None, _) => {}
+        }
+        if let Some(meta_version) = self.meta_version {
+            println!("Meta version: {}", meta_version);
+        }
+        if let Some(info_hash_v2) = &self.info_hash_v2 {
+            // peers and trackers that only take 20 bytes get the truncated hash
+            println!(
+                "Info Hash (v2): {} (truncated: {})",
+                info_hash_v2,
+                &info_hash_v2[..40]
+            );
+        }
+        println!("Piece Length: {}", self.piece_length);
+        if let Some(geometry) = self.piece_geometry {
+            println!("Piece Count: {}", geometry.count());
+            println!("Last Piece Size: {}", geometry.last_piece_size());
+        }
+        if let Some(pieces) = self.pieces_summary() {
+            println!("Pieces: {}", pieces);
+        }
+
+        // only printed when present, so the output of bare torrents doesn't change
+        let optional_fields = [
+            ("Comment", &self.comment),
+            ("Created By", &self.created_by),
+            ("Encoding", &self.encoding),
+            ("Source", &self.source),
+        ];
+        for (label, value) in optional_fields {
+            if let Some(value) = value {
+                println!("{}: {}", label, value);
+            }
+        }
+
+        // private torrents must not use DHT or PEX, only printed when the info dictionary has the key
+        if let Some(private) = self.private {
+            println!("Private: {}", if private { "yes" } else { "no" });
+        }
+
+        if let Some(creation_date) = self.creation_date {
+            match format_utc_timestamp(creation_date) {
+                Some(utc) => println!("Created: {} ({})", utc, creation_date),
+                None => println!(
+                    "Created: {} (not a date between 1970 and 9999)",
+                    creation_date
+                ),
+            }
+        }
+
+        if let Some(tiers) = &self.trackers {
+            println!("Trackers:");
+            for (index, urls) in tiers.iter().enumerate() {
+                println!("Tier {}: {}", index + 1, urls.join(" "));
+            }
+        }
+
+        let url_lists = [
+            ("Webseeds", &self.webseeds),
+            ("HTTP seeds", &self.httpseeds),
+        ];
+        for (heading, urls) in url_lists {
+            if !urls.is_empty() {
+                println!("{}:", heading);
+                urls.iter().for_each(|url| println!("{}", url));
+            }
+        }
+
+        if !self.nodes.is_empty() {
+            println!("DHT Nodes:");
+            self.nodes.iter().for_each(|node| println!("{}", node));
+        }
+
+        // v2 piece hashes are in the "piece layers" of each file
+        if self.info_hash.is_some() {
+            println!("Piece Hashes:");
+            self.piece_hashes
+                .iter()
+                .for_each(|piece_hash| println!("{}", piece_hash));
+        }
+
+        if let Some(files) = &self.files {
+            println!("Files:");
+            for file in files {
+                let padding = if file.padding { ", padding" } else { "" };
+                let range = match (file.offset, file.end) {
+                    (Some(offset), Some(end)) => format!(" at {}..{}", offset, end),
+                    _ => String::new(),
+                };
+                let target = match &file.symlink_path {
+                    Some(target) => format!(" -> {}", target),
+                    None => String::new(),
+                };
+                println!(
+                    "{}{} ({} bytes{}{}){}",
+                    file.path,
+                    target,
+                    file.length,
+                    range,
+                    padding,
+                    file.attr_marker()
+                );
+            }
+        }
+    }
+}
+
+/// Text of an optional field, invalid UTF-8 is replaced (with a warning) instead of failing
+fn lossy_text(bytes: &[u8], key: &str) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    if std::str::from_utf8(bytes).is_err() {
+        eprintln!(
+            "warning: {:?} is not valid UTF-8, invalid bytes are shown as U+FFFD",
+            key
+        );
+    }
+    text.into_owned()
 }
 
 /// For command: "info"
 pub fn execute(args: &InfoArgs) {
-    let torrent_metadata = TorrentMetadata::from_file(args.torrent_file_path.clone());
+    // checked before anything is printed, so --strict doesn't leave half of the output
+    let torrent_metadata = match args.strict {
+        true => read_strict_torrent(&args.torrent_file_path, &args.fetch),
+        false => read_torrent_source(&args.torrent_file_path, &args.fetch),
+    };
+    let info = &torrent_metadata.info;
 
-    println!("Tracker URL: {}", torrent_metadata.announce);
-    // println!("Info: {:?}", torrent_metadata.info);
-    println!("Length: {}", torrent_metadata.info.length.unwrap());
+    // names and paths of old torrents can be in another codec, an unknown one was
+    // warned about and is shown as UTF-8
+    let encoding = torrent_metadata
+        .text_encoding()
+        .unwrap_or(TextEncoding::Utf8);
+    // md5sums that aren't valid were warned about as well
+    let md5sum = |md5sum: &Option<ByteBuf>| {
+        let md5sum = parse_md5sum(md5sum.as_ref()?).ok()?;
+        Some(to_hex_string(&md5sum.to_vec()))
+    };
 
-    println!("Info Hash: {}", torrent_metadata.info.hash_str());
+    // hybrid torrents list the same files both ways, the v1 list is shown for them
+    let files: Option<Vec<FileOutput>> = match info.content() {
+        TorrentContent::SingleFile { .. } => None,
+        TorrentContent::MultiFile { files } => Some(
+            // the same ranges `map` and `verify` use, padding files take up their bytes
+            files
+                .iter()
+                .zip(info.file_ranges())
+                .map(|(file, (_, range))| FileOutput {
+                    path: encoding.join(&file.path),
+                    length: file.length,
+                    pieces_root: None,
+                    padding: file.is_padding(),
+                    md5sum: md5sum(&file.md5sum),
+                    attr: file
+                        .attr
+                        .as_ref()
+                        .map(|attr| String::from_utf8_lossy(attr).into_owned()),
+                    symlink_path: file.symlink_path.as_deref().map(|path| encoding.join(path)),
+                    offset: Some(range.start),
+                    end: Some(range.end),
+                })
+                .collect(),
+        ),
+        TorrentContent::FileTree { files } => (!files.is_empty()).then(|| {
+            files
+                .into_iter()
+                .map(|file| FileOutput {
+                    path: file.path.join("/"),
+                    length: file.length,
+                    pieces_root: file.pieces_root.map(|root| to_hex_string(&root)),
+                    padding: false,
+                    md5sum: None,
+                    attr: None,
+                    symlink_path: None,
+                    offset: None,
+                    end: None,
+                })
+                .collect()
+        }),
+    };
+    let padding_length = files
+        .iter()
+        .flatten()
+        .filter(|file| file.padding)
+        .map(|file| file.length)
+        .sum();
 
-    println!("Piece Length: {}", torrent_metadata.info.piece_length);
+    // v2 torrents (BEP 52) hash pieces with SHA-256, the ones without the v1 fields as
+    // well have no SHA-1 info hash
+    let output = InfoOutput {
+        // trackerless torrents have no announce url, their peers come from the DHT nodes
+        announce: torrent_metadata.announce.as_deref(),
+        // a multi-file torrent has no length of its own, it is the sum of the files
+        length: info.total_length() - padding_length,
+        padding_length,
+        info_hash: (!info.is_v2_only())
+            .then(|| to_hex_string(&torrent_metadata.info_hash().to_vec())),
+        meta_version: info.meta_version,
+        info_hash_v2: info
+            .is_v2()
+            .then(|| to_hex_string(&torrent_metadata.info_hash_v2().to_vec())),
+        piece_length: info.piece_length,
+        piece_hashes: info.get_piece_hashes_str(),
+        comment: torrent_metadata
+            .comment
+            .as_deref()
+            .map(|text| lossy_text(text, "comment")),
+        created_by: torrent_metadata
+            .created_by
+            .as_deref()
+            .map(|text| lossy_text(text, "created by")),
+        encoding: torrent_metadata
+            .encoding
+            .as_deref()
+            .map(|text| lossy_text(text, "encoding")),
+        source: info
+            .source
+            .as_deref()
+            .map(|text| lossy_text(text, "source")),
+        md5sum: md5sum(&info.md5sum),
+        creation_date: torrent_metadata.creation_date,
+        private: info.private.map(|private| private == 1),
+        trackers: torrent_metadata.announce_list.as_deref(),
+        webseeds: torrent_metadata.webseeds(),
+        httpseeds: torrent_metadata.httpseeds.as_deref().unwrap_or_default(),
+        nodes: torrent_metadata
+            .nodes
+            .iter()
+            .flatten()
+            .map(|(host, port)| format!("{}:{}", host, port))
+            .collect(),
+        files: files.map(|files| {
+            files
+                .into_iter()
+                .filter(|file| args.show_padding || !file.padding)
+                .collect()
+        }),
+        // the padding files are part of the pieces
+        piece_geometry: (!info.is_v2_only()).then(|| info.piece_geometry()),
+    };
 
-    println!("Piece Hashes:");
-    torrent_metadata
-        .info
-        .get_piece_hashes_str()
-        .iter()
-        .for_each(|piece_hash_str| {
-            println!("{}", piece_hash_str);
-        });
+    match args.json {
+        true => println!(
+            "{}",
+            serde_json::to_string_pretty(&output).expect("Could not format json")
+        ),
+        false => output.print(),
+    }
 }
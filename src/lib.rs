@@ -0,0 +1,12 @@
+//! Bencode parsing, torrent metadata and tracker announces shared by the bittorrent client, usable from
+//! other crates and from integration tests
+
+pub mod announce_timer;
+pub mod bencode;
+pub mod peer_id;
+pub mod random;
+pub mod sha256;
+pub mod torrent;
+pub mod tracker;
+pub mod tracker_list;
+pub mod udp_tracker;
@@ -0,0 +1,21 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::fetch::FetchArgs;
+use crate::read_torrent_source;
+
+#[derive(Args, Debug)]
+pub struct MagnetArgs {
+    /// torrent file path, or an http(s) url to fetch it from
+    torrent_file_path: PathBuf,
+    #[command(flatten)]
+    fetch: FetchArgs,
+}
+
+/// For command: "magnet"
+pub fn execute(args: &MagnetArgs) {
+    let torrent_metadata = read_torrent_source(&args.torrent_file_path, &args.fetch);
+
+    println!("{}", torrent_metadata.magnet_link());
+}
@@ -1,152 +1,390 @@
 use core::panic;
 use std::cmp::Ordering;
-use std::collections::HashMap;
-use std::io::{Read, Write};
-use std::net::TcpStream;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
 
-use serde::{Deserialize, Serialize};
-use serde_bytes::ByteBuf;
 use sha1::{Digest, Sha1};
 
+use bittorrent_starter_rust::announce_timer;
+use bittorrent_starter_rust::bencode;
+use bittorrent_starter_rust::peer_id;
+use bittorrent_starter_rust::torrent::{self, TorrentMetadata};
+use bittorrent_starter_rust::tracker;
+use bittorrent_starter_rust::tracker_list::TrackerList;
+use bittorrent_starter_rust::udp_tracker;
+
+mod canonicalize;
 mod cli;
+mod create;
 mod decode;
+mod diff;
+mod download;
+mod download_piece;
+mod edit;
+mod encode;
+mod fetch;
+mod handshake;
+mod hash;
 mod info;
+mod magnet;
+mod map;
 mod peers;
-mod handshake;
-mod download_piece;
-mod download;
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Info {
-    name: String,
-    pieces: ByteBuf,
-    #[serde(rename = "piece length")]
-    piece_length: u32,
-    length: Option<u32>,
+mod piece;
+mod progress;
+mod same;
+mod scrape;
+mod stats;
+mod verify;
+
+/// Prints the error and exits, for the commands
+pub fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    std::process::exit(1);
 }
 
-impl Info {
-    pub fn hash_str(&self) -> String {
-        let hash = self.hash_bytes();
-        to_hex_string(&hash.to_vec())
-    }
-
-    pub fn hash_bytes(&self) -> [u8; 20] {
-        let info_encoded_value = serde_bencode::to_bytes(&self).unwrap();
-        calculate_hash(&info_encoded_value)
-            .try_into()
-            .expect("Could not convert Vec<u8> to [u8; 20]")
-    }
-
-    fn get_pieces_count(&self) -> usize {
-        self.pieces.chunks(20).count()
-    }
-
-    fn get_piece_hashes(&self) -> Vec<Vec<u8>> {
-        self.pieces.chunks(20).map(|chunk| chunk.to_vec()).collect()
-    }
+/// Reads and parses a torrent file (or stdin for "-", or fetches it from an http(s) url),
+/// exits with an error message if it is invalid
+pub fn read_torrent_file(file_path: PathBuf) -> TorrentMetadata {
+    read_torrent_source(&file_path, &fetch::FetchArgs::default())
+}
 
-    pub fn get_piece_hashes_str(&self) -> Vec<String> {
-        self.get_piece_hashes()
-            .iter()
-            .map(|piece_hash| to_hex_string(&piece_hash))
-            .collect()
-    }
+/// Like [`read_torrent_file`], with the `--save`, `--max-size` and `--max-pieces` of the
+/// command
+pub fn read_torrent_source(source: &Path, fetch_args: &fetch::FetchArgs) -> TorrentMetadata {
+    read_checked_torrent(source, fetch_args, Refuse::Nothing)
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct TorrentMetadata {
-    announce: String,
-    info: Info,
+/// Like [`read_torrent_source`] for commands that go on to download, torrents that can't
+/// be downloaded (see [`torrent::LimitError::is_hard`]) are refused instead of warned about
+pub fn read_usable_torrent(source: &Path, fetch_args: &fetch::FetchArgs) -> TorrentMetadata {
+    read_checked_torrent(source, fetch_args, Refuse::Unusable)
 }
 
-impl TorrentMetadata {
-    /// Read torrent file from given path and generates TorrentMetadata
-    pub fn from_file(file_path: PathBuf) -> Self {
-        let file_contents = std::fs::read(file_path).expect("Not able to read torrent file.");
+/// Like [`read_torrent_source`] for `--strict`, a torrent with any of the problems that
+/// are otherwise warned about (but md5sums and the encoding) is refused
+pub fn read_strict_torrent(source: &Path, fetch_args: &fetch::FetchArgs) -> TorrentMetadata {
+    read_checked_torrent(source, fetch_args, Refuse::Anything)
+}
 
-        // let decoded_value = decode_bencoded_value_serde_bencode(&file_contents);
+/// Which problems of a torrent make [`read_checked_torrent`] exit instead of warning
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Refuse {
+    Nothing,
+    Unusable,
+    Anything,
+}
 
-        // println!("{}", decoded_value.to_string());
+fn read_checked_torrent(
+    source: &Path,
+    fetch_args: &fetch::FetchArgs,
+    refuse: Refuse,
+) -> TorrentMetadata {
+    let file_contents = fetch::read_torrent_input(source, fetch_args);
+
+    // e.g. a length that doesn't fit, report it instead of panicking
+    let torrent_metadata = TorrentMetadata::from_bytes(&file_contents).unwrap_or_else(|err| {
+        eprintln!(
+            "error: {} is not a valid torrent: {}",
+            fetch::source_name(source),
+            err
+        );
+        std::process::exit(1);
+    });
+    for skipped in &torrent_metadata.skipped {
+        eprintln!("warning: {}", skipped);
+    }
+    let warn_or_refuse = |refused: bool, message: &str| match refused {
+        true => {
+            eprintln!("error: {}", message);
+            std::process::exit(1);
+        }
+        false => eprintln!("warning: {}", message),
+    };
+
+    for err in torrent_metadata.info.check_limits(fetch_args.max_pieces) {
+        let refused = match refuse {
+            Refuse::Nothing => false,
+            Refuse::Unusable => err.is_hard(),
+            Refuse::Anything => true,
+        };
+        warn_or_refuse(refused, &fetch::limit_message(&err));
+    }
+    if let Err(err) = torrent_metadata.info.check_hybrid() {
+        warn_or_refuse(refuse == Refuse::Anything, &err.to_string());
+    }
+    // the names are still shown, with the bytes that aren't UTF-8 replaced
+    let encoding = torrent_metadata.text_encoding().unwrap_or_else(|| {
+        eprintln!(
+            "warning: unknown encoding {:?}, names are shown as UTF-8",
+            torrent_metadata
+                .encoding
+                .as_deref()
+                .map(|label| String::from_utf8_lossy(label))
+                .unwrap_or_default()
+        );
+        torrent::TextEncoding::Utf8
+    });
+    // a client writing these would end up outside of its download directory
+    let unsafe_paths = torrent_metadata.info.check_paths();
+    if let (Refuse::Anything, Some((path, err))) = (refuse, unsafe_paths.first()) {
+        warn_or_refuse(
+            true,
+            &format!("unsafe path {}: {}", encoding.join(path), err),
+        );
+    }
+    if !unsafe_paths.is_empty() {
+        eprintln!(
+            "warning: UNSAFE TORRENT, {} of its paths could be written outside of a download directory:",
+            unsafe_paths.len()
+        );
+    }
+    for (path, err) in unsafe_paths {
+        eprintln!("warning: unsafe path {}: {}", encoding.join(&path), err);
+    }
+    for (path, err) in torrent_metadata.info.check_md5sums() {
+        eprintln!(
+            "warning: ignoring md5sum of {}, {}",
+            encoding.join(&path),
+            err
+        );
+    }
+    torrent_metadata
+}
 
-        serde_bencode::from_bytes(&file_contents).unwrap()
+/// The trackers of the torrent with each tier shuffled, in the order the announces of
+/// this process try them
+fn shuffled_trackers(torrent_metadata: &TorrentMetadata) -> TrackerList {
+    let mut trackers = TrackerList::from_torrent(torrent_metadata);
+    if let Err(err) = trackers.shuffle() {
+        eprintln!(
+            "warning: trackers are tried in the torrent's order: {}",
+            err
+        );
     }
+    trackers
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Peers a tracker gave for the torrent and when to ask it again
 struct TrackerResponse {
+    peers: Vec<tracker::PeerAddress>,
+    /// seconds before the next announce
     interval: u64,
-    #[serde(with = "serde_bytes")]
-    peers: ByteBuf,
+    /// seconds before an announce sooner than `interval`
+    min_interval: Option<u64>,
 }
 
 impl TrackerResponse {
-    async fn from(torrent_metadata: &TorrentMetadata) -> Self {
-        let info_hash = torrent_metadata.info.hash_bytes();
+    /// The first announce of the torrent, see [`Self::announce`]. Exits when no tracker
+    /// answers
+    async fn from(
+        torrent_metadata: &TorrentMetadata,
+        trackers: &mut TrackerList,
+        preferred: Option<tracker::AddressFamily>,
+        verbose: bool,
+    ) -> Self {
+        let request = Self::first_request(torrent_metadata);
+        let (_, response) = Self::from_request(&request, trackers, preferred, verbose).await;
+        response
+    }
 
-        let url = format!(
-            "{}?info_hash={}",
-            torrent_metadata.announce,
-            urlencode_hash(&info_hash)
-        );
+    /// Like [`Self::from`] for a `request` that is kept for the next announces, with the
+    /// tracker that answered
+    async fn from_request(
+        request: &udp_tracker::AnnounceRequest,
+        trackers: &mut TrackerList,
+        preferred: Option<tracker::AddressFamily>,
+        verbose: bool,
+    ) -> (String, Self) {
+        if trackers.is_empty() {
+            eprintln!("error: the torrent has no trackers and DHT is not enabled");
+            std::process::exit(1);
+        }
 
-        let query = &[
-            ("peer_id", "00112233445566778899".to_string()),
-            ("port", "6881".to_string()),
-            ("uploaded", "0".to_string()),
-            ("downloaded", "0".to_string()),
-            ("left", torrent_metadata.info.length.unwrap().to_string()),
-            ("compact", "1".to_string()),
-        ];
+        match Self::announce(request, trackers, preferred, verbose).await {
+            Ok(response) => response,
+            Err(failures) => {
+                // verbose output already showed every failure
+                if !verbose {
+                    for (announce, err) in &failures {
+                        eprintln!("error: announce to {} failed: {}", announce, err);
+                    }
+                }
+                if verbose || failures.len() > 1 {
+                    eprintln!("error: no tracker answered ({} tried)", failures.len());
+                }
+                std::process::exit(1);
+            }
+        }
+    }
 
-        let client = reqwest::Client::new();
-        let res = client.get(url).query(query).send().await.unwrap();
+    /// The announce of a client that has nothing of the torrent yet, later announces
+    /// change its progress
+    fn first_request(torrent_metadata: &TorrentMetadata) -> udp_tracker::AnnounceRequest {
+        udp_tracker::AnnounceRequest {
+            info_hash: torrent_metadata.info_hash(),
+            peer_id: *peer_id::local(),
+            downloaded: 0,
+            left: torrent_metadata.info.total_length(),
+            uploaded: 0,
+            event: udp_tracker::Event::None,
+            // only helps trackers that see the client from several addresses
+            key: tracker::announce_key(&torrent_metadata.info_hash()).unwrap_or_default(),
+            num_want: -1,
+            port: 6881,
+        }
+    }
 
-        // println!("status_code: {}", res.status());
+    /// Peers of the first of `trackers` that answers `request`, which is then asked
+    /// first by the next announces. The peers of `preferred` (see `--prefer-ipv4` and
+    /// `--prefer-ipv6`) come first, `verbose` shows how each tracker answered. Every
+    /// tracker and why it failed when none answered
+    async fn announce(
+        request: &udp_tracker::AnnounceRequest,
+        trackers: &mut TrackerList,
+        preferred: Option<tracker::AddressFamily>,
+        verbose: bool,
+    ) -> Result<(String, Self), Vec<(String, String)>> {
+        let (tracker, mut response) = trackers
+            .announce(|announce| async move {
+                let response = Self::announce_to(&announce, request).await;
+                if verbose {
+                    match &response {
+                        Ok(response) => {
+                            eprintln!("announce to {}: {} peers", announce, response.peers.len())
+                        }
+                        Err(err) => eprintln!("announce to {} failed: {}", announce, err),
+                    }
+                }
+                response
+            })
+            .await?;
+        if let Some(family) = preferred {
+            tracker::prefer_family(&mut response.peers, family);
+        }
+        Ok((tracker, response))
+    }
 
-        let res_bytes = res.bytes().await.unwrap();
-        // println!("res_bytes: {:?}", res_bytes);
+    /// The announce of `request` to the `announce` tracker alone
+    async fn announce_to(
+        announce: &str,
+        request: &udp_tracker::AnnounceRequest,
+    ) -> Result<Self, String> {
+        match udp_tracker::udp_address(announce) {
+            Some(address) => Self::announce_udp(address, request)
+                .await
+                .map_err(|err| err.to_string()),
+            None => Self::announce_http(announce, request).await,
+        }
+    }
 
-        serde_bencode::from_bytes(&res_bytes).expect("TranckerResponse could not be decoded.")
+    async fn announce_http(
+        announce: &str,
+        request: &udp_tracker::AnnounceRequest,
+    ) -> Result<Self, String> {
+        let url = tracker::announce_url(announce, request);
+
+        // the url would show the key, the tracker is named by the caller
+        let network_error = |err: reqwest::Error| format!("network error: {}", err.without_url());
+        let res = reqwest::get(url).await.map_err(network_error)?;
+        let status = res.status();
+        let res_bytes = res.bytes().await.map_err(network_error)?;
+
+        // a refused announce usually comes with a "failure reason", whatever the status
+        match tracker::parse_response(&res_bytes) {
+            Ok(response) => {
+                if let Some(warning) = response.warning {
+                    eprintln!("warning: tracker {}: {}", announce, warning);
+                }
+                Ok(Self {
+                    peers: response.peers,
+                    interval: response.interval,
+                    min_interval: response.min_interval,
+                })
+            }
+            Err(_) if !status.is_success() => Err(format!("the tracker answered {}", status)),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// The socket is blocking and the tracker can take minutes to answer (see
+    /// [`udp_tracker::retransmit_timeout`]), so it gets a thread of its own. The tracker
+    /// of each address is kept for the next announces, which reuse its connection id
+    async fn announce_udp(
+        address: &str,
+        request: &udp_tracker::AnnounceRequest,
+    ) -> Result<Self, udp_tracker::UdpError> {
+        let address = address.to_string();
+        let request = request.clone();
+
+        let response = tokio::task::spawn_blocking(move || {
+            // taken out while it announces, so the lock isn't held while waiting for it
+            let known = UDP_TRACKERS
+                .lock()
+                .expect("the udp trackers lock is not poisoned")
+                .remove(&address);
+            let mut udp_tracker = match known {
+                Some(udp_tracker) => udp_tracker,
+                None => udp_tracker::UdpTracker::connect(&address)?,
+            };
+            let response = udp_tracker.announce(&request);
+            UDP_TRACKERS
+                .lock()
+                .expect("the udp trackers lock is not poisoned")
+                .insert(address, udp_tracker);
+            response
+        })
+        .await
+        .expect("the announce thread does not panic")?;
+        Ok(Self {
+            peers: response
+                .peers
+                .into_iter()
+                .map(tracker::PeerAddress::Ip)
+                .collect(),
+            interval: response.interval.into(),
+            // UDP announces have no min interval
+            min_interval: None,
+        })
     }
-}
 
-impl TrackerResponse {
-    #[allow(dead_code)]
     fn get_peers(&self) -> Vec<String> {
-        self.peers
-            .chunks(6)
-            .map(|chunk| {
-                let ip = chunk[0..4]
-                    .iter()
-                    .map(|b| format!("{}", b))
-                    .collect::<Vec<String>>()
-                    .join(".");
-
-                let port = u16::from_be_bytes([chunk[4], chunk[5]]);
-
-                format!("{}:{}", ip, port)
-            })
-            .collect()
+        self.peers.iter().map(|peer| peer.to_string()).collect()
     }
 }
 
+/// UDP trackers announced to in this run by their "host:port"
+static UDP_TRACKERS: Mutex<BTreeMap<String, udp_tracker::UdpTracker>> = Mutex::new(BTreeMap::new());
+
+/// How long a peer can take to accept the connection or to send the next message
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+
 struct Connection {
     stream: TcpStream,
 }
 
 #[allow(dead_code)]
 impl Connection {
-    fn new(peer: String) -> Self {
-        let stream = TcpStream::connect(peer).expect("tcp connection failed!");
-
-        Self { stream }
+    /// Connects to the first address of `peer` that answers within the timeout, reads
+    /// time out too so a peer that stalls fails instead of hanging the download
+    fn new(peer: String) -> io::Result<Self> {
+        let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no address for the peer");
+        for address in peer.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, PEER_TIMEOUT) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(PEER_TIMEOUT))?;
+                    return Ok(Self { stream });
+                }
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
     }
 
-    fn handshake(&mut self, info_hash: Vec<u8>, peer_id: String) -> Vec<u8> {
-        assert_eq!(peer_id.len(), 20, "peer_id should be 20 characters long");
-
+    fn handshake(&mut self, info_hash: Vec<u8>, peer_id: &[u8; 20]) -> io::Result<Vec<u8>> {
         // Length of the protocol string (1 Byte)
         let mut message: Vec<u8> = vec![19];
         // protocol string (19 Bytes)
@@ -156,20 +394,20 @@ impl Connection {
         // sha1 info_hash (20 Bytes)
         message.extend(info_hash);
         // peer id (20 Bytes)
-        message.extend(b"00112233445566778899");
+        message.extend(peer_id);
 
-        let message_length = self.stream.write(&message).unwrap();
+        self.stream.write_all(&message)?;
 
-        let mut res_message: Vec<u8> = vec![0; message_length];
-        let res_message_length = self.stream.read(&mut res_message).unwrap();
+        let mut res_message: Vec<u8> = vec![0; message.len()];
+        self.stream.read_exact(&mut res_message)?;
 
-        let res_peer_id = &res_message[res_message_length - 20..];
+        let res_peer_id = &res_message[res_message.len() - 20..];
 
-        res_peer_id.to_vec()
+        Ok(res_peer_id.to_vec())
     }
 
-    fn wait(&mut self, message_type: PeerMessageType) -> Vec<u8> {
-        let wait_messages_type = vec![
+    fn wait(&mut self, message_type: PeerMessageType) -> io::Result<Vec<u8>> {
+        let wait_messages_type = [
             PeerMessageType::BitField,
             PeerMessageType::Unchoke,
             PeerMessageType::Piece,
@@ -177,44 +415,51 @@ impl Connection {
 
         if wait_messages_type.contains(&message_type) {
             let mut recv_message_length_buf: [u8; 4] = [0; 4];
-            self.stream
-                .read_exact(&mut recv_message_length_buf)
-                .expect("Could not read length buffer");
+            self.stream.read_exact(&mut recv_message_length_buf)?;
 
             let recv_message_length = u32::from_be_bytes(recv_message_length_buf);
             println!("recv_message_length: {}", recv_message_length);
+            if recv_message_length == 0 {
+                return Err(invalid_data(format!(
+                    "expected {:?} but received a keep-alive",
+                    message_type
+                )));
+            }
 
             let mut recv_message_id_buf: [u8; 1] = [0; 1];
-            self.stream
-                .read_exact(&mut recv_message_id_buf)
-                .expect("Could not read message id");
+            self.stream.read_exact(&mut recv_message_id_buf)?;
 
             let recv_message_id = u8::from_be_bytes(recv_message_id_buf);
+            if recv_message_id > PeerMessageType::Cancel.to_message_id() {
+                return Err(invalid_data(format!(
+                    "invalid message id {}",
+                    recv_message_id
+                )));
+            }
             let recv_message_type = PeerMessageType::from(recv_message_id);
             println!("recv_message_type: {:?}", recv_message_type);
 
-            assert_eq!(
-                message_type, recv_message_type,
-                "Message type expected {:?} but received {:?}",
-                message_type, recv_message_type
-            );
+            if message_type != recv_message_type {
+                return Err(invalid_data(format!(
+                    "Message type expected {:?} but received {:?}",
+                    message_type, recv_message_type
+                )));
+            }
 
             let payload_length = recv_message_length as usize - 1;
             let mut payload_buf: Vec<u8> = vec![0; payload_length];
-            self.stream
-                .read_exact(&mut payload_buf)
-                .expect("Could not read payload");
+            self.stream.read_exact(&mut payload_buf)?;
 
             println!("received payload length: {}", payload_buf.len());
 
-            return payload_buf;
+            Ok(payload_buf)
         } else {
             panic!("Can not wait for this message type: {:?}", message_type);
         }
     }
 
-    fn send(&mut self, message_type: PeerMessageType, payload: Vec<u8>) {
-        let send_messages_type = vec![PeerMessageType::Interested, PeerMessageType::Request];
+    fn send(&mut self, message_type: PeerMessageType, payload: Vec<u8>) -> io::Result<()> {
+        let send_messages_type = [PeerMessageType::Interested, PeerMessageType::Request];
 
         if send_messages_type.contains(&message_type) {
             let message_length = (payload.len() + 1) as u32;
@@ -227,10 +472,10 @@ impl Connection {
             message.extend(message_id_buf);
             message.extend(payload);
 
-            self.stream
-                .write_all(&message)
-                .expect("Could not send the message");
+            self.stream.write_all(&message)?;
             println!("Sent message type: {:?}", message_type);
+
+            Ok(())
         } else {
             panic!("Can not send this type of message: {:?}", message_type);
         }
@@ -241,7 +486,7 @@ impl Connection {
         piece_index: u32,
         block_byte_offset: u32,
         block_length: u32,
-    ) -> Block {
+    ) -> io::Result<Block> {
         println!(
             ">> Sending request for block {} of piece {}",
             block_byte_offset, piece_index
@@ -252,30 +497,31 @@ impl Connection {
         request_payload.extend(block_byte_offset.to_be_bytes());
         request_payload.extend(block_length.to_be_bytes());
 
-        self.send(PeerMessageType::Request, request_payload);
+        self.send(PeerMessageType::Request, request_payload)?;
 
         // 5. Wait for `piece` message
-        let block_data = self.wait(PeerMessageType::Piece);
+        let block_data = self.wait(PeerMessageType::Piece)?;
 
         let block = Block::from(block_data);
 
         let recv_block_data_length = block.block_data.len() as u32;
 
-        assert_eq!(
-            recv_block_data_length, block_length,
-            "Block length expected {} and received {} are not same.",
-            block_length, recv_block_data_length
-        );
+        if recv_block_data_length != block_length {
+            return Err(invalid_data(format!(
+                "Block length expected {} and received {} are not same.",
+                block_length, recv_block_data_length
+            )));
+        }
 
         println!(
             ">> Downloaded block of byte offset {} of block data length: {}",
             block_byte_offset, recv_block_data_length
         );
 
-        block
+        Ok(block)
     }
 
-    fn download_piece(&mut self, piece_index: u32, piece_length: u32) -> Piece {
+    fn download_piece(&mut self, piece_index: u32, piece_length: u32) -> io::Result<Piece> {
         println!("> Downloading piece {}", piece_index);
 
         let block_length: u32 = 16 * 1024;
@@ -284,7 +530,7 @@ impl Connection {
 
         let mut blocks: Vec<Block> = Vec::new();
 
-        let mut block_index = 0 as u32;
+        let mut block_index = 0_u32;
         let mut block_byte_offset;
 
         // let mut piece_data: Vec<u8> = Vec::new();
@@ -306,7 +552,7 @@ impl Connection {
 
             println!("actual_block_length: {}", actual_block_length);
 
-            let block = self.download_block(piece_index, block_byte_offset, actual_block_length);
+            let block = self.download_block(piece_index, block_byte_offset, actual_block_length)?;
 
             // piece_data.extend(&block.block_data);
             blocks.push(block);
@@ -317,7 +563,7 @@ impl Connection {
         // let piece_hash = calculate_hash(&piece_data);
         // println!("* calculated piece_hash: {:?}", piece_hash);
 
-        Piece::from(blocks)
+        Ok(Piece::from(blocks))
     }
 
     fn download_blocks_pipelined(
@@ -326,12 +572,12 @@ impl Connection {
         piece_index: u32,
         piece_length: u32,
         block_index: &mut u32,
-    ) -> Vec<Block> {
-        let mut no_of_requests_sent = 0 as usize;
+    ) -> io::Result<Vec<Block>> {
+        let mut no_of_requests_sent = 0_usize;
         let mut blocks: Vec<Block> = Vec::new();
 
         let mut block_byte_offset;
-        let block_length = 16 * 1024 as u32;
+        let block_length = 16 * 1024_u32;
         // let mut actual_block_lengths: Vec<u32> = vec![];
         let mut actual_block_lengths = HashMap::new();
 
@@ -367,7 +613,7 @@ impl Connection {
             request_payload.extend(block_byte_offset.to_be_bytes());
             request_payload.extend(actual_block_length.to_be_bytes());
 
-            self.send(PeerMessageType::Request, request_payload);
+            self.send(PeerMessageType::Request, request_payload)?;
 
             *block_index += 1;
             no_of_requests_sent += 1;
@@ -375,23 +621,24 @@ impl Connection {
 
         for _ in 0..no_of_requests_sent {
             println!(">> Waiting for next block");
-            let block_data = self.wait(PeerMessageType::Piece);
+            let block_data = self.wait(PeerMessageType::Piece)?;
 
             let block = Block::from(block_data);
             let recv_block_length = block.block_data.len() as u32;
 
-            let actual_block_length = *actual_block_lengths.get(&block.block_byte_offset).expect(
-                format!(
-                    "Could not get value for the key: {}",
+            let Some(&actual_block_length) = actual_block_lengths.get(&block.block_byte_offset)
+            else {
+                return Err(invalid_data(format!(
+                    "received block {} that was not requested",
                     block.block_byte_offset
-                )
-                .as_str(),
-            );
-            assert_eq!(
-                recv_block_length, actual_block_length,
-                "Block length expected {} and received {} are not same.",
-                actual_block_length, recv_block_length
-            );
+                )));
+            };
+            if recv_block_length != actual_block_length {
+                return Err(invalid_data(format!(
+                    "Block length expected {} and received {} are not same.",
+                    actual_block_length, recv_block_length
+                )));
+            }
 
             println!(
                 ">> block of byte offset {} downloaded of block length: {}",
@@ -401,7 +648,7 @@ impl Connection {
             blocks.push(block);
         }
 
-        blocks
+        Ok(blocks)
     }
 
     fn download_piece_pipelined(
@@ -409,16 +656,16 @@ impl Connection {
         no_of_requests: usize,
         piece_index: u32,
         piece_length: u32,
-    ) -> Piece {
+    ) -> io::Result<Piece> {
         println!("> Downloading piece {}", piece_index);
 
-        let block_length = 16 * 1024 as u32;
+        let block_length = 16 * 1024_u32;
         // let blocks_count = (piece_length / block_length) + 5 - block_length * (piece_length / block_length);
         // println!(">> Piece {} contains {} blocks", piece_index, blocks_count);
 
         let mut blocks: Vec<Block> = Vec::new();
 
-        let mut block_index = 0 as u32;
+        let mut block_index = 0_u32;
 
         loop {
             let block_byte_offset = block_index * block_length;
@@ -431,15 +678,20 @@ impl Connection {
                 piece_index,
                 piece_length,
                 &mut block_index,
-            );
+            )?;
 
             blocks.extend(next_blocks);
         }
 
-        Piece::from(blocks)
+        Ok(Piece::from(blocks))
     }
 }
 
+/// An error for a peer message that breaks the protocol
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
 #[derive(Debug, PartialEq)]
 enum PeerMessageType {
     Choke,
@@ -571,7 +823,7 @@ impl Piece {
         });
 
         let piece_index = blocks
-            .get(0)
+            .first()
             .expect("Could not get block from blocks array")
             .piece_index;
 
@@ -594,7 +846,90 @@ fn to_hex_string(bytes: &Vec<u8>) -> String {
     s
 }
 
-fn calculate_hash(bytes: &Vec<u8>) -> Vec<u8> {
+fn from_hex_string(s: &str) -> Option<Vec<u8>> {
+    s.as_bytes()
+        .chunks(2)
+        .map(|chunk| match chunk {
+            [a, b] if a.is_ascii_hexdigit() && b.is_ascii_hexdigit() => {
+                u8::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok()
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn to_base64_string(bytes: &[u8]) -> String {
+    let mut s = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = u32::from_be_bytes([0, b[0], b[1], b[2]]);
+
+        // every 3 bytes map to 4 characters, missing bytes are padded with '='
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// RFC 4648 base32 (magnet links use it for info hashes), padded with '=' to a multiple of 8
+fn to_base32_string(bytes: &[u8]) -> String {
+    let mut s = String::new();
+    for chunk in bytes.chunks(5) {
+        let mut b = [0; 8];
+        b[3..3 + chunk.len()].copy_from_slice(chunk);
+        let n = u64::from_be_bytes(b);
+
+        // every 5 bytes map to 8 characters, only the ones covering a byte are kept
+        let characters = [0, 2, 4, 5, 7, 8][chunk.len()];
+        for i in 0..8 {
+            if i < characters {
+                s.push(BASE32_ALPHABET[(n >> (35 - 5 * i) & 0x1f) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+/// Decodes standard base64 with padding, `None` for anything else
+fn from_base64_string(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let chunks: Vec<&[u8]> = s.as_bytes().chunks(4).collect();
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        // padding is only allowed at the end of the last chunk
+        if chunk.len() != 4 || padding > 2 || (padding > 0 && index != chunks.len() - 1) {
+            return None;
+        }
+
+        let mut n: u32 = 0;
+        for &c in &chunk[..4 - padding] {
+            let sextet = BASE64_ALPHABET.iter().position(|&a| a == c)? as u32;
+            n = n << 6 | sextet;
+        }
+        n <<= 6 * padding;
+        bytes.extend(&n.to_be_bytes()[1..4 - padding]);
+    }
+    Some(bytes)
+}
+
+fn calculate_hash(bytes: &[u8]) -> Vec<u8> {
     let mut hasher = Sha1::new();
     hasher.update(bytes);
     let hash = hasher.finalize();
@@ -602,17 +937,6 @@ fn calculate_hash(bytes: &Vec<u8>) -> Vec<u8> {
     hash.to_vec()
 }
 
-pub fn urlencode_hash(i: &[u8; 20]) -> String {
-    i.into_iter()
-        .map(|&b| match b {
-            b'0'..=b'9' | b'A'..=b'Z' | b'a'..=b'z' | b'-' | b'.' | b'_' | b'~' => {
-                format!("{}", b as char)
-            }
-            _ => format!("%{:02X}", b),
-        })
-        .collect::<String>()
-}
-
 #[tokio::main]
 async fn main() {
     cli::parse_and_execute().await;
@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::read_torrent_file;
+use crate::torrent::TorrentContent;
+
+#[derive(Args, Debug)]
+pub struct MapArgs {
+    /// torrent file path
+    torrent_file_path: PathBuf,
+    /// list the files in each piece instead of the pieces of each file
+    #[arg(long)]
+    by_piece: bool,
+}
+
+/// For command: "map"
+pub fn execute(args: &MapArgs) {
+    let torrent_metadata = read_torrent_file(args.torrent_file_path.clone());
+    let info = &torrent_metadata.info;
+    let encoding = torrent_metadata.text_encoding().unwrap_or_default();
+
+    // padding files are shown too, their bytes take up space in the pieces
+    let padding: Vec<bool> = match info.content() {
+        TorrentContent::MultiFile { files } => files.iter().map(|file| file.is_padding()).collect(),
+        _ => vec![false],
+    };
+    let files: Vec<(String, u64)> = info
+        .file_ranges()
+        .into_iter()
+        .zip(padding)
+        .map(|((path, range), padding)| match padding {
+            true => (
+                format!("{} (padding)", encoding.join(&path)),
+                range.end - range.start,
+            ),
+            false => (encoding.join(&path), range.end - range.start),
+        })
+        .collect();
+    let mut slices = info.file_slices();
+
+    if args.by_piece {
+        slices.sort_by_key(|slice| (slice.piece_index, slice.piece_offset));
+        for (position, slice) in slices.iter().enumerate() {
+            if position == 0 || slices[position - 1].piece_index != slice.piece_index {
+                let range = info.piece_range(slice.piece_index).unwrap_or_default();
+                println!(
+                    "piece {} ({} bytes)",
+                    slice.piece_index,
+                    range.end - range.start
+                );
+            }
+            println!(
+                "  {}: {}..{} of the piece, {}..{} of the file",
+                files[slice.file_index].0,
+                slice.piece_offset,
+                slice.piece_offset + slice.length,
+                slice.file_offset,
+                slice.file_offset + slice.length
+            );
+        }
+        return;
+    }
+
+    for (file_index, (path, length)) in files.iter().enumerate() {
+        println!("{} ({} bytes)", path, length);
+
+        let file_slices: Vec<_> = slices
+            .iter()
+            .filter(|slice| slice.file_index == file_index)
+            .collect();
+        if file_slices.is_empty() {
+            println!("  no pieces");
+        }
+        for slice in file_slices {
+            println!(
+                "  piece {}: {}..{} of the piece, {}..{} of the file",
+                slice.piece_index,
+                slice.piece_offset,
+                slice.piece_offset + slice.length,
+                slice.file_offset,
+                slice.file_offset + slice.length
+            );
+        }
+    }
+}
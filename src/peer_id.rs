@@ -0,0 +1,160 @@
+//! Azureus-style peer ids (`-XX0001-` and 12 random characters), the one this client
+//! sends and the client names of the ones peers send
+
+use std::io;
+use std::sync::OnceLock;
+
+use thiserror::Error;
+
+use crate::random;
+
+/// Client code and version at the start of every peer id this client sends
+pub const CLIENT_PREFIX: &[u8; 8] = b"-XX0001-";
+
+/// Characters of the random part, peer ids stay readable in logs
+const ALPHANUMERIC: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Client codes of Azureus-style peer ids and the clients they stand for
+const KNOWN_CLIENTS: [(&[u8; 2], &str); 5] = [
+    (b"qB", "qBittorrent"),
+    (b"TR", "Transmission"),
+    (b"LT", "libtorrent (Rasterbar)"),
+    (b"lt", "libTorrent (rakshasa)"),
+    (b"UT", "\u{b5}Torrent"),
+];
+
+/// Why a peer id (or its prefix) given by the user can't be used
+#[derive(Error, Debug)]
+pub enum PeerIdError {
+    #[error("peer id prefix {0:?} is longer than 8 characters")]
+    PrefixTooLong(String),
+    #[error("peer id is {0} hex characters instead of 40 (20 bytes)")]
+    WrongLength(usize),
+    #[error("peer id is not hex")]
+    NotHex,
+    #[error("could not read the system random source: {0}")]
+    Random(#[from] io::Error),
+}
+
+/// A new peer id, [`CLIENT_PREFIX`] and 12 alphanumeric characters from the system's
+/// random source
+///
+/// ```
+/// use bittorrent_starter_rust::peer_id::{self, CLIENT_PREFIX};
+///
+/// let peer_id = peer_id::generate().unwrap();
+/// assert!(peer_id.starts_with(CLIENT_PREFIX));
+/// assert!(peer_id[8..].iter().all(u8::is_ascii_alphanumeric));
+/// assert_ne!(peer_id, peer_id::generate().unwrap());
+/// ```
+pub fn generate() -> Result<[u8; 20], PeerIdError> {
+    generate_with_prefix(std::str::from_utf8(CLIENT_PREFIX).expect("the prefix is ASCII"))
+}
+
+/// Like [`generate`] with another client's prefix, some private trackers only let
+/// known clients announce. The rest of the 20 bytes is random
+///
+/// ```
+/// use bittorrent_starter_rust::peer_id;
+///
+/// let peer_id = peer_id::generate_with_prefix("-qB4650-").unwrap();
+/// assert!(peer_id.starts_with(b"-qB4650-"));
+/// assert_eq!(
+///     peer_id::generate_with_prefix("-qBittorrent-").unwrap_err().to_string(),
+///     "peer id prefix \"-qBittorrent-\" is longer than 8 characters"
+/// );
+/// ```
+pub fn generate_with_prefix(prefix: &str) -> Result<[u8; 20], PeerIdError> {
+    if prefix.len() > 8 {
+        return Err(PeerIdError::PrefixTooLong(prefix.to_string()));
+    }
+    let mut peer_id = [0; 20];
+    peer_id[..prefix.len()].copy_from_slice(prefix.as_bytes());
+
+    // bytes from 248 on are skipped, so each character is as likely as the others
+    let mut filled = prefix.len();
+    while filled < peer_id.len() {
+        let mut bytes = [0; 20];
+        random::fill_bytes(&mut bytes[filled..])?;
+        for byte in bytes[filled..].iter().map(|&byte| usize::from(byte)) {
+            if byte < ALPHANUMERIC.len() * 4 {
+                peer_id[filled] = ALPHANUMERIC[byte % ALPHANUMERIC.len()];
+                filled += 1;
+            }
+        }
+    }
+    Ok(peer_id)
+}
+
+/// A peer id written as 40 hex characters, like the fixed one of a test
+///
+/// ```
+/// use bittorrent_starter_rust::peer_id::{self, PeerIdError};
+///
+/// assert_eq!(
+///     &peer_id::parse_hex("2d71423436353030303030303030303030303030").unwrap(),
+///     b"-qB46500000000000000"
+/// );
+/// assert!(matches!(peer_id::parse_hex("2d71"), Err(PeerIdError::WrongLength(4))));
+/// assert!(matches!(peer_id::parse_hex(&"zz".repeat(20)), Err(PeerIdError::NotHex)));
+/// ```
+pub fn parse_hex(hex: &str) -> Result<[u8; 20], PeerIdError> {
+    if hex.len() != 40 {
+        return Err(PeerIdError::WrongLength(hex.len()));
+    }
+
+    let digit = |c: u8| (c as char).to_digit(16).ok_or(PeerIdError::NotHex);
+    let mut peer_id = [0; 20];
+    for (byte, pair) in peer_id.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = (digit(pair[0])? * 16 + digit(pair[1])?) as u8;
+    }
+    Ok(peer_id)
+}
+
+static LOCAL: OnceLock<[u8; 20]> = OnceLock::new();
+
+/// The peer id of this process, generated on first use (unless [`set_local`] chose
+/// one) so trackers and peers all see the same one
+pub fn local() -> &'static [u8; 20] {
+    LOCAL.get_or_init(|| generate().expect("the system random source is readable"))
+}
+
+/// Makes `peer_id` the one [`local`] returns, `false` once it has been used as the
+/// peer id can't change anymore
+pub fn set_local(peer_id: [u8; 20]) -> bool {
+    LOCAL.set(peer_id).is_ok()
+}
+
+/// Best guess at the client that sent `peer_id`, like "qBittorrent 4.6.2", from the
+/// `-XXvvvv-` of Azureus-style ids. `None` for other ids, an unknown client code is
+/// shown as it is
+///
+/// ```
+/// use bittorrent_starter_rust::peer_id::client_name;
+///
+/// assert_eq!(client_name(b"-qB4620-k8hj0wgej6ch").as_deref(), Some("qBittorrent 4.6.2"));
+/// assert_eq!(client_name(b"-TR3000-k8hj0wgej6ch").as_deref(), Some("Transmission 3"));
+/// assert_eq!(client_name(b"-ZZ1200-k8hj0wgej6ch").as_deref(), Some("ZZ 1.2"));
+/// assert_eq!(client_name(b"M7-2-2--k8hj0wgej6ch"), None);
+/// assert_eq!(client_name(b"-qB"), None);
+/// ```
+pub fn client_name(peer_id: &[u8]) -> Option<String> {
+    let prefix = peer_id.get(..8)?;
+    let (code, version) = (&prefix[1..3], &prefix[3..7]);
+    let azureus = prefix[0] == b'-' && prefix[7] == b'-';
+    if !azureus || !code.iter().chain(version).all(u8::is_ascii_alphanumeric) {
+        return None;
+    }
+
+    // each character is a part of the version, the trailing zeros are left out
+    let mut parts: Vec<String> = version.iter().map(|&c| (c as char).to_string()).collect();
+    while parts.len() > 1 && parts.last().map(String::as_str) == Some("0") {
+        parts.pop();
+    }
+    let name = KNOWN_CLIENTS
+        .iter()
+        .find(|(known, _)| known.as_slice() == code)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| String::from_utf8_lossy(code).into_owned());
+    Some(format!("{} {}", name, parts.join(".")))
+}
@@ -1,22 +1,85 @@
 use std::path::PathBuf;
 
-use crate::{TorrentMetadata, TrackerResponse};
+use crate::fetch::FetchArgs;
+use crate::tracker::{AddressFamily, PeerAddress};
+use crate::{read_usable_torrent, shuffled_trackers, TrackerResponse};
 use clap::Args;
 
 #[derive(Args, Debug)]
 pub struct PeersArgs {
-    /// torrent file path
+    /// torrent file path, or an http(s) url to fetch it from
     torrent_file_path: PathBuf,
+    #[command(flatten)]
+    fetch: FetchArgs,
+    #[command(flatten)]
+    family: FamilyArgs,
+    /// look up the host names a tracker can give instead of an address, the first
+    /// address of each is printed
+    #[arg(long)]
+    resolve: bool,
+    /// show how each tracker answered
+    #[arg(short, long)]
+    verbose: bool,
+    /// ask the tracker for this many peers, it picks how many when not given
+    #[arg(long, value_parser = clap::value_parser!(i32).range(0..))]
+    numwant: Option<i32>,
+}
+
+/// Which peers to list (and connect to) first when a tracker gives IPv4 and IPv6 ones
+#[derive(Args, Debug)]
+pub struct FamilyArgs {
+    /// IPv4 peers first
+    #[arg(long, conflicts_with = "prefer_ipv6")]
+    prefer_ipv4: bool,
+    /// IPv6 peers first
+    #[arg(long)]
+    prefer_ipv6: bool,
+}
+
+impl FamilyArgs {
+    pub fn preferred(&self) -> Option<AddressFamily> {
+        match (self.prefer_ipv4, self.prefer_ipv6) {
+            (true, _) => Some(AddressFamily::Ipv4),
+            (_, true) => Some(AddressFamily::Ipv6),
+            _ => None,
+        }
+    }
+}
+
+/// The first address of `host`, or the host as it is when it can't be looked up
+async fn resolve(host: &str, port: u16) -> String {
+    match tokio::net::lookup_host((host, port)).await {
+        Ok(mut addresses) => match addresses.next() {
+            Some(address) => return address.to_string(),
+            None => eprintln!("warning: {} has no addresses", host),
+        },
+        Err(err) => eprintln!("warning: could not look up {}: {}", host, err),
+    }
+    format!("{}:{}", host, port)
 }
 
 /// For command: "peers"
 pub async fn execute(args: &PeersArgs) {
-    let torrent_metadata = TorrentMetadata::from_file(args.torrent_file_path.clone());
+    let torrent_metadata = read_usable_torrent(&args.torrent_file_path, &args.fetch);
 
-    let tracker_response = TrackerResponse::from(&torrent_metadata).await;
+    let mut trackers = shuffled_trackers(&torrent_metadata);
+    let mut request = TrackerResponse::first_request(&torrent_metadata);
+    request.num_want = args.numwant.unwrap_or(-1);
+    let (_, tracker_response) = TrackerResponse::from_request(
+        &request,
+        &mut trackers,
+        args.family.preferred(),
+        args.verbose,
+    )
+    .await;
 
-    tracker_response
-        .get_peers()
-        .iter()
-        .for_each(|peer_str| println!("{}", peer_str));
+    // compact and dictionary peer lists print the same
+    for peer in &tracker_response.peers {
+        match peer {
+            PeerAddress::Host(host, port) if args.resolve => {
+                println!("{}", resolve(host, *port).await)
+            }
+            peer => println!("{}", peer),
+        }
+    }
 }
@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::read_torrent_file;
+
+#[derive(Args, Debug)]
+pub struct PieceArgs {
+    /// torrent file path
+    torrent_file_path: PathBuf,
+    /// index of the piece, starting at 0
+    #[arg(required_unless_present = "all", conflicts_with = "all")]
+    piece_index: Option<usize>,
+    /// print a table of every piece instead
+    #[arg(long)]
+    all: bool,
+}
+
+/// For command: "piece"
+pub fn execute(args: &PieceArgs) {
+    let torrent_metadata = read_torrent_file(args.torrent_file_path.clone());
+    let info = &torrent_metadata.info;
+
+    if args.all {
+        let hashes = info.get_piece_hashes_str();
+        println!("{:>6}  {:>24}  {:>10}  hash", "index", "offset", "size");
+        for (index, hash) in hashes.iter().enumerate() {
+            // a torrent with too many hashes warns about it, their pieces are empty
+            let range = info.piece_range(index).unwrap_or_default();
+            let offset = format!("{}..{}", range.start, range.end);
+            println!(
+                "{:>6}  {:>24}  {:>10}  {}",
+                index,
+                offset,
+                range.end - range.start,
+                hash
+            );
+        }
+        return;
+    }
+
+    let piece_index = args.piece_index.expect("required unless --all");
+    // a torrent with fewer hashes than its length needs has no hash for its last pieces
+    let hashes = info.get_piece_hashes_str();
+    let (Some(range), Some(hash)) = (info.piece_range(piece_index), hashes.get(piece_index)) else {
+        match info.get_pieces_count() {
+            0 => eprintln!("error: the torrent has no pieces"),
+            count => eprintln!(
+                "error: piece index {} is out of range, valid indices are 0..={}",
+                piece_index,
+                count - 1
+            ),
+        }
+        std::process::exit(1);
+    };
+
+    println!("Piece: {}", piece_index);
+    println!("Hash: {}", hash);
+    println!("Offset: {}..{}", range.start, range.end);
+    println!("Size: {}", range.end - range.start);
+}
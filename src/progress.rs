@@ -0,0 +1,101 @@
+use std::io::IsTerminal;
+use std::time::{Duration, Instant};
+
+/// A terminal is redrawn at most this often
+const REDRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Without a terminal (like a log file) a line is written this often
+const LINE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Progress of a long task over pieces on stderr, like "verifying: 120/400 pieces (30%),
+/// 85.2 MB/s, ETA 1m 32s". A terminal gets one line that is redrawn in place, anything
+/// else a plain line now and then
+pub struct Progress {
+    label: &'static str,
+    total: usize,
+    total_bytes: u64,
+    done: usize,
+    done_bytes: u64,
+    started: Instant,
+    last_shown: Option<Instant>,
+    terminal: bool,
+}
+
+impl Progress {
+    pub fn new(label: &'static str, total: usize, total_bytes: u64) -> Self {
+        Self {
+            label,
+            total,
+            total_bytes,
+            done: 0,
+            done_bytes: 0,
+            started: Instant::now(),
+            last_shown: None,
+            terminal: std::io::stderr().is_terminal(),
+        }
+    }
+
+    /// One more piece of `bytes` is done
+    pub fn advance(&mut self, bytes: u64) {
+        self.done += 1;
+        self.done_bytes += bytes;
+
+        let interval = match self.terminal {
+            true => REDRAW_INTERVAL,
+            false => LINE_INTERVAL,
+        };
+        let shown_since = self.last_shown.unwrap_or(self.started).elapsed();
+        if shown_since >= interval {
+            self.show();
+        }
+    }
+
+    /// Shows the final numbers, a terminal line is ended
+    pub fn finish(&mut self) {
+        if self.last_shown.is_none() {
+            return;
+        }
+        self.show();
+        if self.terminal {
+            eprintln!();
+        }
+    }
+
+    fn show(&mut self) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = match elapsed > 0.0 {
+            true => self.done_bytes as f64 / elapsed,
+            false => 0.0,
+        };
+        let eta = match rate > 0.0 {
+            true => format_duration((self.total_bytes - self.done_bytes) as f64 / rate),
+            false => "unknown".to_string(),
+        };
+
+        let line = format!(
+            "{}: {}/{} pieces ({}%), {:.1} MB/s, ETA {}",
+            self.label,
+            self.done,
+            self.total,
+            self.done * 100 / self.total.max(1),
+            rate / 1e6,
+            eta
+        );
+        match self.terminal {
+            // "\x1b[K" clears what is left of a longer line before
+            true => eprint!("\r{}\x1b[K", line),
+            false => eprintln!("{}", line),
+        }
+        self.last_shown = Some(Instant::now());
+    }
+}
+
+/// Like "1h 02m", "3m 12s" or "12s"
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m {:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
@@ -0,0 +1,25 @@
+//! Random bytes from the system's random source, for peer ids, UDP transaction ids,
+//! the order of the trackers of a tier and the jitter of announces
+
+use std::io::{self, Read};
+
+/// Fills `bytes` from /dev/urandom
+///
+/// ```
+/// use bittorrent_starter_rust::random;
+///
+/// let (mut first, mut second) = ([0; 16], [0; 16]);
+/// random::fill_bytes(&mut first).unwrap();
+/// random::fill_bytes(&mut second).unwrap();
+/// assert_ne!(first, second);
+/// ```
+pub fn fill_bytes(bytes: &mut [u8]) -> io::Result<()> {
+    std::fs::File::open("/dev/urandom")?.read_exact(bytes)
+}
+
+/// A random `u64`, from 8 bytes of [`fill_bytes`]
+pub fn random_u64() -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    fill_bytes(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
@@ -0,0 +1,115 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::read_torrent_file;
+use crate::to_hex_string;
+use crate::torrent::TorrentMetadata;
+
+#[derive(Args, Debug)]
+pub struct SameArgs {
+    /// first torrent file path
+    first_torrent_file_path: PathBuf,
+    /// second torrent file path
+    second_torrent_file_path: PathBuf,
+}
+
+/// The first field where the content of the torrents differs, `None` when they describe
+/// the same data in the same files
+fn content_difference(first: &TorrentMetadata, second: &TorrentMetadata) -> Option<String> {
+    // names are compared as bytes, they are only decoded to be shown
+    let encodings = (
+        first.text_encoding().unwrap_or_default(),
+        second.text_encoding().unwrap_or_default(),
+    );
+    let (first, second) = (&first.info, &second.info);
+    if first.name != second.name {
+        return Some(format!(
+            "name {:?} != {:?}",
+            encodings.0.decode(&first.name),
+            encodings.1.decode(&second.name)
+        ));
+    }
+    if first.total_length() != second.total_length() {
+        return Some(format!(
+            "length {} != {}",
+            first.total_length(),
+            second.total_length()
+        ));
+    }
+    if first.piece_length != second.piece_length {
+        return Some(format!(
+            "piece length {} != {}",
+            first.piece_length, second.piece_length
+        ));
+    }
+
+    let first_hashes = first.get_piece_hashes_str();
+    let second_hashes = second.get_piece_hashes_str();
+    let pieces = first_hashes.iter().zip(&second_hashes).enumerate();
+    if let Some((index, (a, b))) = pieces.into_iter().find(|(_, (a, b))| a != b) {
+        return Some(format!("piece {} hash {} != {}", index, a, b));
+    }
+    if first_hashes.len() != second_hashes.len() {
+        return Some(format!(
+            "{} pieces != {}",
+            first_hashes.len(),
+            second_hashes.len()
+        ));
+    }
+
+    // the same bytes can still be split into other files
+    let files = first.file_ranges().into_iter().zip(second.file_ranges());
+    for ((a_path, a_range), (b_path, b_range)) in files {
+        if a_path != b_path || a_range != b_range {
+            return Some(format!(
+                "file {} ({} bytes) != {} ({} bytes)",
+                encodings.0.join(&a_path),
+                a_range.end - a_range.start,
+                encodings.1.join(&b_path),
+                b_range.end - b_range.start
+            ));
+        }
+    }
+    if first.file_ranges().len() != second.file_ranges().len() {
+        return Some(format!(
+            "{} files != {}",
+            first.file_ranges().len(),
+            second.file_ranges().len()
+        ));
+    }
+    // v2 only torrents have no "pieces", their merkle roots are compared instead
+    let roots = first.v2_files().into_iter().zip(second.v2_files());
+    for (a, b) in roots {
+        if a.pieces_root != b.pieces_root {
+            return Some(format!("pieces root of {} differs", a.path.join("/")));
+        }
+    }
+    None
+}
+
+/// For command: "same"
+pub fn execute(args: &SameArgs) {
+    let first = read_torrent_file(args.first_torrent_file_path.clone());
+    let second = read_torrent_file(args.second_torrent_file_path.clone());
+
+    if first.info_hash() == second.info_hash() {
+        println!(
+            "same: identical info hash {}",
+            to_hex_string(&first.info_hash().to_vec())
+        );
+        return;
+    }
+
+    match content_difference(&first, &second) {
+        // like a different "source" key in the info dictionary, for cross-seeding
+        None => {
+            println!("same content, different info hash");
+            std::process::exit(2);
+        }
+        Some(difference) => {
+            println!("different content: {}", difference);
+            std::process::exit(1);
+        }
+    }
+}
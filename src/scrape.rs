@@ -0,0 +1,115 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::tracker::{self, ScrapeStats};
+use crate::udp_tracker::{self, UdpTracker};
+use crate::{fail, read_torrent_file};
+
+#[derive(Args, Debug)]
+pub struct ScrapeArgs {
+    /// torrent file paths, the torrents of the same tracker are scraped together
+    #[arg(required = true)]
+    torrent_file_paths: Vec<PathBuf>,
+}
+
+/// The stats of `info_hashes` in the same order, `None` for the torrents the tracker
+/// doesn't know
+async fn scrape(
+    tracker: &str,
+    info_hashes: Vec<[u8; 20]>,
+) -> Result<Vec<Option<ScrapeStats>>, String> {
+    match udp_tracker::udp_address(tracker) {
+        Some(address) => scrape_udp(address.to_string(), info_hashes).await,
+        None => scrape_http(tracker, &info_hashes).await,
+    }
+}
+
+/// The socket is blocking so it gets a thread of its own, udp:// trackers answer for
+/// every torrent
+async fn scrape_udp(
+    address: String,
+    info_hashes: Vec<[u8; 20]>,
+) -> Result<Vec<Option<ScrapeStats>>, String> {
+    let stats =
+        tokio::task::spawn_blocking(move || UdpTracker::connect(&address)?.scrape(&info_hashes))
+            .await
+            .expect("the scrape thread does not panic")
+            .map_err(|err| err.to_string())?;
+    Ok(stats.into_iter().map(Some).collect())
+}
+
+async fn scrape_http(
+    tracker: &str,
+    info_hashes: &[[u8; 20]],
+) -> Result<Vec<Option<ScrapeStats>>, String> {
+    let not_supported = "scrape not supported by this tracker".to_string();
+    let Some(scrape_url) = tracker::scrape_url(tracker) else {
+        return Err(not_supported);
+    };
+    let url = tracker::scrape_request_url(&scrape_url, info_hashes);
+
+    let network_error = |err: reqwest::Error| format!("network error: {}", err);
+    let res = reqwest::get(url).await.map_err(network_error)?;
+    let status = res.status();
+    let res_bytes = res.bytes().await.map_err(network_error)?;
+
+    match tracker::parse_scrape_response(&res_bytes, info_hashes) {
+        Ok(stats) => Ok(stats),
+        Err(_) if status == reqwest::StatusCode::NOT_FOUND => Err(not_supported),
+        Err(_) if !status.is_success() => Err(format!("the tracker answered {}", status)),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// For command: "scrape"
+pub async fn execute(args: &ScrapeArgs) {
+    let torrents: Vec<_> = args
+        .torrent_file_paths
+        .iter()
+        .map(|path| read_torrent_file(path.clone()))
+        .collect();
+
+    // each torrent is scraped at its first tracker, in the order the trackers come up
+    let mut trackers: Vec<(&str, Vec<usize>)> = Vec::new();
+    for (index, torrent) in torrents.iter().enumerate() {
+        let Some(tracker) = torrent.all_trackers().first().copied() else {
+            fail(&format!(
+                "{} has no trackers",
+                args.torrent_file_paths[index].display()
+            ));
+        };
+        match trackers.iter_mut().find(|(known, _)| *known == tracker) {
+            Some((_, indices)) => indices.push(index),
+            None => trackers.push((tracker, vec![index])),
+        }
+    }
+
+    let mut stats = vec![ScrapeStats::default(); torrents.len()];
+    for (tracker, indices) in trackers {
+        let info_hashes = indices
+            .iter()
+            .map(|&index| torrents[index].info_hash())
+            .collect();
+        let scraped = scrape(tracker, info_hashes)
+            .await
+            .unwrap_or_else(|err| fail(&format!("scrape of {} failed: {}", tracker, err)));
+        for (index, scraped) in indices.into_iter().zip(scraped) {
+            match scraped {
+                Some(scraped) => stats[index] = scraped,
+                None => eprintln!(
+                    "warning: {} is not known by {}",
+                    args.torrent_file_paths[index].display(),
+                    tracker
+                ),
+            }
+        }
+    }
+
+    for (path, stats) in args.torrent_file_paths.iter().zip(stats) {
+        println!("Torrent: {}", path.display());
+        println!("Seeders: {}", stats.seeders);
+        println!("Completed: {}", stats.completed);
+        println!("Leechers: {}", stats.leechers);
+    }
+}
@@ -0,0 +1,150 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use serde::Serialize;
+
+use crate::fail;
+use crate::fetch::{self, FetchArgs};
+use crate::torrent::{format_size, PieceGeometry, TorrentContent, TorrentMetadata};
+
+#[derive(Args, Debug)]
+pub struct StatsArgs {
+    /// torrent file path
+    torrent_file_path: PathBuf,
+    /// print a single json object instead of lines
+    #[arg(long)]
+    json: bool,
+}
+
+/// Sizes of the metadata and how the payload is cut into pieces, to judge a piece length.
+/// All sizes are in bytes
+///
+/// - `metadata_size`: the torrent file, `pieces_size` the SHA-1 hashes in it
+/// - `payload_length`: the files without padding, `padding_length` the padding files
+/// - `average_file_size`: payload length divided by the number of files
+/// - `last_piece_waste`: bytes the last piece is short of a whole piece, of every file
+///   for v2 only torrents (their files each start a piece)
+/// - `boundary_pieces`: pieces with parts of more than one file, `null` for single
+///   file torrents
+#[derive(Serialize)]
+struct StatsOutput {
+    metadata_size: u64,
+    pieces_size: u64,
+    file_count: usize,
+    payload_length: u64,
+    padding_length: u64,
+    piece_length: u32,
+    piece_count: usize,
+    average_file_size: u64,
+    last_piece_waste: u64,
+    boundary_pieces: Option<usize>,
+}
+
+impl StatsOutput {
+    fn new(torrent_metadata: &TorrentMetadata, metadata_size: u64) -> Self {
+        let info = &torrent_metadata.info;
+        let piece_length = u64::from(info.piece_length);
+
+        // v2 files don't share pieces, each of them is cut on its own
+        let geometries: Vec<PieceGeometry> = match info.content() {
+            TorrentContent::FileTree { files } => files
+                .iter()
+                .map(|file| PieceGeometry::new(file.length, piece_length))
+                .collect(),
+            _ => vec![info.piece_geometry()],
+        };
+        let (file_count, padding_length) = match info.content() {
+            TorrentContent::SingleFile { .. } => (1, 0),
+            TorrentContent::MultiFile { files } => {
+                let padding = files.iter().filter(|file| file.is_padding());
+                (
+                    files.len() - padding.clone().count(),
+                    padding.map(|file| file.length).sum(),
+                )
+            }
+            TorrentContent::FileTree { files } => (files.len(), 0),
+        };
+        let payload_length = info.total_length() - padding_length;
+
+        Self {
+            metadata_size,
+            pieces_size: info.pieces.len() as u64,
+            file_count,
+            payload_length,
+            padding_length,
+            piece_length: info.piece_length,
+            piece_count: geometries.iter().map(PieceGeometry::count).sum(),
+            average_file_size: payload_length.checked_div(file_count as u64).unwrap_or(0),
+            last_piece_waste: geometries
+                .iter()
+                .filter(|geometry| geometry.count() > 0)
+                .map(|geometry| geometry.piece_length - geometry.last_piece_size())
+                .sum(),
+            boundary_pieces: boundary_pieces(torrent_metadata),
+        }
+    }
+
+    fn print(&self) {
+        let size = |bytes: u64| format!("{} ({})", bytes, format_size(bytes));
+        println!("Metadata Size: {}", size(self.metadata_size));
+        println!("Pieces Size: {}", size(self.pieces_size));
+        println!("Files: {}", self.file_count);
+        println!("Payload: {}", size(self.payload_length));
+        if self.padding_length > 0 {
+            println!("Padding: {}", size(self.padding_length));
+        }
+        println!("Piece Length: {}", size(u64::from(self.piece_length)));
+        println!("Piece Count: {}", self.piece_count);
+        println!("Average File Size: {}", size(self.average_file_size));
+        println!("Last Piece Waste: {}", size(self.last_piece_waste));
+        if let Some(boundary_pieces) = self.boundary_pieces {
+            println!(
+                "Pieces Spanning Files: {}/{}",
+                boundary_pieces, self.piece_count
+            );
+        }
+    }
+}
+
+/// Pieces with parts of two or more files of a multi-file torrent, padding files don't
+/// count as they are there to keep the next file from sharing a piece
+fn boundary_pieces(torrent_metadata: &TorrentMetadata) -> Option<usize> {
+    let TorrentContent::MultiFile { files } = torrent_metadata.info.content() else {
+        return None;
+    };
+
+    let mut pieces: Vec<usize> = torrent_metadata
+        .info
+        .file_slices()
+        .into_iter()
+        .filter(|slice| !files[slice.file_index].is_padding())
+        .map(|slice| slice.piece_index)
+        .collect();
+    pieces.sort_unstable();
+    let spanning = pieces.windows(2).filter(|pair| pair[0] == pair[1]);
+
+    // a piece with three files is counted once
+    let mut spanning: Vec<usize> = spanning.map(|pair| pair[0]).collect();
+    spanning.dedup();
+    Some(spanning.len())
+}
+
+/// For command: "stats"
+pub fn execute(args: &StatsArgs) {
+    let path = fetch::source_name(&args.torrent_file_path);
+    let file_contents = fetch::read_torrent_input(&args.torrent_file_path, &FetchArgs::default());
+    let torrent_metadata = TorrentMetadata::from_bytes(&file_contents)
+        .unwrap_or_else(|err| fail(&format!("{} is not a valid torrent: {}", path, err)));
+    for skipped in &torrent_metadata.skipped {
+        eprintln!("warning: {}", skipped);
+    }
+
+    let output = StatsOutput::new(&torrent_metadata, file_contents.len() as u64);
+    match args.json {
+        true => println!(
+            "{}",
+            serde_json::to_string_pretty(&output).expect("Could not format json")
+        ),
+        false => output.print(),
+    }
+}
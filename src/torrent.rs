@@ -0,0 +1,1868 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+
+use crate::bencode::{self, borrowed, de, ser, DecodeOptions, Value};
+use crate::sha256::sha256;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Info {
+    /// in the torrent's [`TextEncoding`] (see [`TorrentMetadata::text_encoding`]), the
+    /// bytes are kept as they are for the info hash
+    pub name: ByteBuf,
+    /// SHA-1 of each piece, empty for v2 only torrents (see [`Info::is_v2_only`]). Owned
+    /// rather than borrowed from the file like [`crate::bencode::de::from_bytes`] allows:
+    /// the torrent readers of the commands drop the bytes they read it from, and torrents
+    /// that are created or edited have pieces no input holds
+    #[serde(default)]
+    pub pieces: ByteBuf,
+    #[serde(rename = "piece length")]
+    pub piece_length: u32,
+    /// only for single file torrents, multi-file ones list their `files` instead
+    pub length: Option<u64>,
+    pub files: Option<Vec<FileEntry>>,
+    /// 1 for private torrents, their peers must only come from the trackers (no DHT or PEX)
+    pub private: Option<i64>,
+    /// 2 for BitTorrent v2 torrents (BEP 52), which describe their files in `file_tree`
+    #[serde(rename = "meta version")]
+    pub meta_version: Option<i64>,
+    /// nested dictionaries of directory and file names, see [`Info::v2_files`]
+    #[serde(rename = "file tree")]
+    pub file_tree: Option<Value>,
+    /// hex MD5 of the file of a single file torrent, see [`parse_md5sum`]
+    pub md5sum: Option<ByteBuf>,
+    /// set by private trackers so the same content gets another info hash on each of them,
+    /// bytes since it is free text like [`TorrentMetadata::comment`]
+    pub source: Option<ByteBuf>,
+    /// keys of the info dictionary that have no field of their own (like "x_cross_seed")
+    #[serde(flatten)]
+    pub extra: BTreeMap<ByteBuf, Value>,
+}
+
+/// File of a multi-file torrent
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileEntry {
+    pub length: u64,
+    /// directory names followed by the file name, bytes like [`Info::name`]
+    pub path: Vec<ByteBuf>,
+    /// BEP 47 attributes, one letter each: 'p' padding, 'x' executable, 'l' symlink and
+    /// 'h' hidden, other letters are kept but mean nothing
+    pub attr: Option<ByteBuf>,
+    /// target of a symlink ('l' in `attr`), components from the torrent's root directory
+    #[serde(rename = "symlink path")]
+    pub symlink_path: Option<Vec<ByteBuf>>,
+    /// hex MD5 of the whole file, some torrents have it besides the piece hashes
+    pub md5sum: Option<ByteBuf>,
+    /// keys without a field of their own (like "sha1")
+    #[serde(flatten)]
+    pub extra: BTreeMap<ByteBuf, Value>,
+}
+
+/// What a torrent holds, from "length", "files" or the v2 "file tree" of its info dictionary
+#[derive(Debug, Clone, PartialEq)]
+pub enum TorrentContent<'a> {
+    SingleFile {
+        length: u64,
+    },
+    MultiFile {
+        files: &'a [FileEntry],
+    },
+    /// v2 only torrents have neither of the v1 keys
+    FileTree {
+        files: Vec<V2File>,
+    },
+}
+
+/// How the payload is cut into pieces, every piece is `piece_length` bytes but the last
+/// one, which has what is left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PieceGeometry {
+    pub length: u64,
+    pub piece_length: u64,
+}
+
+impl PieceGeometry {
+    /// ```
+    /// use bittorrent_starter_rust::torrent::PieceGeometry;
+    ///
+    /// // exact multiple, one byte over and a single short piece
+    /// let exact = PieceGeometry::new(65536, 32768);
+    /// assert_eq!((exact.count(), exact.last_piece_size()), (2, 32768));
+    /// let over = PieceGeometry::new(65537, 32768);
+    /// assert_eq!((over.count(), over.last_piece_size()), (3, 1));
+    /// assert_eq!(over.piece(2), Some((65536, 1)));
+    /// assert_eq!(over.piece(3), None);
+    /// let short = PieceGeometry::new(5, 32768);
+    /// assert_eq!(short.pieces().collect::<Vec<_>>(), [(0, 5)]);
+    ///
+    /// // nothing to cut
+    /// assert_eq!(PieceGeometry::new(0, 32768).count(), 0);
+    /// assert_eq!(PieceGeometry::new(5, 0).count(), 0);
+    /// ```
+    pub fn new(length: u64, piece_length: u64) -> Self {
+        Self {
+            length,
+            piece_length,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        match (self.length, self.piece_length) {
+            (0, _) | (_, 0) => 0,
+            (length, piece_length) => ((length - 1) / piece_length + 1) as usize,
+        }
+    }
+
+    /// Bytes of the last piece, a whole piece when the length is a multiple of it
+    pub fn last_piece_size(&self) -> u64 {
+        last_piece_length(self.length, self.piece_length)
+    }
+
+    /// Offset and size of piece `index`, `None` past the last piece
+    pub fn piece(&self, index: usize) -> Option<(u64, u64)> {
+        if index >= self.count() {
+            return None;
+        }
+
+        let offset = index as u64 * self.piece_length;
+        Some((offset, self.piece_length.min(self.length - offset)))
+    }
+
+    /// Offset and size of every piece in order
+    pub fn pieces(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        (0..self.count()).filter_map(|index| self.piece(index))
+    }
+
+    /// Index of the piece with byte `offset` of the payload, `None` past the end
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::PieceGeometry;
+    ///
+    /// let geometry = PieceGeometry::new(40, 16);
+    /// assert_eq!(geometry.index_at(15), Some(0));
+    /// assert_eq!(geometry.index_at(16), Some(1));
+    /// assert_eq!(geometry.index_at(39), Some(2));
+    /// assert_eq!(geometry.index_at(40), None);
+    /// ```
+    pub fn index_at(&self, offset: u64) -> Option<usize> {
+        match offset < self.length && self.piece_length > 0 {
+            true => Some((offset / self.piece_length) as usize),
+            false => None,
+        }
+    }
+}
+
+/// Part of a file that is in a piece, a piece can hold parts of several files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileSlice {
+    pub file_index: usize,
+    pub piece_index: usize,
+    /// where the part starts in the file
+    pub file_offset: u64,
+    /// where the part starts in the piece
+    pub piece_offset: u64,
+    pub length: u64,
+}
+
+/// File of a v2 torrent's file tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct V2File {
+    /// directory names followed by the file name
+    pub path: Vec<String>,
+    pub length: u64,
+    /// root of the file's merkle tree (SHA-256), missing for empty files
+    pub pieces_root: Option<Vec<u8>>,
+}
+
+/// Adds the files under `tree` to `files`, a file is a dictionary with an empty key
+fn walk_file_tree(tree: &Value, path: &mut Vec<String>, files: &mut Vec<V2File>) {
+    for (name, entry) in tree.as_dict().unwrap_or_default() {
+        if name.is_empty() {
+            let field = |key: &[u8]| entry.get(key);
+            files.push(V2File {
+                path: path.clone(),
+                length: field(b"length")
+                    .and_then(|length| length.as_int()?.as_u64())
+                    .unwrap_or_default(),
+                pieces_root: field(b"pieces root")
+                    .and_then(|root| root.as_bytes())
+                    .map(|root| root.to_vec()),
+            });
+            continue;
+        }
+
+        path.push(String::from_utf8_lossy(name).into_owned());
+        walk_file_tree(entry, path, files);
+        path.pop();
+    }
+}
+
+impl FileEntry {
+    fn has_attr(&self, letter: u8) -> bool {
+        self.attr
+            .as_ref()
+            .is_some_and(|attr| attr.contains(&letter))
+    }
+
+    /// Padding files (BEP 47) only align the next file to a piece, they have a 'p' in
+    /// their "attr" (older torrents only put them in a ".pad" directory). Their bytes are
+    /// part of the pieces but they aren't written to disk
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TorrentMetadata;
+    ///
+    /// let files = "d6:lengthi3e4:pathl1:aeed4:attr1:p6:lengthi1e4:pathl4:.pad1:1ee\
+    ///     d6:lengthi4e4:pathl1:bee";
+    /// let encoded = format!(
+    ///     "d4:infod5:filesl{}e4:name1:d12:piece lengthi4e6:pieces40:{}ee",
+    ///     files,
+    ///     "x".repeat(40)
+    /// );
+    /// let info = TorrentMetadata::from_bytes(encoded.as_bytes()).unwrap().info;
+    ///
+    /// let files = info.files.as_ref().unwrap();
+    /// assert!(!files[0].is_padding() && files[1].is_padding());
+    /// // the padding still takes up its bytes, "b" starts the second piece
+    /// assert_eq!(info.file_ranges()[2].1, 4..8);
+    /// assert_eq!(info.file_slices().last().unwrap().piece_index, 1);
+    /// ```
+    pub fn is_padding(&self) -> bool {
+        self.has_attr(b'p')
+            || self
+                .path
+                .first()
+                .is_some_and(|directory| directory.as_slice() == b".pad")
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.has_attr(b'x')
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.has_attr(b'h')
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.has_attr(b'l')
+    }
+
+    /// Checks that a symlink points to something in the torrent, so writing it can't
+    /// expose files elsewhere on the disk, `Ok` for other files
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::{SymlinkError, TorrentMetadata};
+    ///
+    /// let with_target = |target: &str| {
+    ///     let encoded = format!(
+    ///         "d4:infod5:filesld4:attr2:lq6:lengthi0e4:pathl4:link\
+    ///          e12:symlink pathl{}eee4:name1:d12:piece lengthi4e6:pieces0:ee",
+    ///         target
+    ///     );
+    ///     let torrent = TorrentMetadata::from_bytes(encoded.as_bytes()).unwrap();
+    ///     torrent.info.files.unwrap().remove(0)
+    /// };
+    ///
+    /// // unknown letters like 'q' are ignored
+    /// assert!(with_target("1:a1:b").is_symlink());
+    /// assert_eq!(with_target("1:a1:b").check_symlink(), Ok(()));
+    /// assert_eq!(
+    ///     with_target("2:..3:etc").check_symlink(),
+    ///     Err(SymlinkError::OutsideRoot("link".to_string(), "../etc".to_string()))
+    /// );
+    /// ```
+    pub fn check_symlink(&self) -> Result<(), SymlinkError> {
+        if !self.is_symlink() {
+            return Ok(());
+        }
+        let path = TextEncoding::Utf8.join(&self.path);
+        let Some(target) = &self.symlink_path else {
+            return Err(SymlinkError::NoTarget(path));
+        };
+
+        // the target is checked like a path, ".." or a '/' could leave the root
+        match sanitize_path(target) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(SymlinkError::OutsideRoot(
+                path,
+                TextEncoding::Utf8.join(target),
+            )),
+        }
+    }
+
+    /// Where the file goes in the torrent's directory, with the bytes of the torrent
+    /// rather than its names transcoded, so other clients find the same files. See
+    /// [`sanitize_path`] for the paths that are refused
+    pub fn relative_path(&self) -> Result<PathBuf, UnsafePath> {
+        sanitize_path(&self.path)
+    }
+}
+
+impl Info {
+    /// The single file, the files of a multi-file torrent or the file tree of a v2 only
+    /// torrent, parsing fails for info dictionaries that have none of them
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::{TorrentContent, TorrentMetadata};
+    ///
+    /// let encoded = b"d4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16e6:pieces0:ee";
+    /// let torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// assert_eq!(torrent.info.content(), TorrentContent::SingleFile { length: 5 });
+    ///
+    /// // files over 4 GiB, like most ISO images, fit too
+    /// let encoded = format!(
+    ///     "d4:infod6:lengthi5000000000e4:name5:a.iso12:piece lengthi4194304e6:pieces{}:{}ee",
+    ///     1193 * 20,
+    ///     "x".repeat(1193 * 20)
+    /// );
+    /// let torrent = TorrentMetadata::from_bytes(encoded.as_bytes()).unwrap();
+    /// assert_eq!(torrent.info.total_length(), 5_000_000_000);
+    /// assert!(torrent.info.check_limits(10_000_000).is_empty());
+    ///
+    /// let stub = b"d4:infod4:name5:a.txt12:piece lengthi16e6:pieces0:ee";
+    /// assert_eq!(
+    ///     TorrentMetadata::from_bytes(stub).unwrap_err().to_string(),
+    ///     "info dictionary has neither 'length' nor 'files'"
+    /// );
+    /// ```
+    pub fn content(&self) -> TorrentContent<'_> {
+        match (&self.length, &self.files) {
+            (Some(length), _) => TorrentContent::SingleFile { length: *length },
+            (None, Some(files)) => TorrentContent::MultiFile { files },
+            (None, None) => TorrentContent::FileTree {
+                files: self.v2_files(),
+            },
+        }
+    }
+
+    /// Length of the single file, or the sum of the files of a multi-file torrent
+    /// (or of the file tree of a v2 only torrent)
+    pub fn total_length(&self) -> u64 {
+        match self.content() {
+            TorrentContent::SingleFile { length } => length,
+            TorrentContent::MultiFile { files } => files.iter().map(|file| file.length).sum(),
+            TorrentContent::FileTree { files } => files.iter().map(|file| file.length).sum(),
+        }
+    }
+
+    /// True for BitTorrent v2 torrents, hybrid ones included
+    pub fn is_v2(&self) -> bool {
+        self.meta_version == Some(2)
+    }
+
+    /// True for v2 torrents without the v1 "pieces", which have no SHA-1 info hash
+    /// other clients would use
+    pub fn is_v2_only(&self) -> bool {
+        self.is_v2() && self.pieces.is_empty() && self.length.is_none() && self.files.is_none()
+    }
+
+    /// True for v2 torrents that also have the v1 fields, both info hashes can be used
+    pub fn is_hybrid(&self) -> bool {
+        self.is_v2() && !self.is_v2_only()
+    }
+
+    /// Checks that the v1 files and the v2 file tree of a hybrid torrent have the same
+    /// paths and lengths (v1 padding files are left out), `Ok` for other torrents
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::{HybridMismatch, TorrentMetadata};
+    ///
+    /// let hybrid = |v1_length: u64| {
+    ///     let encoded = format!(
+    ///         "d4:infod9:file treed5:a.txtd0:d6:lengthi5e11:pieces root32:{}eee\
+    ///          6:lengthi{}e12:meta versioni2e4:name5:a.txt12:piece lengthi16384e\
+    ///          6:pieces20:{}ee",
+    ///         "r".repeat(32),
+    ///         v1_length,
+    ///         "x".repeat(20)
+    ///     );
+    ///     TorrentMetadata::from_bytes(encoded.as_bytes()).unwrap().info
+    /// };
+    ///
+    /// assert!(hybrid(5).is_hybrid());
+    /// assert_eq!(hybrid(5).check_hybrid(), Ok(()));
+    /// assert_eq!(
+    ///     hybrid(6).check_hybrid(),
+    ///     Err(HybridMismatch::NotInFileTree("a.txt".to_string(), 6))
+    /// );
+    /// ```
+    pub fn check_hybrid(&self) -> Result<(), HybridMismatch> {
+        if !self.is_hybrid() {
+            return Ok(());
+        }
+
+        // names are UTF-8 in v2 torrents, the v1 names of a hybrid one too
+        let mut v1_files: Vec<(String, u64)> = match self.content() {
+            TorrentContent::MultiFile { files } => files
+                .iter()
+                .filter(|file| !file.is_padding())
+                .map(|file| (TextEncoding::Utf8.join(&file.path), file.length))
+                .collect(),
+            _ => vec![(TextEncoding::Utf8.decode(&self.name), self.total_length())],
+        };
+        let mut v2_files: Vec<(String, u64)> = self
+            .v2_files()
+            .into_iter()
+            .map(|file| (file.path.join("/"), file.length))
+            .collect();
+        v1_files.sort();
+        v2_files.sort();
+
+        if let Some((path, length)) = v1_files.iter().find(|file| !v2_files.contains(file)) {
+            return Err(HybridMismatch::NotInFileTree(path.clone(), *length));
+        }
+        if let Some((path, length)) = v2_files.iter().find(|file| !v1_files.contains(file)) {
+            return Err(HybridMismatch::NotInFiles(path.clone(), *length));
+        }
+        Ok(())
+    }
+
+    /// Files of the v2 file tree in order, empty for v1 torrents
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TorrentMetadata;
+    ///
+    /// let encoded = b"d4:infod9:file treed5:a.txtd0:d6:lengthi5e11:pieces root32:\
+    ///     rrrrrrrrrrrrrrrrrrrrrrrrrrrrrrrree3:dird5:b.bind0:d6:lengthi20000e11:pieces root32:\
+    ///     sssssssssssssssssssssssssssssssseeee12:meta versioni2e4:name1:v\
+    ///     12:piece lengthi16384ee12:piece layersdee";
+    /// let torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// assert!(torrent.info.is_v2_only());
+    ///
+    /// let files = torrent.info.v2_files();
+    /// assert_eq!(files[1].path, ["dir", "b.bin"]);
+    /// assert_eq!(files[1].length, 20000);
+    /// assert_eq!(files[0].pieces_root.as_deref(), Some(&[b'r'; 32][..]));
+    /// assert_eq!(torrent.info.total_length(), 20005);
+    ///
+    /// let hex: String = torrent.info_hash_v2().iter().map(|byte| format!("{:02x}", byte)).collect();
+    /// assert_eq!(hex, "220d3e765443c144cb886f10543b9b43f57a15cbc499f3911de91f3e66b35ef4");
+    /// ```
+    pub fn v2_files(&self) -> Vec<V2File> {
+        let mut files = Vec::new();
+        if let Some(tree) = &self.file_tree {
+            walk_file_tree(tree, &mut Vec::new(), &mut files);
+        }
+        files
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.private == Some(1)
+    }
+
+    /// Path and payload byte range of every file in order, a single file torrent
+    /// has one file named after the torrent
+    pub fn file_ranges(&self) -> Vec<(Vec<ByteBuf>, Range<u64>)> {
+        let TorrentContent::MultiFile { files } = self.content() else {
+            return vec![(vec![self.name.clone()], 0..self.total_length())];
+        };
+
+        let ranges = file_offsets(files.iter().map(|file| file.length));
+        files
+            .iter()
+            .map(|file| file.path.clone())
+            .zip(ranges)
+            .collect()
+    }
+
+    /// Parts of the files in each piece, ordered by file and then by piece (sort by
+    /// `piece_index` for the reverse mapping), empty files are in no piece
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::{FileSlice, TorrentMetadata};
+    ///
+    /// let files = "d6:lengthi3e4:pathl1:aeed6:lengthi0e4:pathl1:beed6:lengthi6e4:pathl1:cee";
+    /// let encoded = format!(
+    ///     "d4:infod5:filesl{}e4:name1:d12:piece lengthi4e6:pieces60:{}ee",
+    ///     files,
+    ///     "x".repeat(60)
+    /// );
+    /// let info = TorrentMetadata::from_bytes(encoded.as_bytes()).unwrap().info;
+    ///
+    /// let slice = |file_index, piece_index, file_offset, piece_offset, length| FileSlice {
+    ///     file_index, piece_index, file_offset, piece_offset, length,
+    /// };
+    /// assert_eq!(
+    ///     info.file_slices(),
+    ///     [
+    ///         slice(0, 0, 0, 0, 3),
+    ///         slice(2, 0, 0, 3, 1),
+    ///         slice(2, 1, 1, 0, 4),
+    ///         slice(2, 2, 5, 0, 1),
+    ///     ]
+    /// );
+    /// ```
+    pub fn file_slices(&self) -> Vec<FileSlice> {
+        let piece_length = u64::from(self.piece_length);
+        if piece_length == 0 {
+            return Vec::new();
+        }
+
+        let mut slices = Vec::new();
+        for (file_index, (_, file)) in self.file_ranges().into_iter().enumerate() {
+            if file.is_empty() {
+                continue;
+            }
+
+            let first_piece = file.start / piece_length;
+            let last_piece = (file.end - 1) / piece_length;
+            for piece_index in first_piece..=last_piece {
+                let piece_start = piece_index * piece_length;
+                let start = file.start.max(piece_start);
+                let end = file.end.min(piece_start + piece_length);
+                slices.push(FileSlice {
+                    file_index,
+                    piece_index: piece_index as usize,
+                    file_offset: start - file.start,
+                    piece_offset: start - piece_start,
+                    length: end - start,
+                });
+            }
+        }
+        slices
+    }
+
+    /// See [`check_pieces`], v2 only torrents have no "pieces" to check
+    pub fn check_pieces(&self) -> Result<(), PiecesError> {
+        if self.is_v2_only() {
+            return Ok(());
+        }
+        check_pieces(
+            self.pieces.len(),
+            self.total_length(),
+            u64::from(self.piece_length),
+        )
+    }
+
+    /// Pieces of the torrent, each file of a v2 only torrent starts a piece so its pieces
+    /// are counted one file at a time
+    pub fn piece_count(&self) -> u64 {
+        let count = |length: u64| PieceGeometry::new(length, u64::from(self.piece_length)).count();
+        match self.content() {
+            TorrentContent::FileTree { files } => {
+                files.iter().map(|file| count(file.length) as u64).sum()
+            }
+            _ => count(self.total_length()) as u64,
+        }
+    }
+
+    /// See [`check_limits`], with the hashes in "pieces" checked against the length (see
+    /// [`check_pieces`]) as well
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::{LimitError, TorrentMetadata};
+    ///
+    /// // a 2 EB file in 1 byte pieces, with a single hash
+    /// let encoded = format!(
+    ///     "d4:infod5:filesld6:lengthi2000000000000000000e4:pathl1:aeee4:name1:d\
+    ///     12:piece lengthi1e6:pieces20:{}ee",
+    ///     "x".repeat(20)
+    /// );
+    /// let info = TorrentMetadata::from_bytes(encoded.as_bytes()).unwrap().info;
+    ///
+    /// let errors = info.check_limits(10_000_000);
+    /// assert_eq!(errors.len(), 3);
+    /// assert_eq!(errors[0], LimitError::PieceLengthTooSmall(1));
+    /// assert!(errors.iter().skip(1).all(LimitError::is_hard));
+    /// ```
+    pub fn check_limits(&self, max_pieces: u64) -> Vec<LimitError> {
+        let mut errors = check_limits(u64::from(self.piece_length), self.piece_count(), max_pieces);
+        // a piece length of 0 is reported once
+        if self.piece_length > 0 {
+            if let Err(err) = self.check_pieces() {
+                errors.push(LimitError::Pieces(err));
+            }
+        }
+        errors
+    }
+
+    /// Path and error of each "md5sum" that isn't 32 hex characters, those can't be
+    /// checked but the pieces still can
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::{Md5sumError, TorrentMetadata};
+    /// use serde_bytes::ByteBuf;
+    ///
+    /// let files = "d6:lengthi1e6:md5sum32:0cc175b9c0f1b6a831c399e269772661\
+    ///     4:pathl1:aeed6:lengthi1e6:md5sum3:xyz4:pathl1:bee";
+    /// let encoded = format!(
+    ///     "d4:infod5:filesl{}e4:name1:d12:piece lengthi4e6:pieces20:{}ee",
+    ///     files,
+    ///     "x".repeat(20)
+    /// );
+    /// let info = TorrentMetadata::from_bytes(encoded.as_bytes()).unwrap().info;
+    ///
+    /// assert_eq!(
+    ///     info.check_md5sums(),
+    ///     vec![(vec![ByteBuf::from("b")], Md5sumError::WrongLength(3))]
+    /// );
+    /// ```
+    pub fn check_md5sums(&self) -> Vec<(Vec<ByteBuf>, Md5sumError)> {
+        let md5sums: Vec<(Vec<ByteBuf>, &ByteBuf)> = match self.content() {
+            TorrentContent::MultiFile { files } => files
+                .iter()
+                .filter_map(|file| Some((file.path.clone(), file.md5sum.as_ref()?)))
+                .collect(),
+            _ => self
+                .md5sum
+                .iter()
+                .map(|md5sum| (vec![self.name.clone()], md5sum))
+                .collect(),
+        };
+
+        md5sums
+            .into_iter()
+            .filter_map(|(path, md5sum)| Some((path, parse_md5sum(md5sum).err()?)))
+            .collect()
+    }
+
+    /// The name and every file path that [`sanitize_path`] refuses, padding files are
+    /// left out as they are never written
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::{TorrentMetadata, UnsafePath};
+    /// use serde_bytes::ByteBuf;
+    ///
+    /// let files = "d6:lengthi1e4:pathl2:..6:passwdeed6:lengthi1e4:pathl1:aee";
+    /// let encoded = format!(
+    ///     "d4:infod5:filesl{}e4:name3:C:d12:piece lengthi4e6:pieces20:{}ee",
+    ///     files,
+    ///     "x".repeat(20)
+    /// );
+    /// let info = TorrentMetadata::from_bytes(encoded.as_bytes()).unwrap().info;
+    ///
+    /// let paths = info.check_paths();
+    /// assert_eq!(paths[0].1, UnsafePath::DrivePrefix("C:d".to_string()));
+    /// assert_eq!(paths[1].0, [ByteBuf::from(".."), ByteBuf::from("passwd")]);
+    /// assert_eq!(paths.len(), 2);
+    /// ```
+    pub fn check_paths(&self) -> Vec<(Vec<ByteBuf>, UnsafePath)> {
+        let mut paths = vec![vec![self.name.clone()]];
+        match self.content() {
+            TorrentContent::MultiFile { files } => paths.extend(
+                files
+                    .iter()
+                    .filter(|file| !file.is_padding())
+                    .map(|file| file.path.clone()),
+            ),
+            TorrentContent::FileTree { files } => paths.extend(
+                files
+                    .into_iter()
+                    .map(|file| file.path.into_iter().map(ByteBuf::from).collect::<Vec<_>>()),
+            ),
+            TorrentContent::SingleFile { .. } => {}
+        }
+
+        paths
+            .into_iter()
+            .filter_map(|path| Some((path.clone(), sanitize_path(&path).err()?)))
+            .collect()
+    }
+
+    /// Bytes of the payload covered by a piece, `None` past the last piece, which is
+    /// shorter unless the length is a multiple of the piece length
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TorrentMetadata;
+    ///
+    /// let torrent = |length: u64, pieces: usize| {
+    ///     let mut encoded = format!("d4:infod6:lengthi{}e4:name1:a12:piece lengthi16e", length);
+    ///     encoded += &format!("6:pieces{}:{}ee", pieces * 20, "x".repeat(pieces * 20));
+    ///     TorrentMetadata::from_bytes(encoded.as_bytes()).unwrap()
+    /// };
+    ///
+    /// let short_last_piece = torrent(40, 3).info;
+    /// assert_eq!(short_last_piece.piece_range(0), Some(0..16));
+    /// assert_eq!(short_last_piece.piece_range(2), Some(32..40));
+    /// assert_eq!(short_last_piece.piece_range(3), None);
+    ///
+    /// let exact_multiple = torrent(32, 2).info;
+    /// assert_eq!(exact_multiple.piece_range(1), Some(16..32));
+    /// assert_eq!(exact_multiple.piece_range(2), None);
+    /// ```
+    pub fn piece_range(&self, index: usize) -> Option<Range<u64>> {
+        let (offset, size) = self.piece_geometry().piece(index)?;
+        Some(offset..offset + size)
+    }
+
+    /// Offsets and sizes of the v1 pieces, from the length and the piece length
+    pub fn piece_geometry(&self) -> PieceGeometry {
+        PieceGeometry::new(self.total_length(), u64::from(self.piece_length))
+    }
+
+    pub fn get_pieces_count(&self) -> usize {
+        self.pieces.chunks(20).count()
+    }
+
+    pub fn get_piece_hashes(&self) -> Vec<Vec<u8>> {
+        self.pieces.chunks(20).map(|chunk| chunk.to_vec()).collect()
+    }
+
+    pub fn get_piece_hashes_str(&self) -> Vec<String> {
+        self.get_piece_hashes()
+            .iter()
+            .map(|hash| hash.iter().map(|byte| format!("{:02x}", byte)).collect())
+            .collect()
+    }
+}
+
+/// Size in bytes with a binary unit, like "16 KiB" or "12.7 MiB"
+///
+/// ```
+/// use bittorrent_starter_rust::torrent::format_size;
+///
+/// assert_eq!(format_size(16384), "16 KiB");
+/// assert_eq!(format_size(13_316_915), "12.7 MiB");
+/// assert_eq!(format_size(92063), "89.9 KiB");
+/// assert_eq!(format_size(5), "5 B");
+/// ```
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match size.fract() == 0.0 {
+        true => format!("{} {}", size, UNITS[unit]),
+        false => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+/// SHA-1 of every `piece_length` bytes read from `reader` (the last piece is shorter),
+/// concatenated like the "pieces" of the info dictionary, nothing for an empty reader
+///
+/// ```
+/// use bittorrent_starter_rust::torrent::hash_pieces;
+///
+/// let pieces = hash_pieces(&b"abcdefghij"[..], 4).unwrap();
+/// let hashes: Vec<String> = pieces
+///     .chunks(20)
+///     .map(|hash| hash.iter().map(|byte| format!("{:02x}", byte)).collect())
+///     .collect();
+/// assert_eq!(
+///     hashes,
+///     [
+///         "81fe8bfe87576c3ecb22426f8e57847382917acf",
+///         "2aed8aa9f826c21ef07d5ee15b48eea06e9c8a62",
+///         "4cfa380a7a05ae26270f5ea888009520ab54b677",
+///     ]
+/// );
+///
+/// // an exact multiple has no empty piece at the end
+/// assert_eq!(hash_pieces(&b"abcdefgh"[..], 4).unwrap().len(), 40);
+/// assert!(hash_pieces(&b""[..], 4).unwrap().is_empty());
+/// ```
+pub fn hash_pieces<R: Read>(mut reader: R, piece_length: usize) -> io::Result<Vec<u8>> {
+    assert!(piece_length > 0, "piece length should not be 0");
+
+    let mut pieces = Vec::new();
+    let mut piece = vec![0; piece_length];
+    loop {
+        // a read can return less than asked for, fill the piece unless the reader ends
+        let mut filled = 0;
+        while filled < piece_length {
+            match reader.read(&mut piece[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        if filled > 0 {
+            pieces.extend(Sha1::digest(&piece[..filled]));
+        }
+        if filled < piece_length {
+            return Ok(pieces);
+        }
+    }
+}
+
+/// Smallest and largest piece length [`auto_piece_length`] picks
+const MIN_AUTO_PIECE_LENGTH: u32 = 16 * 1024;
+const MAX_AUTO_PIECE_LENGTH: u32 = 16 * 1024 * 1024;
+/// Most pieces [`auto_piece_length`] aims for, the smallest power of two that stays below
+/// it gives between half of it and all of it
+const MAX_AUTO_PIECES: u64 = 2000;
+
+/// Piece length for a torrent of `length` bytes: the power of two between 16 KiB and
+/// 16 MiB that gives 1000 to 2000 pieces (fewer for small torrents, more for huge ones)
+///
+/// ```
+/// use bittorrent_starter_rust::torrent::auto_piece_length;
+///
+/// assert_eq!(auto_piece_length(5_000_000), 16 * 1024);
+/// assert_eq!(auto_piece_length(700_000_000), 512 * 1024);
+/// assert_eq!(auto_piece_length(40_000_000_000), 16 * 1024 * 1024);
+/// assert_eq!(auto_piece_length(0), 16 * 1024);
+/// ```
+pub fn auto_piece_length(length: u64) -> u32 {
+    let mut piece_length = MIN_AUTO_PIECE_LENGTH;
+    while piece_length < MAX_AUTO_PIECE_LENGTH
+        && length / u64::from(piece_length) >= MAX_AUTO_PIECES
+    {
+        piece_length *= 2;
+    }
+    piece_length
+}
+
+/// Bytes in the last piece, which is a whole piece when the length is a multiple
+/// of the piece length (0 for an empty torrent)
+///
+/// ```
+/// use bittorrent_starter_rust::torrent::last_piece_length;
+///
+/// // exact multiple
+/// assert_eq!(last_piece_length(65536, 32768), 32768);
+/// // one byte over
+/// assert_eq!(last_piece_length(65537, 32768), 1);
+/// // single piece
+/// assert_eq!(last_piece_length(5, 32768), 5);
+/// assert_eq!(last_piece_length(0, 32768), 0);
+/// ```
+pub fn last_piece_length(length: u64, piece_length: u64) -> u64 {
+    if length == 0 || piece_length == 0 {
+        return 0;
+    }
+
+    match length % piece_length {
+        0 => piece_length,
+        rest => rest,
+    }
+}
+
+/// Payload byte range of each file when the files are put one after the other, padding
+/// files included. [`Info::file_ranges`] and so [`Info::file_slices`] use it too
+///
+/// ```
+/// use bittorrent_starter_rust::torrent::file_offsets;
+///
+/// assert_eq!(file_offsets([3, 0, 5]), [0..3, 3..3, 3..8]);
+/// ```
+pub fn file_offsets(lengths: impl IntoIterator<Item = u64>) -> Vec<Range<u64>> {
+    let mut offset = 0;
+    lengths
+        .into_iter()
+        .map(|length| {
+            let range = offset..offset + length;
+            offset = range.end;
+            range
+        })
+        .collect()
+}
+
+/// A file that only one of the layouts of a hybrid torrent has
+#[derive(Error, Debug, PartialEq)]
+pub enum HybridMismatch {
+    #[error("v1 file {0} ({1} bytes) is not in the v2 file tree")]
+    NotInFileTree(String, u64),
+    #[error("v2 file {0} ({1} bytes) is not in the v1 files")]
+    NotInFiles(String, u64),
+}
+
+/// Why a symlink of a torrent must not be created
+#[derive(Error, Debug, PartialEq)]
+pub enum SymlinkError {
+    #[error("symlink {0} has no \"symlink path\"")]
+    NoTarget(String),
+    #[error("symlink {0} points outside the torrent ({1})")]
+    OutsideRoot(String, String),
+}
+
+/// Why a name or path of a torrent can't be a path on disk, a client writing it could
+/// end up outside its download directory
+#[derive(Error, Debug, PartialEq)]
+pub enum UnsafePath {
+    #[error("the path is empty or has an empty component")]
+    Empty,
+    #[error("{0:?} leaves its directory")]
+    Traversal(String),
+    #[error("{0:?} is an absolute path")]
+    Absolute(String),
+    #[error("{0:?} has a drive prefix")]
+    DrivePrefix(String),
+    #[error("{0:?} has a path separator")]
+    Separator(String),
+    #[error("{0:?} has a NUL byte")]
+    Nul(String),
+    #[error("{0:?} is a reserved name on Windows")]
+    Reserved(String),
+    #[error("a component is {0} bytes long, more than 255")]
+    TooLong(usize),
+}
+
+/// Names Windows keeps for devices, with any extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn check_component(component: &[u8]) -> Result<(), UnsafePath> {
+    let text = || TextEncoding::Utf8.decode(component);
+    // the part before the extension, Windows ignores trailing spaces and dots too
+    let stem = text()
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .trim_end()
+        .to_ascii_uppercase();
+
+    match component {
+        [] => Err(UnsafePath::Empty),
+        _ if component.len() > 255 => Err(UnsafePath::TooLong(component.len())),
+        b"." | b".." => Err(UnsafePath::Traversal(text())),
+        [b'/' | b'\\', ..] => Err(UnsafePath::Absolute(text())),
+        [letter, b':', ..] if letter.is_ascii_alphabetic() => Err(UnsafePath::DrivePrefix(text())),
+        _ if component.contains(&0) => Err(UnsafePath::Nul(text())),
+        _ if component.iter().any(|&byte| byte == b'/' || byte == b'\\') => {
+            Err(UnsafePath::Separator(text()))
+        }
+        _ if RESERVED_NAMES.contains(&stem.as_str()) => Err(UnsafePath::Reserved(text())),
+        _ => Ok(()),
+    }
+}
+
+/// The relative path of the components, checked so it stays in the directory it is
+/// joined to on every system, the first hostile component is rejected
+///
+/// ```
+/// use bittorrent_starter_rust::torrent::{sanitize_path, UnsafePath};
+/// use std::path::PathBuf;
+///
+/// let path = |components: &[&str]| sanitize_path(components);
+/// assert_eq!(path(&["dir", "a.txt"]), Ok(PathBuf::from("dir/a.txt")));
+/// assert_eq!(path(&["CONSOLE", "icon.d"]), Ok(PathBuf::from("CONSOLE/icon.d")));
+///
+/// assert_eq!(path(&[]), Err(UnsafePath::Empty));
+/// assert_eq!(path(&["dir", ""]), Err(UnsafePath::Empty));
+/// assert_eq!(path(&["..", "etc"]), Err(UnsafePath::Traversal("..".to_string())));
+/// assert_eq!(path(&["."]), Err(UnsafePath::Traversal(".".to_string())));
+/// assert_eq!(path(&["/etc"]), Err(UnsafePath::Absolute("/etc".to_string())));
+/// assert_eq!(path(&["\\\\server"]), Err(UnsafePath::Absolute("\\\\server".to_string())));
+/// assert_eq!(path(&["C:"]), Err(UnsafePath::DrivePrefix("C:".to_string())));
+/// assert_eq!(path(&["c:x.txt"]), Err(UnsafePath::DrivePrefix("c:x.txt".to_string())));
+/// assert_eq!(path(&["a/../b"]), Err(UnsafePath::Separator("a/../b".to_string())));
+/// assert_eq!(path(&["a\\b"]), Err(UnsafePath::Separator("a\\b".to_string())));
+/// assert_eq!(path(&["a\0b"]), Err(UnsafePath::Nul("a\0b".to_string())));
+/// assert_eq!(path(&["nul"]), Err(UnsafePath::Reserved("nul".to_string())));
+/// assert_eq!(path(&["Com1.txt"]), Err(UnsafePath::Reserved("Com1.txt".to_string())));
+/// assert_eq!(path(&["aux .tar.gz"]), Err(UnsafePath::Reserved("aux .tar.gz".to_string())));
+/// assert_eq!(path(&["a".repeat(256).as_str()]), Err(UnsafePath::TooLong(256)));
+/// assert!(path(&["a".repeat(255).as_str()]).is_ok());
+/// ```
+pub fn sanitize_path(components: &[impl AsRef<[u8]>]) -> Result<PathBuf, UnsafePath> {
+    if components.is_empty() {
+        return Err(UnsafePath::Empty);
+    }
+
+    let mut path = PathBuf::new();
+    for component in components {
+        check_component(component.as_ref())?;
+        path.push(path_component(component.as_ref()));
+    }
+    Ok(path)
+}
+
+/// Why an "md5sum" can't be used
+#[derive(Error, Debug, PartialEq)]
+pub enum Md5sumError {
+    #[error("it is {0} characters long instead of 32")]
+    WrongLength(usize),
+    #[error("it is not hexadecimal")]
+    NotHex,
+}
+
+/// The 16 bytes of a hex "md5sum", upper or lower case
+///
+/// ```
+/// use bittorrent_starter_rust::torrent::{parse_md5sum, Md5sumError};
+///
+/// let md5sum = parse_md5sum(b"D41D8CD98F00B204E9800998ECF8427E").unwrap();
+/// assert_eq!(md5sum[..2], [0xd4, 0x1d]);
+/// assert_eq!(parse_md5sum(b"d41d8cd9"), Err(Md5sumError::WrongLength(8)));
+/// assert_eq!(parse_md5sum(&[b'g'; 32]), Err(Md5sumError::NotHex));
+/// ```
+pub fn parse_md5sum(md5sum: &[u8]) -> Result<[u8; 16], Md5sumError> {
+    if md5sum.len() != 32 {
+        return Err(Md5sumError::WrongLength(md5sum.len()));
+    }
+
+    let digit = |c: u8| (c as char).to_digit(16).ok_or(Md5sumError::NotHex);
+    let mut bytes = [0; 16];
+    for (byte, pair) in bytes.iter_mut().zip(md5sum.chunks(2)) {
+        *byte = (digit(pair[0])? * 16 + digit(pair[1])?) as u8;
+    }
+    Ok(bytes)
+}
+
+/// Why the "pieces" of a torrent don't fit its length
+#[derive(Error, Debug, PartialEq)]
+pub enum PiecesError {
+    #[error("pieces length {0} is not a multiple of 20")]
+    NotMultipleOf20(usize),
+    #[error("piece length is 0")]
+    ZeroPieceLength,
+    #[error(
+        "expected {expected} piece hashes for {} at {} pieces, found {found}",
+        format_size(*.length),
+        format_size(*.piece_length)
+    )]
+    WrongCount {
+        expected: u64,
+        found: u64,
+        length: u64,
+        piece_length: u64,
+    },
+}
+
+/// Checks that `pieces` holds one 20 byte hash for every piece of the torrent
+///
+/// ```
+/// use bittorrent_starter_rust::torrent::check_pieces;
+///
+/// assert!(check_pieces(60, 92063, 32768).is_ok());
+/// assert_eq!(
+///     check_pieces(1234, 92063, 32768).unwrap_err().to_string(),
+///     "pieces length 1234 is not a multiple of 20"
+/// );
+/// assert_eq!(
+///     check_pieces(810 * 20, 13_316_915, 16384).unwrap_err().to_string(),
+///     "expected 813 piece hashes for 12.7 MiB at 16 KiB pieces, found 810"
+/// );
+/// ```
+pub fn check_pieces(pieces_len: usize, length: u64, piece_length: u64) -> Result<(), PiecesError> {
+    let found = (pieces_len / 20) as u64;
+    if found * 20 != pieces_len as u64 {
+        return Err(PiecesError::NotMultipleOf20(pieces_len));
+    }
+    if piece_length == 0 {
+        return Err(PiecesError::ZeroPieceLength);
+    }
+
+    // the last piece is shorter unless the length is a multiple of the piece length
+    let expected = match length {
+        0 => 0,
+        length => (length - 1) / piece_length + 1,
+    };
+    match expected == found {
+        true => Ok(()),
+        false => Err(PiecesError::WrongCount {
+            expected,
+            found,
+            length,
+            piece_length,
+        }),
+    }
+}
+
+/// Smallest piece length clients make, smaller ones mean millions of pieces to track for
+/// a large payload
+pub const MIN_PIECE_LENGTH: u64 = 16 * 1024;
+
+/// Largest piece length clients make, a piece is held in memory until it is checked
+pub const MAX_PIECE_LENGTH: u64 = 128 * 1024 * 1024;
+
+/// Pieces a torrent can have before it is refused, unless the command is given another
+/// limit. At 16 KiB pieces that is about 150 GiB
+pub const DEFAULT_MAX_PIECES: u64 = 10_000_000;
+
+/// Why a torrent is unusual enough that clients would misbehave on it
+#[derive(Error, Debug, PartialEq)]
+pub enum LimitError {
+    #[error(
+        "piece length {} is below {}, clients may refuse the torrent or track far too many pieces",
+        format_size(*.0),
+        format_size(MIN_PIECE_LENGTH)
+    )]
+    PieceLengthTooSmall(u64),
+    #[error(
+        "piece length {} is above {}, clients may refuse the torrent or run out of memory checking a piece",
+        format_size(*.0),
+        format_size(MAX_PIECE_LENGTH)
+    )]
+    PieceLengthTooLarge(u64),
+    #[error("the torrent has {count} pieces, more than the {max} allowed")]
+    TooManyPieces { count: u64, max: u64 },
+    #[error("{0}")]
+    Pieces(#[from] PiecesError),
+}
+
+impl LimitError {
+    /// True when nothing can be downloaded from the torrent (or not without allocating
+    /// for an absurd number of pieces), an unusual piece length only makes it unusual
+    pub fn is_hard(&self) -> bool {
+        match self {
+            LimitError::PieceLengthTooSmall(piece_length) => *piece_length == 0,
+            LimitError::PieceLengthTooLarge(_) => false,
+            LimitError::TooManyPieces { .. } | LimitError::Pieces(_) => true,
+        }
+    }
+}
+
+/// Checks that the piece length is between [`MIN_PIECE_LENGTH`] and [`MAX_PIECE_LENGTH`]
+/// and that there are at most `max_pieces` pieces
+///
+/// ```
+/// use bittorrent_starter_rust::torrent::{check_limits, LimitError};
+///
+/// assert!(check_limits(32768, 3, 10_000_000).is_empty());
+/// assert_eq!(
+///     check_limits(1, 50_000_000, 10_000_000),
+///     [
+///         LimitError::PieceLengthTooSmall(1),
+///         LimitError::TooManyPieces { count: 50_000_000, max: 10_000_000 },
+///     ]
+/// );
+/// assert_eq!(
+///     check_limits(1 << 28, 1, 10_000_000)[0].to_string(),
+///     "piece length 256 MiB is above 128 MiB, clients may refuse the torrent or run out of \
+///      memory checking a piece"
+/// );
+/// ```
+pub fn check_limits(piece_length: u64, piece_count: u64, max_pieces: u64) -> Vec<LimitError> {
+    let mut errors = Vec::new();
+    if piece_length < MIN_PIECE_LENGTH {
+        errors.push(LimitError::PieceLengthTooSmall(piece_length));
+    }
+    if piece_length > MAX_PIECE_LENGTH {
+        errors.push(LimitError::PieceLengthTooLarge(piece_length));
+    }
+    if piece_count > max_pieces {
+        errors.push(LimitError::TooManyPieces {
+            count: piece_count,
+            max: max_pieces,
+        });
+    }
+    errors
+}
+
+/// Last second that still has a four digit year, 9999-12-31T23:59:59Z
+const MAX_FORMATTED_TIMESTAMP: i64 = 253_402_300_799;
+
+/// ISO-8601 UTC rendering of a unix timestamp, `None` before 1970 or after year 9999
+///
+/// ```
+/// use bittorrent_starter_rust::torrent::format_utc_timestamp;
+///
+/// assert_eq!(format_utc_timestamp(1622550896).unwrap(), "2021-06-01T12:34:56Z");
+/// assert_eq!(format_utc_timestamp(-1), None);
+/// ```
+pub fn format_utc_timestamp(seconds: i64) -> Option<String> {
+    if !(0..=MAX_FORMATTED_TIMESTAMP).contains(&seconds) {
+        return None;
+    }
+
+    let days = seconds / 86_400;
+    let seconds_of_day = seconds % 86_400;
+
+    // days since 1970-01-01 to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    Some(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    ))
+}
+
+/// An entry of a list of the torrent that was left out, see [`TorrentMetadata::skipped`]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SkippedEntry {
+    #[error("skipping empty {0} entry")]
+    EmptyUrl(&'static str),
+    #[error("skipping {key} entry {url:?}, it is not valid UTF-8")]
+    InvalidUrl { key: &'static str, url: String },
+    #[error("skipping nodes entry, it is not a [host, port] pair")]
+    InvalidNode,
+}
+
+thread_local! {
+    /// Entries the deserializers below skipped, [`TorrentMetadata::from_bytes`] moves
+    /// them to the torrent (serde has no way to hand them to it)
+    static SKIPPED_ENTRIES: RefCell<Vec<SkippedEntry>> = const { RefCell::new(Vec::new()) };
+}
+
+fn skip_entry(entry: SkippedEntry) {
+    SKIPPED_ENTRIES.with(|skipped| skipped.borrow_mut().push(entry));
+}
+
+/// Urls of a list that are valid UTF-8 and not empty, the others are skipped
+fn url_entries(urls: Vec<ByteBuf>, key: &'static str) -> Vec<String> {
+    urls.into_iter()
+        .filter_map(|url| match String::from_utf8(url.into_vec()) {
+            Ok(url) if url.is_empty() => {
+                skip_entry(SkippedEntry::EmptyUrl(key));
+                None
+            }
+            Ok(url) => Some(url),
+            Err(err) => {
+                skip_entry(SkippedEntry::InvalidUrl {
+                    key,
+                    url: String::from_utf8_lossy(err.as_bytes()).into_owned(),
+                });
+                None
+            }
+        })
+        .collect()
+}
+
+/// Reads the tiers as bytes so a tracker url that isn't UTF-8 only loses that entry, a
+/// tier left with no urls is dropped
+fn deserialize_announce_list<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<Vec<String>>>, D::Error> {
+    let tiers: Option<Vec<Vec<ByteBuf>>> = Deserialize::deserialize(deserializer)?;
+
+    Ok(tiers.map(|tiers| {
+        tiers
+            .into_iter()
+            .map(|tier| url_entries(tier, "announce-list"))
+            .filter(|tier| !tier.is_empty())
+            .collect()
+    }))
+}
+
+/// A single url or a list of them, "url-list" is found encoded both ways
+fn one_or_many_urls<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+    key: &'static str,
+) -> Result<Option<Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(ByteBuf),
+        Many(Vec<ByteBuf>),
+    }
+
+    let urls = match Option::<OneOrMany>::deserialize(deserializer)? {
+        Some(OneOrMany::One(url)) => vec![url],
+        Some(OneOrMany::Many(urls)) => urls,
+        None => return Ok(None),
+    };
+    Ok(Some(url_entries(urls, key)))
+}
+
+fn deserialize_url_list<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    one_or_many_urls(deserializer, "url-list")
+}
+
+fn deserialize_httpseeds<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    one_or_many_urls(deserializer, "httpseeds")
+}
+
+/// DHT bootstrap nodes are `[host, port]` lists, malformed ones are skipped
+fn deserialize_nodes<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<(String, u16)>>, D::Error> {
+    let nodes: Option<Vec<Value>> = Deserialize::deserialize(deserializer)?;
+
+    Ok(nodes.map(|nodes| {
+        nodes
+            .iter()
+            .filter_map(|node| {
+                let pair = match node.as_list() {
+                    Some([host, port]) => host.as_str().zip(port.as_int()),
+                    _ => None,
+                };
+                let node = pair.and_then(|(host, port)| {
+                    let port = u16::try_from(port.as_u64()?).ok()?;
+                    Some((host.to_string(), port))
+                });
+                if node.is_none() {
+                    skip_entry(SkippedEntry::InvalidNode);
+                }
+                node
+            })
+            .collect()
+    }))
+}
+
+/// Which extension an http source comes from, they are requested differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpSeedKind {
+    /// BEP 19 "url-list", the url is the file (or the directory of a multi-file torrent)
+    WebSeed,
+    /// BEP 17 "httpseeds", the url is a script that is asked for pieces by info hash
+    HttpSeed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpSource<'a> {
+    pub url: &'a str,
+    pub kind: HttpSeedKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TorrentMetadata {
+    /// missing from trackerless torrents, which only list DHT `nodes`
+    pub announce: Option<String>,
+    /// tiers of tracker urls, entries that aren't UTF-8 are skipped and so are tiers
+    /// without any other
+    #[serde(
+        rename = "announce-list",
+        default,
+        deserialize_with = "deserialize_announce_list",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub announce_list: Option<Vec<Vec<String>>>,
+    /// web seeds (BEP 19), a single url is written back as a list with one entry
+    #[serde(
+        rename = "url-list",
+        default,
+        deserialize_with = "deserialize_url_list",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub url_list: Option<Vec<String>>,
+    /// http seeds (BEP 17)
+    #[serde(
+        default,
+        deserialize_with = "deserialize_httpseeds",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub httpseeds: Option<Vec<String>>,
+    /// DHT bootstrap nodes of trackerless torrents
+    #[serde(
+        default,
+        deserialize_with = "deserialize_nodes",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub nodes: Option<Vec<(String, u16)>>,
+    /// merkle tree layers of the v2 files larger than a piece, keyed by their pieces root
+    #[serde(rename = "piece layers")]
+    pub piece_layers: Option<BTreeMap<ByteBuf, ByteBuf>>,
+    pub info: Info,
+    /// free text fields are kept as bytes, they are not always valid UTF-8
+    pub comment: Option<ByteBuf>,
+    #[serde(rename = "created by")]
+    pub created_by: Option<ByteBuf>,
+    pub encoding: Option<ByteBuf>,
+    /// unix timestamp, see [`format_utc_timestamp`]
+    #[serde(rename = "creation date")]
+    pub creation_date: Option<i64>,
+    /// top level keys that have no field of their own
+    #[serde(flatten)]
+    pub extra: BTreeMap<ByteBuf, Value>,
+    /// the torrent file as it was read
+    #[serde(skip)]
+    raw: Vec<u8>,
+    /// where the "info" dictionary is in `raw`
+    #[serde(skip)]
+    info_span: Range<usize>,
+    /// entries of "announce-list", "url-list", "httpseeds" and "nodes" that were left
+    /// out of their field, for the warnings of the commands
+    #[serde(skip)]
+    pub skipped: Vec<SkippedEntry>,
+}
+
+impl TorrentMetadata {
+    /// A torrent that wasn't read from a file, like one that is being created
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::{Info, TorrentMetadata};
+    ///
+    /// let encoded = b"d6:lengthi5e4:name5:a.txt12:piece lengthi16e6:pieces0:e";
+    /// let info: Info = bittorrent_starter_rust::bencode::de::from_bytes(encoded).unwrap();
+    /// let torrent = TorrentMetadata::new(Some("http://t/".to_string()), info);
+    ///
+    /// let parsed = TorrentMetadata::from_bytes(&torrent.to_bytes()).unwrap();
+    /// assert_eq!(parsed.info_hash(), torrent.info_hash());
+    /// assert_eq!(parsed.info, torrent.info);
+    /// ```
+    pub fn new(announce: Option<String>, info: Info) -> Self {
+        Self {
+            announce,
+            announce_list: None,
+            url_list: None,
+            httpseeds: None,
+            nodes: None,
+            piece_layers: None,
+            info,
+            comment: None,
+            created_by: None,
+            encoding: None,
+            creation_date: None,
+            extra: BTreeMap::new(),
+            raw: Vec::new(),
+            info_span: 0..0,
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Parses a torrent file, keys without a field are kept in `extra`
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TorrentMetadata;
+    ///
+    /// let encoded = b"d8:announce9:http://t/7:comment2:hi4:infod6:lengthi5e4:name5:a.txt\
+    ///     12:piece lengthi16e6:pieces0:7:privatei1eee";
+    /// let torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// assert_eq!(torrent.info.length, Some(5));
+    /// assert_eq!(torrent.to_bytes(), encoded);
+    /// ```
+    ///
+    /// Unknown keys are written back, at the top level and in the info dictionary:
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TorrentMetadata;
+    ///
+    /// let encoded = b"d8:announce9:http://t/4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16e\
+    ///     6:pieces0:12:x_cross_seed4:abcde6:x_tagsl1:ae7:x_weirdi7ee";
+    /// let mut torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// let info_hash = torrent.info_hash();
+    /// torrent.announce = Some("http://new/".to_string());
+    ///
+    /// let edited = TorrentMetadata::from_bytes(&torrent.to_bytes()).unwrap();
+    /// assert_eq!(edited.announce.as_deref(), Some("http://new/"));
+    /// assert_eq!(edited.extra.len(), 2);
+    /// let key = serde_bytes::ByteBuf::from("x_cross_seed");
+    /// assert!(edited.info.extra.contains_key(&key));
+    /// assert_eq!(edited.info_hash(), info_hash);
+    ///
+    /// // encoded again, the info dictionary keeps its unknown key too
+    /// torrent.reencode_info();
+    /// let reencoded = TorrentMetadata::from_bytes(&torrent.to_bytes()).unwrap();
+    /// assert_eq!(reencoded.info.extra, edited.info.extra);
+    /// assert_eq!(reencoded.info_hash(), info_hash);
+    /// ```
+    ///
+    /// List entries that can't be used are left out and kept in `skipped`:
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::{SkippedEntry, TorrentMetadata};
+    ///
+    /// let encoded = b"d4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16e6:pieces0:e\
+    ///     5:nodesll1:hi99999eel1:hi6881eee8:url-listl0:2:\xff/9:http://w/ee";
+    /// let torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// assert_eq!(torrent.nodes, Some(vec![("h".to_string(), 6881)]));
+    /// assert_eq!(torrent.webseeds(), ["http://w/"]);
+    /// assert_eq!(
+    ///     torrent.skipped,
+    ///     [
+    ///         SkippedEntry::InvalidNode,
+    ///         SkippedEntry::EmptyUrl("url-list"),
+    ///         SkippedEntry::InvalidUrl { key: "url-list", url: "\u{fffd}/".to_string() },
+    ///     ]
+    /// );
+    /// ```
+    pub fn from_bytes(encoded: &[u8]) -> Result<Self, de::Error> {
+        SKIPPED_ENTRIES.with(|skipped| skipped.borrow_mut().clear());
+        let mut torrent_metadata: Self = de::from_bytes(encoded)?;
+        torrent_metadata.skipped = SKIPPED_ENTRIES.with(|skipped| skipped.take());
+
+        let (spanned, _) = borrowed::parse_spanned(encoded, &DecodeOptions::default())?;
+        torrent_metadata.info_span = match spanned.get(b"info") {
+            Some(info) => info.span(),
+            None => return Err(serde::de::Error::missing_field("info")),
+        };
+        // stub torrents of broken tools have no files at all, v2 ones have a file tree
+        let info = &torrent_metadata.info;
+        if info.length.is_none() && info.files.is_none() && info.file_tree.is_none() {
+            return Err(serde::de::Error::custom(
+                "info dictionary has neither 'length' nor 'files'",
+            ));
+        }
+        torrent_metadata.raw = encoded.to_vec();
+
+        Ok(torrent_metadata)
+    }
+
+    /// Forgets the info dictionary as it was read, so changes to `info` are written by
+    /// [`TorrentMetadata::to_bytes`], it is encoded canonically and the info hash changes
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TorrentMetadata;
+    /// use serde_bytes::ByteBuf;
+    ///
+    /// let encoded = b"d4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16e6:pieces0:ee";
+    /// let mut torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// torrent.info.source = Some(ByteBuf::from("TRACKER"));
+    /// torrent.reencode_info();
+    ///
+    /// // "source" goes between "pieces" and the end, in sorted order
+    /// assert_eq!(
+    ///     torrent.to_bytes(),
+    ///     b"d4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16e6:pieces0:6:source7:TRACKERee"
+    /// );
+    /// ```
+    pub fn reencode_info(&mut self) {
+        self.raw.clear();
+        self.info_span = 0..0;
+    }
+
+    /// Encodes the torrent with its keys sorted, the "info" dictionary of a parsed
+    /// torrent is copied byte for byte from the file so the info hash doesn't change,
+    /// even when it isn't canonical (edits to `info` only show up for torrents that
+    /// weren't parsed)
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TorrentMetadata;
+    ///
+    /// // "name" before "length" is out of order
+    /// let encoded = b"d8:announce9:http://t/4:infod4:name5:a.txt6:lengthi5e\
+    ///     12:piece lengthi16e6:pieces0:ee";
+    /// let mut torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// torrent.announce = Some("http://new/".to_string());
+    ///
+    /// let edited = TorrentMetadata::from_bytes(&torrent.to_bytes()).unwrap();
+    /// assert_eq!(edited.announce.as_deref(), Some("http://new/"));
+    /// assert_eq!(edited.info_hash(), torrent.info_hash());
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let value = ser::to_value(self).expect("torrent metadata is always valid bencode");
+        let Value::Dict(entries) = &value else {
+            unreachable!("a struct is encoded as a dictionary")
+        };
+        if self.raw.is_empty() {
+            return bencode::encode_bencoded_value(&value);
+        }
+
+        let mut encoded = b"d".to_vec();
+        for (key, value) in entries {
+            encoded.extend(bencode::encode_bencoded_value(&Value::Bytes(key.clone())));
+            match key.as_slice() {
+                b"info" => encoded.extend_from_slice(self.info_span()),
+                _ => encoded.extend(bencode::encode_bencoded_value(value)),
+            }
+        }
+        encoded.push(b'e');
+        encoded
+    }
+
+    /// Every tracker url, `announce` first and then the tiers in order, without duplicates
+    /// (empty for trackerless torrents)
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TorrentMetadata;
+    ///
+    /// let encoded = b"d8:announce9:http://t/13:announce-listll9:http://t/8:http://ae\
+    ///     l8:http://b2:\xff\xfeel2:\xff\xfeee\
+    ///     4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16e6:pieces0:ee";
+    /// let torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// // the last tier only had a url that isn't UTF-8
+    /// let tiers = torrent.announce_list.as_ref().unwrap();
+    /// assert_eq!(tiers.len(), 2);
+    /// assert_eq!(tiers[1], ["http://b"]);
+    /// assert_eq!(torrent.all_trackers(), ["http://t/", "http://a", "http://b"]);
+    /// ```
+    pub fn all_trackers(&self) -> Vec<&str> {
+        let mut trackers: Vec<&str> = self.announce.as_deref().into_iter().collect();
+        for url in self.announce_list.iter().flatten().flatten() {
+            if !trackers.contains(&url.as_str()) {
+                trackers.push(url);
+            }
+        }
+        trackers
+    }
+
+    /// True when the torrent has no tracker, its peers can only be found through the DHT
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TorrentMetadata;
+    ///
+    /// let encoded = b"d4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16e\
+    ///     6:pieces20:xxxxxxxxxxxxxxxxxxxxe5:nodesll9:127.0.0.1i6881eel3:badeee";
+    /// let torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// assert!(torrent.is_trackerless());
+    /// assert_eq!(torrent.announce, None);
+    /// assert_eq!(torrent.nodes, Some(vec![("127.0.0.1".to_string(), 6881)]));
+    ///
+    /// // everything but the trackers is still there
+    /// assert_eq!(torrent.info.total_length(), 5);
+    /// assert_eq!(torrent.info.get_piece_hashes_str(), ["78".repeat(20)]);
+    /// assert_eq!(torrent.info.piece_range(0), Some(0..5));
+    /// ```
+    pub fn is_trackerless(&self) -> bool {
+        self.all_trackers().is_empty()
+    }
+
+    /// Web seed urls from "url-list", empty when the torrent has none
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TorrentMetadata;
+    ///
+    /// let encoded = b"d8:announce9:http://t/4:infod6:lengthi5e4:name5:a.txt\
+    ///     12:piece lengthi16e6:pieces0:e8:url-list9:http://w/e";
+    /// let torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// assert_eq!(torrent.webseeds(), ["http://w/"]);
+    ///
+    /// let encoded = b"d8:announce9:http://t/4:infod6:lengthi5e4:name5:a.txt\
+    ///     12:piece lengthi16e6:pieces0:e8:url-listl9:http://w/0:9:http://x/ee";
+    /// let torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// assert_eq!(torrent.webseeds(), ["http://w/", "http://x/"]);
+    /// ```
+    pub fn webseeds(&self) -> &[String] {
+        self.url_list.as_deref().unwrap_or_default()
+    }
+
+    /// Web seeds followed by http seeds, a url that is in both is only kept as a web seed
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::{HttpSeedKind, HttpSource, TorrentMetadata};
+    ///
+    /// let encoded = b"d8:announce9:http://t/9:httpseedsl9:http://w/9:http://h/e4:infod6:lengthi5e\
+    ///     4:name5:a.txt12:piece lengthi16e6:pieces0:e8:url-list9:http://w/e";
+    /// let torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// assert_eq!(
+    ///     torrent.http_sources(),
+    ///     [
+    ///         HttpSource { url: "http://w/", kind: HttpSeedKind::WebSeed },
+    ///         HttpSource { url: "http://h/", kind: HttpSeedKind::HttpSeed },
+    ///     ]
+    /// );
+    /// ```
+    pub fn http_sources(&self) -> Vec<HttpSource<'_>> {
+        let webseeds = self
+            .webseeds()
+            .iter()
+            .map(|url| (url, HttpSeedKind::WebSeed));
+        let httpseeds = self.httpseeds.iter().flatten();
+        let httpseeds = httpseeds.map(|url| (url, HttpSeedKind::HttpSeed));
+
+        let mut sources: Vec<HttpSource> = Vec::new();
+        for (url, kind) in webseeds.chain(httpseeds) {
+            if !sources.iter().any(|source| source.url == url) {
+                sources.push(HttpSource { url, kind });
+            }
+        }
+        sources
+    }
+
+    /// The "info" dictionary exactly as it is encoded in the torrent file,
+    /// re-encoding `Info` would lose the original key order
+    pub fn info_span(&self) -> &[u8] {
+        &self.raw[self.info_span.clone()]
+    }
+
+    /// SHA-1 of the raw info dictionary, keys `Info` doesn't know about are part of it
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TorrentMetadata;
+    ///
+    /// let encoded = b"d8:announce9:http://t/4:infod6:lengthi5e4:name5:a.txt\
+    ///     12:piece lengthi16e6:pieces0:7:privatei1eee";
+    /// let torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// assert!(torrent.info.is_private());
+    /// let hex: String = torrent.info_hash().iter().map(|byte| format!("{:02x}", byte)).collect();
+    /// assert_eq!(hex, "034e4b7e6bc93cde07d666bb6c541e6165aa36e5");
+    /// ```
+    pub fn info_hash(&self) -> [u8; 20] {
+        let bytes = match self.raw.is_empty() {
+            true => ser::to_bytes(&self.info).expect("info is always valid bencode"),
+            false => self.info_span().to_vec(),
+        };
+
+        Sha1::digest(bytes).into()
+    }
+
+    /// SHA-256 of the raw info dictionary, the v2 info hash (trackers and peers that
+    /// only take 20 bytes get the first 20)
+    pub fn info_hash_v2(&self) -> [u8; 32] {
+        match self.raw.is_empty() {
+            true => sha256(&ser::to_bytes(&self.info).expect("info is always valid bencode")),
+            false => sha256(self.info_span()),
+        }
+    }
+
+    /// The codec of names and paths named by "encoding", UTF-8 when there is none and
+    /// `None` when it isn't one of [`TextEncoding`] (like GBK)
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::{TextEncoding, TorrentMetadata};
+    ///
+    /// let with_encoding = |encoding: &[u8]| {
+    ///     let encoded = [
+    ///         b"d".as_slice(),
+    ///         encoding,
+    ///         b"4:infod6:lengthi2e4:name2:\xe9t12:piece lengthi16e6:pieces0:ee",
+    ///     ]
+    ///     .concat();
+    ///     TorrentMetadata::from_bytes(&encoded).unwrap()
+    /// };
+    ///
+    /// assert_eq!(with_encoding(b"").text_encoding(), Some(TextEncoding::Utf8));
+    /// assert_eq!(with_encoding(b"8:encoding3:GBK").text_encoding(), None);
+    /// let latin1 = with_encoding(b"8:encoding10:ISO-8859-1");
+    /// let encoding = latin1.text_encoding().unwrap();
+    /// assert_eq!(encoding.decode(&latin1.info.name), "ét");
+    /// // the bytes are kept as they are, for the info hash
+    /// assert_eq!(latin1.info.name.as_slice(), b"\xe9t");
+    /// ```
+    pub fn text_encoding(&self) -> Option<TextEncoding> {
+        match &self.encoding {
+            Some(label) => TextEncoding::from_label(label),
+            None => Some(TextEncoding::Utf8),
+        }
+    }
+
+    /// Magnet link (BEP 9) with the info hash, name, length and every tracker, hybrid
+    /// torrents get both info hashes and v2 only torrents just the SHA-256 one
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TorrentMetadata;
+    ///
+    /// let encoded = std::fs::read("sample.torrent").unwrap();
+    /// let torrent = TorrentMetadata::from_bytes(&encoded).unwrap();
+    /// assert_eq!(
+    ///     torrent.magnet_link(),
+    ///     "magnet:?xt=urn:btih:d69f91e6b2ae4c542468d1073a71d4ea13879a7f&dn=sample.txt\
+    ///      &xl=92063&tr=http%3A%2F%2Fbittorrent-test-tracker.codecrafters.io%2Fannounce"
+    /// );
+    /// ```
+    pub fn magnet_link(&self) -> String {
+        let hex = |bytes: &[u8]| -> String { bytes.iter().map(|b| format!("{:02x}", b)).collect() };
+
+        let mut parameters = Vec::new();
+        if !self.info.is_v2_only() {
+            parameters.push(format!("xt=urn:btih:{}", hex(&self.info_hash())));
+        }
+        if self.info.is_v2() {
+            // a multihash, 0x12 is SHA-256 and 0x20 its length
+            parameters.push(format!("xt=urn:btmh:1220{}", hex(&self.info_hash_v2())));
+        }
+        // the name in UTF-8, magnet links have no "encoding"
+        let name = self
+            .text_encoding()
+            .unwrap_or_default()
+            .decode(&self.info.name);
+        parameters.push(format!("dn={}", percent_encode(name.as_bytes())));
+        parameters.push(format!("xl={}", self.info.total_length()));
+        for tracker in self.all_trackers() {
+            parameters.push(format!("tr={}", percent_encode(tracker.as_bytes())));
+        }
+
+        format!("magnet:?{}", parameters.join("&"))
+    }
+}
+
+/// Codecs names and paths can be in, old torrents name theirs in "encoding"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextEncoding {
+    /// invalid bytes are shown as U+FFFD
+    #[default]
+    Utf8,
+    /// also used for "ISO-8859-1" and "ASCII", like browsers do
+    Windows1252,
+}
+
+/// Characters of the Windows-1252 bytes 0x80 to 0x9f, the ones it leaves undefined are
+/// the C1 controls of ISO-8859-1
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+impl TextEncoding {
+    /// The codec of an "encoding" label, ignoring case, `None` for the ones there is no
+    /// table for
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TextEncoding;
+    ///
+    /// assert_eq!(TextEncoding::from_label(b"UTF-8"), Some(TextEncoding::Utf8));
+    /// assert_eq!(TextEncoding::from_label(b"cp1252"), Some(TextEncoding::Windows1252));
+    /// assert_eq!(TextEncoding::from_label(b"Shift_JIS"), None);
+    /// ```
+    pub fn from_label(label: &[u8]) -> Option<Self> {
+        let label = String::from_utf8_lossy(label).trim().to_ascii_lowercase();
+        match label.as_str() {
+            "utf-8" | "utf8" | "unicode-1-1-utf-8" => Some(Self::Utf8),
+            "windows-1252" | "cp1252" | "iso-8859-1" | "iso8859-1" | "latin1" | "l1" | "ascii"
+            | "us-ascii" => Some(Self::Windows1252),
+            _ => None,
+        }
+    }
+
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TextEncoding;
+    ///
+    /// assert_eq!(TextEncoding::Windows1252.decode(b"\x80 caf\xe9"), "€ café");
+    /// assert_eq!(TextEncoding::Utf8.decode(b"caf\xe9"), "caf\u{fffd}");
+    /// ```
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            Self::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Self::Windows1252 => bytes
+                .iter()
+                .map(|&byte| match byte {
+                    0x80..=0x9f => WINDOWS_1252_HIGH[usize::from(byte - 0x80)],
+                    _ => char::from(byte),
+                })
+                .collect(),
+        }
+    }
+
+    /// Path components decoded and joined with "/"
+    pub fn join(&self, components: &[ByteBuf]) -> String {
+        components
+            .iter()
+            .map(|component| self.decode(component))
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+/// A path component with the bytes of the torrent, which don't have to be UTF-8 on Unix
+#[cfg(unix)]
+fn path_component(component: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(component))
+}
+
+/// A path component, other systems need valid Unicode
+#[cfg(not(unix))]
+fn path_component(component: &[u8]) -> PathBuf {
+    PathBuf::from(TextEncoding::Utf8.decode(component))
+}
+
+/// The bytes with everything but the unreserved characters of RFC 3986 as "%XX"
+///
+/// ```
+/// use bittorrent_starter_rust::torrent::percent_encode;
+///
+/// assert_eq!(percent_encode(b"a b/c~"), "a%20b%2Fc~");
+/// ```
+pub fn percent_encode(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| match b {
+            b'0'..=b'9' | b'A'..=b'Z' | b'a'..=b'z' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
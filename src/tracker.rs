@@ -0,0 +1,594 @@
+//! HTTP tracker announces (BEP 3), the request url and the response with its compact
+//! (BEP 23) or dictionary peer list and its compact IPv6 peers (BEP 7), and scrapes
+//! (BEP 48)
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::sync::{Mutex, OnceLock};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::bencode::{de, Value};
+use crate::torrent::percent_encode;
+use crate::udp_tracker::{self, AnnounceRequest};
+
+/// Why an announce gave no peers, the tracker refused it or its answer isn't one
+#[derive(Error, Debug, PartialEq)]
+pub enum AnnounceError {
+    #[error("the tracker refused the request: {0}")]
+    Failure(String),
+    #[error("the tracker response is not valid: {0}")]
+    Invalid(String),
+    #[error("the tracker response has no {0:?}")]
+    Missing(&'static str),
+    #[error("compact peers length {0} is not a multiple of 6")]
+    CompactLength(usize),
+    #[error("compact peers6 length {0} is not a multiple of 18")]
+    Compact6Length(usize),
+    #[error("the answer is to transaction {found} instead of {expected}")]
+    WrongTransaction { found: u32, expected: u32 },
+    #[error("peer {index} of the list {problem}")]
+    InvalidPeer { index: usize, problem: &'static str },
+}
+
+/// Where a peer can be reached, only dictionary peer lists can name a host instead of
+/// an address. Shown as "ip:port", "[ipv6]:port" or "host:port"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeerAddress {
+    Ip(SocketAddr),
+    Host(String, u16),
+}
+
+/// How many peers a tracker knows for a torrent, the answer to a scrape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ScrapeStats {
+    pub seeders: u64,
+    /// peers that finished downloading it since the tracker knows the torrent
+    pub completed: u64,
+    pub leechers: u64,
+}
+
+/// IPv4 or IPv6, to connect to the peers of one of them first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl PeerAddress {
+    /// `None` for a host name, it can have addresses of both
+    pub fn family(&self) -> Option<AddressFamily> {
+        match self {
+            PeerAddress::Ip(SocketAddr::V4(_)) => Some(AddressFamily::Ipv4),
+            PeerAddress::Ip(SocketAddr::V6(_)) => Some(AddressFamily::Ipv6),
+            PeerAddress::Host(..) => None,
+        }
+    }
+}
+
+/// Moves the peers of `family` to the front, the others keep their order behind them
+///
+/// ```
+/// use bittorrent_starter_rust::tracker::{prefer_family, AddressFamily, PeerAddress};
+///
+/// let mut peers: Vec<PeerAddress> = ["10.0.0.1:1", "[2001:db8::1]:2", "10.0.0.2:3"]
+///     .iter()
+///     .map(|peer| PeerAddress::Ip(peer.parse().unwrap()))
+///     .collect();
+/// prefer_family(&mut peers, AddressFamily::Ipv6);
+/// let peers: Vec<String> = peers.iter().map(|peer| peer.to_string()).collect();
+/// assert_eq!(peers, ["[2001:db8::1]:2", "10.0.0.1:1", "10.0.0.2:3"]);
+/// ```
+pub fn prefer_family(peers: &mut [PeerAddress], family: AddressFamily) {
+    peers.sort_by_key(|peer| peer.family() != Some(family));
+}
+
+impl fmt::Display for PeerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PeerAddress::Ip(address) => write!(f, "{}", address),
+            PeerAddress::Host(host, port) => write!(f, "{}:{}", host, port),
+        }
+    }
+}
+
+/// What a tracker answers to an announce
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnounceResponse {
+    /// seconds to wait before the next announce
+    pub interval: u64,
+    /// "min interval", seconds to wait at least before announcing sooner than `interval`
+    pub min_interval: Option<u64>,
+    pub peers: Vec<PeerAddress>,
+    /// "warning message", the announce still worked
+    pub warning: Option<String>,
+}
+
+/// The keys of a response as they are encoded, checked once it has no failure reason
+#[derive(Deserialize)]
+struct EncodedResponse<'a> {
+    #[serde(rename = "warning message")]
+    warning_message: Option<&'a str>,
+    interval: Option<u64>,
+    #[serde(rename = "min interval")]
+    min_interval: Option<u64>,
+    /// a compact string, or a list of dictionaries from trackers that ignore "compact"
+    peers: Option<Value>,
+    #[serde(borrow)]
+    peers6: Option<&'a [u8]>,
+}
+
+/// Url of an announce to `tracker` with the fields of `request`, the same as those of
+/// a UDP announce. A tracker url with a query of its own (like a passkey) keeps it. The
+/// key is sent as 8 hex digits, which need no percent-encoding
+///
+/// ```
+/// use bittorrent_starter_rust::tracker::announce_url;
+/// use bittorrent_starter_rust::udp_tracker::{AnnounceRequest, Event};
+///
+/// let mut request = AnnounceRequest {
+///     info_hash: *b"\xd6\x9f\x91\xe6\xb2\xaeLT$h\xd1\x07:q\xd4\xea\x13\x87\x9a\x7f",
+///     peer_id: *b"00112233445566778899",
+///     downloaded: 0,
+///     left: 92063,
+///     uploaded: 0,
+///     event: Event::None,
+///     key: 0x0012_abcd,
+///     num_want: -1,
+///     port: 6881,
+/// };
+/// assert_eq!(
+///     announce_url("http://bittorrent-test-tracker.codecrafters.io/announce", &request),
+///     "http://bittorrent-test-tracker.codecrafters.io/announce\
+///      ?info_hash=%D6%9F%91%E6%B2%AELT%24h%D1%07%3Aq%D4%EA%13%87%9A%7F\
+///      &peer_id=00112233445566778899&port=6881&uploaded=0&downloaded=0&left=92063&compact=1\
+///      &key=0012ABCD"
+/// );
+///
+/// // a later announce reports the progress
+/// request.downloaded = 32768;
+/// request.left = 59295;
+/// let url = announce_url("http://t/announce?passkey=abc", &request);
+/// assert!(url.starts_with("http://t/announce?passkey=abc&info_hash=%D6"));
+/// assert!(url.contains("&uploaded=0&downloaded=32768&left=59295&"));
+///
+/// // only the first, the last and the one at the end of the download have an event
+/// request.event = Event::Completed;
+/// assert!(announce_url("http://t/announce", &request).ends_with("&key=0012ABCD&event=completed"));
+///
+/// // "numwant" is left out for the tracker's default number of peers
+/// assert!(!announce_url("http://t/announce", &request).contains("numwant"));
+/// request.num_want = 0;
+/// assert!(announce_url("http://t/announce", &request).ends_with("&event=completed&numwant=0"));
+/// ```
+pub fn announce_url(tracker: &str, request: &AnnounceRequest) -> String {
+    let separator = match tracker.contains('?') {
+        true => '&',
+        false => '?',
+    };
+    let mut url = format!(
+        "{}{}info_hash={}&peer_id={}&port={}&uploaded={}&downloaded={}&left={}&compact=1&key={:08X}",
+        tracker,
+        separator,
+        urlencode_bytes(&request.info_hash),
+        urlencode_bytes(&request.peer_id),
+        request.port,
+        request.uploaded,
+        request.downloaded,
+        request.left,
+        request.key
+    );
+    if let Some(event) = request.event.name() {
+        url = format!("{}&event={}", url, event);
+    }
+    if request.num_want >= 0 {
+        url = format!("{}&numwant={}", url, request.num_want);
+    }
+    url
+}
+
+static KEYS: OnceLock<Mutex<HashMap<[u8; 20], u32>>> = OnceLock::new();
+
+/// The "key" of the announces of `info_hash` in this process, random on first use and
+/// then the same for every announce of the torrent so its trackers still know this
+/// client when its address changes
+///
+/// ```
+/// use bittorrent_starter_rust::tracker::announce_key;
+///
+/// let key = announce_key(&[1; 20]).unwrap();
+/// assert_eq!(announce_key(&[1; 20]).unwrap(), key);
+/// ```
+pub fn announce_key(info_hash: &[u8; 20]) -> io::Result<u32> {
+    let mut keys = KEYS
+        .get_or_init(Default::default)
+        .lock()
+        .expect("the keys lock is not poisoned");
+    if let Some(key) = keys.get(info_hash) {
+        return Ok(*key);
+    }
+    let key = udp_tracker::random_id()?;
+    keys.insert(*info_hash, key);
+    Ok(key)
+}
+
+/// An info hash or peer id as a query value, each of the 20 raw bytes is percent-encoded
+/// unless it is an unreserved character. The url is built by hand so an http client
+/// doesn't encode it again (or the hex string or UTF-8 of the bytes is sent instead)
+///
+/// ```
+/// use bittorrent_starter_rust::tracker::urlencode_bytes;
+///
+/// let hash = *b"\x12\x34\x56\x78\x9a\xbc\xde\xf1\x23\x45\x67\x89\xab\xcd\xef\x12\x34\x56\x78\x9a";
+/// assert_eq!(
+///     urlencode_bytes(&hash),
+///     "%124Vx%9A%BC%DE%F1%23Eg%89%AB%CD%EF%124Vx%9A"
+/// );
+/// assert_eq!(urlencode_bytes(b"-TR2940-k8hj0wgej6ch"), "-TR2940-k8hj0wgej6ch");
+/// assert_eq!(urlencode_bytes(&[0; 20]), "%00".repeat(20));
+/// ```
+pub fn urlencode_bytes(bytes: &[u8; 20]) -> String {
+    percent_encode(bytes)
+}
+
+/// Parses the bencoded answer of a tracker, a "failure reason" is an error whatever else
+/// the response has. Both forms of peer list give the same peers, the "peers6" of a
+/// tracker with IPv6 peers come after them
+///
+/// ```
+/// use bittorrent_starter_rust::tracker::{parse_response, AnnounceError};
+///
+/// // as the codecrafters test tracker answers for sample.torrent
+/// let response = b"d8:completei3e10:incompletei1e8:intervali60e12:min intervali60e\
+///     5:peers18:\xa5\xe8\x29\x49\xc9\x64\xa5\xe8\x26\xa4\xc9\x2c\xa5\xe8\x23\x72\xc9\x1ce";
+/// let response = parse_response(response).unwrap();
+/// assert_eq!(response.interval, 60);
+/// assert_eq!(response.min_interval, Some(60));
+/// let peers: Vec<String> = response.peers.iter().map(|peer| peer.to_string()).collect();
+/// assert_eq!(
+///     peers,
+///     ["165.232.41.73:51556", "165.232.38.164:51500", "165.232.35.114:51484"]
+/// );
+///
+/// assert_eq!(
+///     parse_response(b"d14:failure reason25:info_hash not whitelistede"),
+///     Err(AnnounceError::Failure("info_hash not whitelisted".to_string()))
+/// );
+/// // the reason wins over whatever else a refused announce has, valid or not
+/// assert_eq!(
+///     parse_response(
+///         b"d14:failure reason40:torrent not registered with this tracker\
+///         8:interval3:now5:peers0:e"
+///     )
+///     .unwrap_err()
+///     .to_string(),
+///     "the tracker refused the request: torrent not registered with this tracker"
+/// );
+///
+/// // a warning comes with a working announce, its peers are still there
+/// let response = parse_response(
+///     b"d8:intervali1800e15:warning message22:your client is too old5:peers6:\x7f\x00\x00\x01\x1a\xe1e",
+/// )
+/// .unwrap();
+/// assert_eq!(response.warning.as_deref(), Some("your client is too old"));
+/// assert_eq!(response.peers[0].to_string(), "127.0.0.1:6881");
+///
+/// assert_eq!(
+///     parse_response(b"d8:intervali60ee"),
+///     Err(AnnounceError::Missing("peers"))
+/// );
+/// assert!(matches!(
+///     parse_response(b"<html>Not Found</html>"),
+///     Err(AnnounceError::Invalid(_))
+/// ));
+///
+/// // the same peers from a tracker that ignores "compact=1"
+/// let response = b"d8:intervali60e5:peersld2:ip13:165.232.41.737:peer id20:-qB4620-k8hj0wgej6ch\
+///     4:porti51556eed2:ip14:165.232.38.1644:porti51500eed2:ip14:165.232.35.1144:porti51484eeee";
+/// let listed: Vec<String> = parse_response(response)
+///     .unwrap()
+///     .peers
+///     .iter()
+///     .map(|peer| peer.to_string())
+///     .collect();
+/// assert_eq!(listed, peers);
+/// ```
+pub fn parse_response(encoded: &[u8]) -> Result<AnnounceResponse, AnnounceError> {
+    let response: Value =
+        de::from_bytes(encoded).map_err(|err| AnnounceError::Invalid(err.to_string()))?;
+    check_failure(&response)?;
+    let response: EncodedResponse =
+        de::from_bytes(encoded).map_err(|err| AnnounceError::Invalid(err.to_string()))?;
+
+    let mut peers = match response.peers {
+        Some(Value::Bytes(peers)) => parse_compact_peers(&peers)?
+            .into_iter()
+            .map(|peer| PeerAddress::Ip(SocketAddr::V4(peer)))
+            .collect(),
+        Some(Value::List(peers)) => parse_peer_list(&peers)?,
+        Some(_) => return Err(AnnounceError::Invalid("peers is not a list".to_string())),
+        // a tracker only has IPv6 peers for the torrent
+        None if response.peers6.is_some() => Vec::new(),
+        None => return Err(AnnounceError::Missing("peers")),
+    };
+    if let Some(peers6) = response.peers6 {
+        peers.extend(
+            parse_compact_peers6(peers6)?
+                .into_iter()
+                .map(PeerAddress::Ip),
+        );
+    }
+    Ok(AnnounceResponse {
+        interval: response
+            .interval
+            .ok_or(AnnounceError::Missing("interval"))?,
+        min_interval: response.min_interval,
+        peers,
+        warning: response.warning_message.map(str::to_string),
+    })
+}
+
+/// The "failure reason" of a response, checked before anything else as the rest of a
+/// refused request's answer (if any) can't be trusted
+fn check_failure(response: &Value) -> Result<(), AnnounceError> {
+    match response.get(b"failure reason") {
+        Some(reason) => {
+            let reason = String::from_utf8_lossy(reason.as_bytes().unwrap_or_default());
+            Err(AnnounceError::Failure(reason.into_owned()))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Peers of a dictionary peer list, each with an "ip" (an IPv4 or IPv6 address, or a
+/// host name) and a "port". Their "peer id" isn't needed to connect
+///
+/// ```
+/// use std::net::SocketAddr;
+///
+/// use bittorrent_starter_rust::bencode::{borrowed, DecodeOptions, Value};
+/// use bittorrent_starter_rust::tracker::{parse_peer_list, AnnounceError, PeerAddress};
+///
+/// let options = DecodeOptions::default();
+/// let list = |encoded: &[u8]| match Value::from(borrowed::parse(encoded, &options).unwrap().0) {
+///     Value::List(peers) => peers,
+///     _ => unreachable!(),
+/// };
+///
+/// let peers = parse_peer_list(&list(
+///     b"ld2:ip3:::14:porti6881eed2:ip16:peer.example.org4:porti80eee",
+/// ))
+/// .unwrap();
+/// assert_eq!(peers[0], PeerAddress::Ip("[::1]:6881".parse::<SocketAddr>().unwrap()));
+/// assert_eq!(peers[0].to_string(), "[::1]:6881");
+/// assert_eq!(peers[1], PeerAddress::Host("peer.example.org".to_string(), 80));
+///
+/// assert_eq!(
+///     parse_peer_list(&list(b"ld2:ip9:127.0.0.1ee")).unwrap_err().to_string(),
+///     "peer 0 of the list has no valid \"port\""
+/// );
+/// ```
+pub fn parse_peer_list(peers: &[Value]) -> Result<Vec<PeerAddress>, AnnounceError> {
+    peers
+        .iter()
+        .enumerate()
+        .map(|(index, peer)| {
+            let invalid = |problem| AnnounceError::InvalidPeer { index, problem };
+            let ip = peer
+                .get(b"ip")
+                .and_then(Value::as_str)
+                .filter(|ip| !ip.is_empty())
+                .ok_or(invalid("has no valid \"ip\""))?;
+            let port = peer
+                .get(b"port")
+                .and_then(Value::as_int)
+                .and_then(|port| port.as_u64())
+                .and_then(|port| u16::try_from(port).ok())
+                .ok_or(invalid("has no valid \"port\""))?;
+
+            // IPv6 addresses are sometimes in brackets, like in urls
+            let address = ip.trim_start_matches('[').trim_end_matches(']');
+            Ok(match address.parse::<IpAddr>() {
+                Ok(address) => PeerAddress::Ip(SocketAddr::new(address, port)),
+                Err(_) => PeerAddress::Host(ip.to_string(), port),
+            })
+        })
+        .collect()
+}
+
+/// Peers of a compact "peers" string, 4 bytes of IPv4 address and 2 of port each (both
+/// big-endian)
+///
+/// ```
+/// use std::net::{Ipv4Addr, SocketAddrV4};
+///
+/// use bittorrent_starter_rust::tracker::{parse_compact_peers, AnnounceError};
+///
+/// assert_eq!(
+///     parse_compact_peers(b"\x7f\x00\x00\x01\x1a\xe1\x0a\x00\x00\x02\x00\x50").unwrap(),
+///     [
+///         SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 6881),
+///         SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 80),
+///     ]
+/// );
+/// assert_eq!(parse_compact_peers(b""), Ok(vec![]));
+/// assert_eq!(
+///     parse_compact_peers(b"\x7f\x00\x00\x01\x1a"),
+///     Err(AnnounceError::CompactLength(5))
+/// );
+/// ```
+pub fn parse_compact_peers(peers: &[u8]) -> Result<Vec<SocketAddrV4>, AnnounceError> {
+    let chunks = peers.chunks_exact(6);
+    if !chunks.remainder().is_empty() {
+        return Err(AnnounceError::CompactLength(peers.len()));
+    }
+
+    Ok(chunks
+        .map(|peer| {
+            let ip = Ipv4Addr::new(peer[0], peer[1], peer[2], peer[3]);
+            SocketAddrV4::new(ip, u16::from_be_bytes([peer[4], peer[5]]))
+        })
+        .collect())
+}
+
+/// Peers of a compact "peers6" string, 16 bytes of IPv6 address and 2 of port each.
+/// IPv4-mapped addresses (`::ffff:a.b.c.d`) are peers with that IPv4 address
+///
+/// ```
+/// use std::net::SocketAddr;
+///
+/// use bittorrent_starter_rust::tracker::{parse_compact_peers6, AnnounceError};
+///
+/// let peers6 = [
+///     b"\x20\x01\x0d\xb8\0\0\0\0\0\0\0\0\0\0\0\x01\x1a\xe1".as_slice(),
+///     b"\xfe\x80\0\0\0\0\0\0\x02\x11\x22\xff\xfe\x33\x44\x55\xc8\xd5",
+///     b"\0\0\0\0\0\0\0\0\0\0\xff\xff\xc0\xa8\x01\x02\x00\x50",
+/// ]
+/// .concat();
+/// let peers: Vec<String> = parse_compact_peers6(&peers6)
+///     .unwrap()
+///     .iter()
+///     .map(SocketAddr::to_string)
+///     .collect();
+/// assert_eq!(
+///     peers,
+///     ["[2001:db8::1]:6881", "[fe80::211:22ff:fe33:4455]:51413", "192.168.1.2:80"]
+/// );
+///
+/// assert_eq!(
+///     parse_compact_peers6(&peers6[..20]),
+///     Err(AnnounceError::Compact6Length(20))
+/// );
+/// ```
+pub fn parse_compact_peers6(peers: &[u8]) -> Result<Vec<SocketAddr>, AnnounceError> {
+    let chunks = peers.chunks_exact(18);
+    if !chunks.remainder().is_empty() {
+        return Err(AnnounceError::Compact6Length(peers.len()));
+    }
+
+    Ok(chunks
+        .map(|peer| {
+            let mut ip = [0; 16];
+            ip.copy_from_slice(&peer[..16]);
+            let ip = Ipv6Addr::from(ip);
+            let port = u16::from_be_bytes([peer[16], peer[17]]);
+            match ip.to_ipv4_mapped() {
+                Some(ip) => SocketAddr::V4(SocketAddrV4::new(ip, port)),
+                None => SocketAddr::V6(SocketAddrV6::new(ip, port, 0, 0)),
+            }
+        })
+        .collect())
+}
+
+/// Url to scrape the tracker of `announce` at, by the convention that its last path
+/// component "announce" becomes "scrape" (like "announce.php" becomes "scrape.php").
+/// `None` for trackers whose url doesn't follow it, they can't be scraped
+///
+/// ```
+/// use bittorrent_starter_rust::tracker::scrape_url;
+///
+/// assert_eq!(
+///     scrape_url("http://t.example.org/announce").as_deref(),
+///     Some("http://t.example.org/scrape")
+/// );
+/// assert_eq!(
+///     scrape_url("https://t/x/announce.php?passkey=abc").as_deref(),
+///     Some("https://t/x/scrape.php?passkey=abc")
+/// );
+/// assert_eq!(scrape_url("http://t/a"), None);
+/// assert_eq!(scrape_url("http://t/announce/x"), None);
+/// ```
+pub fn scrape_url(announce: &str) -> Option<String> {
+    let (path, query) = match announce.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (announce, None),
+    };
+    let (directory, last) = path.rsplit_once('/')?;
+    let rest = last.strip_prefix("announce")?;
+
+    let mut url = format!("{}/scrape{}", directory, rest);
+    if let Some(query) = query {
+        url = format!("{}?{}", url, query);
+    }
+    Some(url)
+}
+
+/// The scrape url with an "info_hash" for each of `info_hashes`, the tracker answers
+/// for all of them at once
+///
+/// ```
+/// use bittorrent_starter_rust::tracker::scrape_request_url;
+///
+/// assert_eq!(
+///     scrape_request_url("http://t/scrape", &[[0xaa; 20], [b'a'; 20]]),
+///     format!("http://t/scrape?info_hash={}&info_hash={}", "%AA".repeat(20), "a".repeat(20))
+/// );
+/// ```
+pub fn scrape_request_url(scrape_url: &str, info_hashes: &[[u8; 20]]) -> String {
+    let mut url = scrape_url.to_string();
+    for (index, info_hash) in info_hashes.iter().enumerate() {
+        let separator = match index == 0 && !scrape_url.contains('?') {
+            true => '?',
+            false => '&',
+        };
+        url = format!(
+            "{}{}info_hash={}",
+            url,
+            separator,
+            urlencode_bytes(info_hash)
+        );
+    }
+    url
+}
+
+/// The stats of each of `info_hashes` in the "files" of a scrape answer ("complete",
+/// "downloaded" and "incomplete"), `None` for the torrents the tracker doesn't know
+///
+/// ```
+/// use bittorrent_starter_rust::tracker::{parse_scrape_response, AnnounceError, ScrapeStats};
+///
+/// let response = format!(
+///     "d5:filesd20:{}d8:completei5e10:downloadedi100e10:incompletei1eeee",
+///     "a".repeat(20)
+/// );
+/// assert_eq!(
+///     parse_scrape_response(response.as_bytes(), &[[b'a'; 20], [b'b'; 20]]).unwrap(),
+///     [Some(ScrapeStats { seeders: 5, completed: 100, leechers: 1 }), None]
+/// );
+/// assert_eq!(
+///     parse_scrape_response(b"d14:failure reason7:privatee", &[[b'a'; 20]]),
+///     Err(AnnounceError::Failure("private".to_string()))
+/// );
+/// ```
+pub fn parse_scrape_response(
+    encoded: &[u8],
+    info_hashes: &[[u8; 20]],
+) -> Result<Vec<Option<ScrapeStats>>, AnnounceError> {
+    let response: Value =
+        de::from_bytes(encoded).map_err(|err| AnnounceError::Invalid(err.to_string()))?;
+    check_failure(&response)?;
+    let files = response
+        .get(b"files")
+        .ok_or(AnnounceError::Missing("files"))?;
+
+    let count = |file: &Value, key: &[u8]| {
+        file.get(key)
+            .and_then(Value::as_int)
+            .and_then(|count| count.as_u64())
+            .unwrap_or(0)
+    };
+    Ok(info_hashes
+        .iter()
+        .map(|info_hash| {
+            let file = files.get(info_hash)?;
+            Some(ScrapeStats {
+                seeders: count(file, b"complete"),
+                completed: count(file, b"downloaded"),
+                leechers: count(file, b"incomplete"),
+            })
+        })
+        .collect())
+}
@@ -0,0 +1,187 @@
+//! Tracker tiers of a torrent (BEP 12): trackers are shuffled within their tier once,
+//! tiers are tried in order and the tracker that answers moves to the front of its tier
+
+use std::future::Future;
+use std::io;
+
+use crate::random;
+use crate::torrent::TorrentMetadata;
+
+/// The trackers of a torrent in the order they are tried, kept for every announce of
+/// the torrent so the one that answered last is asked first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackerList {
+    tiers: Vec<Vec<String>>,
+}
+
+impl TrackerList {
+    /// The tiers as they are, without empty tiers and without the trackers already in
+    /// an earlier tier
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::tracker_list::TrackerList;
+    ///
+    /// let tiers = vec![
+    ///     vec!["http://a".to_string(), "http://b".to_string()],
+    ///     vec![],
+    ///     vec!["http://a".to_string(), "http://c".to_string()],
+    /// ];
+    /// assert_eq!(
+    ///     TrackerList::new(tiers).tiers(),
+    ///     [vec!["http://a", "http://b"], vec!["http://c"]]
+    /// );
+    /// ```
+    pub fn new(tiers: Vec<Vec<String>>) -> Self {
+        let mut known: Vec<String> = Vec::new();
+        let mut list = Vec::new();
+        for tier in tiers {
+            let mut kept = Vec::new();
+            for tracker in tier {
+                if !known.contains(&tracker) {
+                    known.push(tracker.clone());
+                    kept.push(tracker);
+                }
+            }
+            if !kept.is_empty() {
+                list.push(kept);
+            }
+        }
+        Self { tiers: list }
+    }
+
+    /// The "announce-list" tiers of the torrent, or its "announce" alone when it has
+    /// none (clients that know the list ignore "announce")
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::torrent::TorrentMetadata;
+    /// use bittorrent_starter_rust::tracker_list::TrackerList;
+    ///
+    /// let encoded = b"d8:announce9:http://t/13:announce-listll8:http://a8:http://be\
+    ///     l8:http://cee4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16e6:pieces0:ee";
+    /// let torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// assert_eq!(
+    ///     TrackerList::from_torrent(&torrent).tiers(),
+    ///     [vec!["http://a", "http://b"], vec!["http://c"]]
+    /// );
+    ///
+    /// let encoded = b"d8:announce9:http://t/4:infod6:lengthi5e4:name5:a.txt\
+    ///     12:piece lengthi16e6:pieces0:ee";
+    /// let torrent = TorrentMetadata::from_bytes(encoded).unwrap();
+    /// assert_eq!(TrackerList::from_torrent(&torrent).tiers(), [vec!["http://t/"]]);
+    /// ```
+    pub fn from_torrent(torrent: &TorrentMetadata) -> Self {
+        let list = Self::new(torrent.announce_list.clone().unwrap_or_default());
+        match (list.is_empty(), &torrent.announce) {
+            (true, Some(announce)) => Self::new(vec![vec![announce.clone()]]),
+            _ => list,
+        }
+    }
+
+    pub fn tiers(&self) -> &[Vec<String>] {
+        &self.tiers
+    }
+
+    /// True for trackerless torrents
+    pub fn is_empty(&self) -> bool {
+        self.tiers.is_empty()
+    }
+
+    /// Shuffles the trackers of each tier with the system's random source, done once
+    /// when the list is made so the load spreads over the trackers of a tier
+    pub fn shuffle(&mut self) -> io::Result<()> {
+        for tier in &mut self.tiers {
+            // Fisher-Yates, the bias of the modulo is negligible for tiers this short
+            for last in (1..tier.len()).rev() {
+                let other = (random::random_u64()? % (last as u64 + 1)) as usize;
+                tier.swap(last, other);
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves `tracker` to the front of its tier, the others keep their order
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::tracker_list::TrackerList;
+    ///
+    /// let tiers = vec![["a", "b", "c"].map(String::from).to_vec()];
+    /// let mut trackers = TrackerList::new(tiers);
+    /// trackers.promote("c");
+    /// assert_eq!(trackers.tiers(), [vec!["c", "a", "b"]]);
+    /// ```
+    pub fn promote(&mut self, tracker: &str) {
+        for tier in &mut self.tiers {
+            if let Some(index) = tier.iter().position(|known| known == tracker) {
+                let promoted = tier.remove(index);
+                tier.insert(0, promoted);
+                return;
+            }
+        }
+    }
+
+    /// Asks the trackers in order until one answers, which then moves to the front of
+    /// its tier. Every tracker that failed and why when none did
+    ///
+    /// ```
+    /// use std::future::ready;
+    ///
+    /// use bittorrent_starter_rust::tracker_list::TrackerList;
+    ///
+    /// let tiers = vec![vec!["http://a".to_string(), "http://b".to_string()], vec!["http://c".to_string()]];
+    /// let mut trackers = TrackerList::new(tiers);
+    /// let runtime = tokio::runtime::Runtime::new().unwrap();
+    ///
+    /// // the first tier fails, the second one is tried
+    /// let answer = runtime.block_on(trackers.announce(|tracker| {
+    ///     ready(match tracker.as_str() {
+    ///         "http://c" => Ok(vec!["127.0.0.1:6881"]),
+    ///         _ => Err(format!("{} is down", tracker)),
+    ///     })
+    /// }));
+    /// assert_eq!(answer, Ok(("http://c".to_string(), vec!["127.0.0.1:6881"])));
+    ///
+    /// // the tracker that answered within a tier is asked first next time
+    /// let mut asked = Vec::new();
+    /// let answer = runtime.block_on(trackers.announce(|tracker| {
+    ///     asked.push(tracker.clone());
+    ///     ready(match tracker.as_str() {
+    ///         "http://b" => Ok(()),
+    ///         _ => Err("down"),
+    ///     })
+    /// }));
+    /// assert_eq!(answer, Ok(("http://b".to_string(), ())));
+    /// assert_eq!(asked, ["http://a", "http://b"]);
+    /// assert_eq!(trackers.tiers(), [vec!["http://b", "http://a"], vec!["http://c"]]);
+    ///
+    /// let answer = runtime.block_on(trackers.announce(|_| ready(Err::<(), _>("down"))));
+    /// assert_eq!(
+    ///     answer,
+    ///     Err(vec![
+    ///         ("http://b".to_string(), "down"),
+    ///         ("http://a".to_string(), "down"),
+    ///         ("http://c".to_string(), "down"),
+    ///     ])
+    /// );
+    /// ```
+    pub async fn announce<T, E, F, Fut>(
+        &mut self,
+        mut announce: F,
+    ) -> Result<(String, T), Vec<(String, E)>>
+    where
+        F: FnMut(String) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut failures = Vec::new();
+        let trackers: Vec<String> = self.tiers.iter().flatten().cloned().collect();
+        for tracker in trackers {
+            match announce(tracker.clone()).await {
+                Ok(answer) => {
+                    self.promote(&tracker);
+                    return Ok((tracker, answer));
+                }
+                Err(err) => failures.push((tracker, err)),
+            }
+        }
+        Err(failures)
+    }
+}
@@ -0,0 +1,593 @@
+//! UDP tracker announces and scrapes (BEP 15): the packets, and a client that resends
+//! them on the 15·2^n second schedule and reuses its connection id while it is valid
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::random;
+use crate::tracker::{
+    parse_compact_peers, parse_compact_peers6, AddressFamily, AnnounceError, ScrapeStats,
+};
+
+/// Magic number a connect request starts with
+pub const PROTOCOL_ID: u64 = 0x417_2710_1980;
+
+/// A connection id can be used this long after the tracker sent it
+pub const CONNECTION_ID_LIFETIME: Duration = Duration::from_secs(60);
+
+/// Largest `n` of the 15·2^n second wait, the last wait is an hour
+pub const MAX_RETRANSMISSIONS: u32 = 8;
+
+const ACTION_CONNECT: u32 = 0;
+const ACTION_ANNOUNCE: u32 = 1;
+const ACTION_SCRAPE: u32 = 2;
+const ACTION_ERROR: u32 = 3;
+
+/// Info hashes a scrape packet can have, more are scraped in several packets
+pub const MAX_SCRAPE_HASHES: usize = 74;
+
+/// What the announce tells the tracker, like the "event" of an HTTP announce
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Event {
+    #[default]
+    None,
+    Completed,
+    Started,
+    Stopped,
+}
+
+impl Event {
+    /// The "event" of an HTTP announce, `None` for the regular announces that leave it
+    /// out
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::udp_tracker::Event;
+    ///
+    /// assert_eq!(Event::Started.name(), Some("started"));
+    /// assert_eq!(Event::None.name(), None);
+    /// ```
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            Event::None => None,
+            Event::Completed => Some("completed"),
+            Event::Started => Some("started"),
+            Event::Stopped => Some("stopped"),
+        }
+    }
+}
+
+/// The fields of an announce, the same as those of an HTTP announce. The IP address
+/// field is always 0, the tracker uses the one the packet comes from
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnounceRequest {
+    pub info_hash: [u8; 20],
+    pub peer_id: [u8; 20],
+    pub downloaded: u64,
+    pub left: u64,
+    pub uploaded: u64,
+    pub event: Event,
+    /// random, lets the tracker tell this client apart when its address changes
+    pub key: u32,
+    /// -1 for the tracker's default number of peers
+    pub num_want: i32,
+    pub port: u16,
+}
+
+/// What a tracker answers to a UDP announce
+#[derive(Debug, Clone, PartialEq)]
+pub struct UdpAnnounceResponse {
+    /// seconds to wait before the next announce
+    pub interval: u32,
+    pub leechers: u32,
+    pub seeders: u32,
+    pub peers: Vec<SocketAddr>,
+}
+
+/// Why a UDP announce gave no peers
+#[derive(Error, Debug)]
+pub enum UdpError {
+    #[error("network error: {0}")]
+    Io(#[from] io::Error),
+    #[error("{0} has no address")]
+    NoAddress(String),
+    #[error("the tracker did not answer, {0} packets were sent")]
+    Timeout(u32),
+    #[error(transparent)]
+    Announce(#[from] AnnounceError),
+}
+
+/// The "host:port" of a "udp://" tracker url, `None` for other urls
+///
+/// ```
+/// use bittorrent_starter_rust::udp_tracker::udp_address;
+///
+/// assert_eq!(
+///     udp_address("udp://tracker.opentrackr.org:1337/announce"),
+///     Some("tracker.opentrackr.org:1337")
+/// );
+/// assert_eq!(udp_address("udp://[2001:db8::1]:6969"), Some("[2001:db8::1]:6969"));
+/// assert_eq!(udp_address("http://t/announce"), None);
+/// ```
+pub fn udp_address(url: &str) -> Option<&str> {
+    let rest = url.strip_prefix("udp://")?;
+    Some(rest.split('/').next().unwrap_or(rest))
+}
+
+/// Seconds to wait for an answer to the `n`th retransmission of a packet, 15·2^n
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use bittorrent_starter_rust::udp_tracker::retransmit_timeout;
+///
+/// assert_eq!(retransmit_timeout(0), Duration::from_secs(15));
+/// assert_eq!(retransmit_timeout(3), Duration::from_secs(120));
+/// assert_eq!(retransmit_timeout(8), Duration::from_secs(3840));
+/// ```
+pub fn retransmit_timeout(n: u32) -> Duration {
+    Duration::from_secs(15 << n)
+}
+
+/// A connect request, the protocol id, the connect action and the transaction id
+///
+/// ```
+/// use bittorrent_starter_rust::udp_tracker::connect_request;
+///
+/// assert_eq!(
+///     connect_request(0x1234_5678),
+///     *b"\x00\x00\x04\x17\x27\x10\x19\x80\x00\x00\x00\x00\x12\x34\x56\x78"
+/// );
+/// ```
+pub fn connect_request(transaction_id: u32) -> [u8; 16] {
+    let mut packet = [0; 16];
+    packet[..8].copy_from_slice(&PROTOCOL_ID.to_be_bytes());
+    packet[8..12].copy_from_slice(&ACTION_CONNECT.to_be_bytes());
+    packet[12..].copy_from_slice(&transaction_id.to_be_bytes());
+    packet
+}
+
+/// The connection id of the answer to the connect request with `transaction_id`
+///
+/// ```
+/// use bittorrent_starter_rust::udp_tracker::parse_connect_response;
+///
+/// let response = b"\x00\x00\x00\x00\x12\x34\x56\x78\x01\x02\x03\x04\x05\x06\x07\x08";
+/// assert_eq!(parse_connect_response(response, 0x1234_5678).unwrap(), 0x0102_0304_0506_0708);
+///
+/// // an answer to another request
+/// assert_eq!(
+///     parse_connect_response(response, 1).unwrap_err().to_string(),
+///     "the answer is to transaction 305419896 instead of 1"
+/// );
+/// ```
+pub fn parse_connect_response(packet: &[u8], transaction_id: u32) -> Result<u64, AnnounceError> {
+    let body = check_header(packet, ACTION_CONNECT, transaction_id)?;
+    let connection_id = body
+        .get(..8)
+        .ok_or_else(|| too_short(packet))?
+        .try_into()
+        .expect("8 bytes");
+    Ok(u64::from_be_bytes(connection_id))
+}
+
+impl AnnounceRequest {
+    /// The 98 bytes of the announce
+    ///
+    /// ```
+    /// use bittorrent_starter_rust::udp_tracker::{AnnounceRequest, Event};
+    ///
+    /// let request = AnnounceRequest {
+    ///     info_hash: [0xaa; 20],
+    ///     peer_id: *b"-XX0001-abcdefghijkl",
+    ///     downloaded: 1,
+    ///     left: 92063,
+    ///     uploaded: 2,
+    ///     event: Event::Started,
+    ///     key: 0xdead_beef,
+    ///     num_want: -1,
+    ///     port: 6881,
+    /// };
+    /// let expected = [
+    ///     b"\x01\x02\x03\x04\x05\x06\x07\x08\x00\x00\x00\x01\x12\x34\x56\x78".as_slice(),
+    ///     &[0xaa; 20],
+    ///     b"-XX0001-abcdefghijkl",
+    ///     b"\x00\x00\x00\x00\x00\x00\x00\x01",
+    ///     b"\x00\x00\x00\x00\x00\x01\x67\x9f",
+    ///     b"\x00\x00\x00\x00\x00\x00\x00\x02",
+    ///     b"\x00\x00\x00\x02\x00\x00\x00\x00\xde\xad\xbe\xef\xff\xff\xff\xff\x1a\xe1",
+    /// ]
+    /// .concat();
+    /// assert_eq!(request.encode(0x0102_0304_0506_0708, 0x1234_5678), *expected);
+    /// ```
+    pub fn encode(&self, connection_id: u64, transaction_id: u32) -> [u8; 98] {
+        let event: u32 = match self.event {
+            Event::None => 0,
+            Event::Completed => 1,
+            Event::Started => 2,
+            Event::Stopped => 3,
+        };
+
+        let mut packet = Vec::with_capacity(98);
+        packet.extend(connection_id.to_be_bytes());
+        packet.extend(ACTION_ANNOUNCE.to_be_bytes());
+        packet.extend(transaction_id.to_be_bytes());
+        packet.extend(self.info_hash);
+        packet.extend(self.peer_id);
+        packet.extend(self.downloaded.to_be_bytes());
+        packet.extend(self.left.to_be_bytes());
+        packet.extend(self.uploaded.to_be_bytes());
+        packet.extend(event.to_be_bytes());
+        packet.extend(0u32.to_be_bytes());
+        packet.extend(self.key.to_be_bytes());
+        packet.extend(self.num_want.to_be_bytes());
+        packet.extend(self.port.to_be_bytes());
+        packet.try_into().expect("98 bytes")
+    }
+}
+
+/// The answer to the announce with `transaction_id`, its peers are IPv6 ones (18 bytes
+/// each) when the tracker was reached over IPv6
+///
+/// ```
+/// use bittorrent_starter_rust::tracker::AddressFamily;
+/// use bittorrent_starter_rust::udp_tracker::parse_announce_response;
+///
+/// let packet = [
+///     b"\x00\x00\x00\x01\x12\x34\x56\x78\x00\x00\x07\x08\x00\x00\x00\x02\x00\x00\x00\x05".as_slice(),
+///     b"\xa5\xe8\x29\x49\xc9\x64\x7f\x00\x00\x01\x1a\xe1",
+/// ]
+/// .concat();
+/// let response = parse_announce_response(&packet, 0x1234_5678, AddressFamily::Ipv4).unwrap();
+/// assert_eq!((response.interval, response.leechers, response.seeders), (1800, 2, 5));
+/// let peers: Vec<String> = response.peers.iter().map(|peer| peer.to_string()).collect();
+/// assert_eq!(peers, ["165.232.41.73:51556", "127.0.0.1:6881"]);
+///
+/// // an error packet is the tracker's message
+/// let error = b"\x00\x00\x00\x03\x12\x34\x56\x78unknown torrent";
+/// assert_eq!(
+///     parse_announce_response(error, 0x1234_5678, AddressFamily::Ipv4).unwrap_err().to_string(),
+///     "the tracker refused the request: unknown torrent"
+/// );
+/// assert_eq!(
+///     parse_announce_response(&packet[..10], 0x1234_5678, AddressFamily::Ipv4)
+///         .unwrap_err()
+///         .to_string(),
+///     "the tracker response is not valid: packet of 10 bytes is too short"
+/// );
+/// ```
+pub fn parse_announce_response(
+    packet: &[u8],
+    transaction_id: u32,
+    family: AddressFamily,
+) -> Result<UdpAnnounceResponse, AnnounceError> {
+    let body = check_header(packet, ACTION_ANNOUNCE, transaction_id)?;
+    if body.len() < 12 {
+        return Err(too_short(packet));
+    }
+
+    let field = |index: usize| {
+        let bytes = body[index * 4..index * 4 + 4].try_into().expect("4 bytes");
+        u32::from_be_bytes(bytes)
+    };
+    let peers = match family {
+        AddressFamily::Ipv4 => parse_compact_peers(&body[12..])?
+            .into_iter()
+            .map(SocketAddr::V4)
+            .collect(),
+        AddressFamily::Ipv6 => parse_compact_peers6(&body[12..])?,
+    };
+    Ok(UdpAnnounceResponse {
+        interval: field(0),
+        leechers: field(1),
+        seeders: field(2),
+        peers,
+    })
+}
+
+/// A scrape request for up to [`MAX_SCRAPE_HASHES`] info hashes
+///
+/// ```
+/// use bittorrent_starter_rust::udp_tracker::scrape_request;
+///
+/// let expected = [
+///     b"\x01\x02\x03\x04\x05\x06\x07\x08\x00\x00\x00\x02\x12\x34\x56\x78".as_slice(),
+///     &[0xaa; 20],
+///     &[0xbb; 20],
+/// ]
+/// .concat();
+/// assert_eq!(
+///     scrape_request(0x0102_0304_0506_0708, 0x1234_5678, &[[0xaa; 20], [0xbb; 20]]),
+///     expected
+/// );
+/// ```
+pub fn scrape_request(
+    connection_id: u64,
+    transaction_id: u32,
+    info_hashes: &[[u8; 20]],
+) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(16 + 20 * info_hashes.len());
+    packet.extend(connection_id.to_be_bytes());
+    packet.extend(ACTION_SCRAPE.to_be_bytes());
+    packet.extend(transaction_id.to_be_bytes());
+    for info_hash in info_hashes {
+        packet.extend(info_hash);
+    }
+    packet
+}
+
+/// The seeders, completed and leechers of each of the `count` info hashes of the scrape
+/// with `transaction_id`, in the order of the request
+///
+/// ```
+/// use bittorrent_starter_rust::tracker::ScrapeStats;
+/// use bittorrent_starter_rust::udp_tracker::parse_scrape_response;
+///
+/// let packet = [
+///     b"\x00\x00\x00\x02\x12\x34\x56\x78".as_slice(),
+///     b"\x00\x00\x00\x05\x00\x00\x00\x64\x00\x00\x00\x01",
+///     b"\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00",
+/// ]
+/// .concat();
+/// assert_eq!(
+///     parse_scrape_response(&packet, 0x1234_5678, 2).unwrap(),
+///     [
+///         ScrapeStats { seeders: 5, completed: 100, leechers: 1 },
+///         ScrapeStats::default(),
+///     ]
+/// );
+/// assert_eq!(
+///     parse_scrape_response(&packet, 0x1234_5678, 3).unwrap_err().to_string(),
+///     "the tracker response is not valid: 2 scrape results for 3 info hashes"
+/// );
+/// ```
+pub fn parse_scrape_response(
+    packet: &[u8],
+    transaction_id: u32,
+    count: usize,
+) -> Result<Vec<ScrapeStats>, AnnounceError> {
+    let body = check_header(packet, ACTION_SCRAPE, transaction_id)?;
+    let results = body.chunks_exact(12);
+    if results.len() != count {
+        return Err(AnnounceError::Invalid(format!(
+            "{} scrape results for {} info hashes",
+            results.len(),
+            count
+        )));
+    }
+
+    let field = |result: &[u8], index: usize| {
+        let bytes = result[index * 4..index * 4 + 4]
+            .try_into()
+            .expect("4 bytes");
+        u64::from(u32::from_be_bytes(bytes))
+    };
+    Ok(results
+        .map(|result| ScrapeStats {
+            seeders: field(result, 0),
+            completed: field(result, 1),
+            leechers: field(result, 2),
+        })
+        .collect())
+}
+
+fn too_short(packet: &[u8]) -> AnnounceError {
+    AnnounceError::Invalid(format!("packet of {} bytes is too short", packet.len()))
+}
+
+/// What comes after the action and transaction id of an answer, an error packet of the
+/// same transaction is the tracker refusing the request
+fn check_header(packet: &[u8], action: u32, transaction_id: u32) -> Result<&[u8], AnnounceError> {
+    if packet.len() < 8 {
+        return Err(too_short(packet));
+    }
+    let found_action = u32::from_be_bytes(packet[..4].try_into().expect("4 bytes"));
+    let found_transaction = u32::from_be_bytes(packet[4..8].try_into().expect("4 bytes"));
+
+    if found_transaction != transaction_id {
+        return Err(AnnounceError::WrongTransaction {
+            found: found_transaction,
+            expected: transaction_id,
+        });
+    }
+    if found_action == ACTION_ERROR {
+        let message = String::from_utf8_lossy(&packet[8..]);
+        return Err(AnnounceError::Failure(
+            message.trim_end_matches('\0').to_string(),
+        ));
+    }
+    if found_action != action {
+        return Err(AnnounceError::Invalid(format!(
+            "action {} instead of {}",
+            found_action, action
+        )));
+    }
+    Ok(&packet[8..])
+}
+
+/// A random transaction id (or announce key) from the system's random source
+pub fn random_id() -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    random::fill_bytes(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+/// A tracker reached over UDP, its connection id is kept for the next announces and
+/// scrapes
+///
+/// ```
+/// use std::net::UdpSocket;
+///
+/// use bittorrent_starter_rust::tracker::ScrapeStats;
+/// use bittorrent_starter_rust::udp_tracker::UdpTracker;
+///
+/// // a tracker that answers one connect and then every scrape with "2 seeders" a hash
+/// let mock = UdpSocket::bind("127.0.0.1:0").unwrap();
+/// let address = mock.local_addr().unwrap().to_string();
+/// let tracker = std::thread::spawn(move || {
+///     let mut connects = 0;
+///     for _ in 0..4 {
+///         let mut packet = [0; 2048];
+///         let (length, client) = mock.recv_from(&mut packet).unwrap();
+///         let (action, transaction) = (&packet[8..12], &packet[12..16]);
+///         let answer = match action {
+///             [0, 0, 0, 0] => {
+///                 connects += 1;
+///                 [b"\0\0\0\0", transaction, &42u64.to_be_bytes()].concat()
+///             }
+///             _ => {
+///                 let results = (length - 16) / 20;
+///                 let result = b"\0\0\0\x02\0\0\0\0\0\0\0\0".repeat(results);
+///                 [b"\0\0\0\x02", transaction, &result].concat()
+///             }
+///         };
+///         mock.send_to(&answer, client).unwrap();
+///     }
+///     connects
+/// });
+///
+/// let mut client = UdpTracker::connect(&address).unwrap();
+/// let stats = client.scrape(&[[1; 20], [2; 20]]).unwrap();
+/// assert_eq!(stats, [ScrapeStats { seeders: 2, ..Default::default() }; 2]);
+/// // 80 hashes take two packets on the same connection id
+/// assert_eq!(client.scrape(&[[3; 20]; 80]).unwrap().len(), 80);
+/// assert_eq!(tracker.join().unwrap(), 1);
+/// ```
+pub struct UdpTracker {
+    socket: UdpSocket,
+    family: AddressFamily,
+    connection: Option<(u64, Instant)>,
+}
+
+impl UdpTracker {
+    /// `address` is the "host:port" of the tracker (see [`udp_address`]), its first
+    /// address is used
+    pub fn connect(address: &str) -> Result<Self, UdpError> {
+        let tracker = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| UdpError::NoAddress(address.to_string()))?;
+        let (local, family) = match tracker {
+            SocketAddr::V4(_) => ("0.0.0.0:0", AddressFamily::Ipv4),
+            SocketAddr::V6(_) => ("[::]:0", AddressFamily::Ipv6),
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(tracker)?;
+
+        Ok(Self {
+            socket,
+            family,
+            connection: None,
+        })
+    }
+
+    /// Announces `request`, connecting first unless the connection id is less than a
+    /// minute old
+    pub fn announce(&mut self, request: &AnnounceRequest) -> Result<UdpAnnounceResponse, UdpError> {
+        let family = self.family;
+        self.request(
+            |connection_id, transaction_id| request.encode(connection_id, transaction_id).to_vec(),
+            |answer, transaction_id| parse_announce_response(answer, transaction_id, family),
+        )
+    }
+
+    /// Scrapes `info_hashes`, [`MAX_SCRAPE_HASHES`] at a time, the results are in the
+    /// same order
+    pub fn scrape(&mut self, info_hashes: &[[u8; 20]]) -> Result<Vec<ScrapeStats>, UdpError> {
+        let mut stats = Vec::with_capacity(info_hashes.len());
+        for info_hashes in info_hashes.chunks(MAX_SCRAPE_HASHES) {
+            stats.extend(self.request(
+                |connection_id, transaction_id| {
+                    scrape_request(connection_id, transaction_id, info_hashes)
+                },
+                |answer, transaction_id| {
+                    parse_scrape_response(answer, transaction_id, info_hashes.len())
+                },
+            )?);
+        }
+        Ok(stats)
+    }
+
+    /// Sends the packet of a new transaction until it is answered, on the 15·2^n second
+    /// schedule
+    fn request<T>(
+        &mut self,
+        packet: impl Fn(u64, u32) -> Vec<u8>,
+        parse: impl Fn(&[u8], u32) -> Result<T, AnnounceError>,
+    ) -> Result<T, UdpError> {
+        for n in 0..=MAX_RETRANSMISSIONS {
+            // the connection id can run out while the tracker doesn't answer
+            let connection_id = self.connection_id()?;
+            let transaction_id = random_id()?;
+            let packet = packet(connection_id, transaction_id);
+            let answer = self.exchange(&packet, retransmit_timeout(n), |answer| {
+                parse(answer, transaction_id)
+            })?;
+            if let Some(answer) = answer {
+                return Ok(answer);
+            }
+        }
+        Err(UdpError::Timeout(MAX_RETRANSMISSIONS + 1))
+    }
+
+    fn connection_id(&mut self) -> Result<u64, UdpError> {
+        if let Some((connection_id, received)) = self.connection {
+            if received.elapsed() < CONNECTION_ID_LIFETIME {
+                return Ok(connection_id);
+            }
+        }
+
+        for n in 0..=MAX_RETRANSMISSIONS {
+            let transaction_id = random_id()?;
+            let packet = connect_request(transaction_id);
+            let answer = self.exchange(&packet, retransmit_timeout(n), |answer| {
+                parse_connect_response(answer, transaction_id)
+            })?;
+            if let Some(connection_id) = answer {
+                self.connection = Some((connection_id, Instant::now()));
+                return Ok(connection_id);
+            }
+        }
+        Err(UdpError::Timeout(MAX_RETRANSMISSIONS + 1))
+    }
+
+    /// Sends `packet` and waits up to `timeout` for its answer, `None` when there is
+    /// none. Answers to other transactions (like a late one to an earlier packet) are
+    /// skipped
+    fn exchange<T>(
+        &self,
+        packet: &[u8],
+        timeout: Duration,
+        parse: impl Fn(&[u8]) -> Result<T, AnnounceError>,
+    ) -> Result<Option<T>, UdpError> {
+        self.socket.send(packet)?;
+
+        let deadline = Instant::now() + timeout;
+        let mut buffer = [0; 2048];
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Ok(None);
+            }
+            self.socket.set_read_timeout(Some(left))?;
+            let received = match self.socket.recv(&mut buffer) {
+                Ok(received) => received,
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Ok(None)
+                }
+                Err(err) => return Err(err.into()),
+            };
+            match parse(&buffer[..received]) {
+                Err(AnnounceError::WrongTransaction { .. }) => continue,
+                answer => return Ok(Some(answer?)),
+            }
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use crate::calculate_hash;
+use crate::fail;
+use crate::progress::Progress;
+use crate::read_torrent_file;
+use crate::torrent::{FileSlice, Info, TextEncoding, TorrentContent};
+
+/// Exit code when some pieces don't match, 1 is for errors like a torrent that can't be read
+const EXIT_CODE_BAD_PIECES: i32 = 3;
+
+/// Exit code when files of the torrent are not there at all, even if other pieces are bad
+const EXIT_CODE_MISSING_FILES: i32 = 4;
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// torrent file path
+    torrent_file_path: PathBuf,
+    /// the downloaded file, or the directory with the files of a multi-file torrent
+    data_path: PathBuf,
+    /// print the byte range of every piece that doesn't match
+    #[arg(long)]
+    list_bad: bool,
+    /// only check these pieces, like "0,5,17-30" (ranges include their end)
+    #[arg(long, value_parser = parse_piece_list)]
+    pieces: Option<PieceList>,
+    /// only check the pieces that cover these bytes of the payload, like "1000..5000"
+    #[arg(long, value_parser = parse_byte_range)]
+    range: Option<Range<u64>>,
+}
+
+/// Piece indices given to `--pieces`
+#[derive(Clone, Debug)]
+struct PieceList(Vec<RangeInclusive<usize>>);
+
+/// Comma separated indices and inclusive ranges of them
+fn parse_piece_list(text: &str) -> Result<PieceList, String> {
+    let index = |index: &str| {
+        index
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format!("{:?} is not a piece index", index))
+    };
+
+    text.split(',')
+        .map(|entry| match entry.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (index(start)?, index(end)?);
+                match start <= end {
+                    true => Ok(start..=end),
+                    false => Err(format!("{:?} ends before it starts", entry)),
+                }
+            }
+            None => index(entry).map(|index| index..=index),
+        })
+        .collect::<Result<_, _>>()
+        .map(PieceList)
+}
+
+/// "start..end" in bytes, the end isn't included
+fn parse_byte_range(text: &str) -> Result<Range<u64>, String> {
+    let Some((start, end)) = text.split_once("..") else {
+        return Err(format!("{:?} is not like \"start..end\"", text));
+    };
+    let offset = |offset: &str| {
+        offset
+            .parse::<u64>()
+            .map_err(|_| format!("{:?} is not a byte offset", offset))
+    };
+
+    let (start, end) = (offset(start)?, offset(end)?);
+    match start < end {
+        true => Ok(start..end),
+        false => Err(format!("{:?} is empty", text)),
+    }
+}
+
+/// Indices of the pieces to check in order, every piece unless `--pieces` or `--range`
+/// is given, out of range ones are an error
+fn selected_pieces(args: &VerifyArgs, info: &Info) -> Vec<usize> {
+    let count = info.get_pieces_count();
+    if args.pieces.is_none() && args.range.is_none() {
+        return (0..count).collect();
+    }
+
+    let mut selected = Vec::new();
+    for range in args.pieces.iter().flat_map(|pieces| &pieces.0) {
+        if *range.end() >= count {
+            fail(&format!(
+                "piece index {} is out of range, the torrent has {} pieces",
+                range.end(),
+                count
+            ));
+        }
+        selected.extend(range.clone());
+    }
+    if let Some(range) = &args.range {
+        let length = info.total_length();
+        if range.end > length {
+            fail(&format!(
+                "byte range {}..{} is past the end of the payload ({} bytes)",
+                range.start, range.end, length
+            ));
+        }
+        // the same pieces `piece` and `map` show for these offsets
+        let geometry = info.piece_geometry();
+        let first = geometry
+            .index_at(range.start)
+            .expect("the range is in the payload");
+        let last = geometry
+            .index_at(range.end - 1)
+            .expect("the range is in the payload");
+        // a torrent with fewer hashes than its length needs can't check its last pieces
+        if last >= count {
+            fail(&format!(
+                "byte range {}..{} needs piece {}, the torrent has hashes for {} pieces",
+                range.start, range.end, last, count
+            ));
+        }
+        selected.extend(first..=last);
+    }
+
+    selected.sort_unstable();
+    selected.dedup();
+    selected
+}
+
+/// Reads pieces from the files on disk, a piece can span several files
+struct PieceReader<'a> {
+    info: &'a Info,
+    data_path: &'a Path,
+    /// slices of each piece, in the order they make up the piece
+    pieces: Vec<Vec<FileSlice>>,
+    /// files are opened when a piece first needs them
+    files: Vec<Option<File>>,
+}
+
+impl<'a> PieceReader<'a> {
+    fn new(info: &'a Info, data_path: &'a Path) -> Self {
+        let mut pieces = vec![Vec::new(); info.get_pieces_count()];
+        let mut slices = info.file_slices();
+        slices.sort_by_key(|slice| (slice.piece_index, slice.piece_offset));
+        for slice in slices {
+            if let Some(piece) = pieces.get_mut(slice.piece_index) {
+                piece.push(slice);
+            }
+        }
+
+        let file_count = info.file_ranges().len();
+        Self {
+            info,
+            data_path,
+            pieces,
+            files: (0..file_count).map(|_| None).collect(),
+        }
+    }
+
+    /// Where a file of the torrent is, the data path itself for a single file torrent.
+    /// A path that could be outside of the data path is an error
+    fn file_path(&self, file_index: usize) -> PathBuf {
+        match self.info.content() {
+            TorrentContent::MultiFile { files } => match files[file_index].relative_path() {
+                Ok(path) => self.data_path.join(path),
+                Err(err) => fail(&format!("refusing to read a file of the torrent, {}", err)),
+            },
+            _ => self.data_path.to_path_buf(),
+        }
+    }
+
+    fn is_padding(&self, file_index: usize) -> bool {
+        matches!(
+            self.info.content(),
+            TorrentContent::MultiFile { files } if files[file_index].is_padding()
+        )
+    }
+
+    /// Reads piece `index` into `buffer`, which is reused so only one piece is in memory
+    fn read_piece(&mut self, index: usize, buffer: &mut Vec<u8>) -> io::Result<()> {
+        buffer.clear();
+        for slice in self.pieces[index].clone() {
+            let start = buffer.len();
+            buffer.resize(start + slice.length as usize, 0);
+            // padding files aren't on disk, their bytes are zeros
+            if self.is_padding(slice.file_index) {
+                continue;
+            }
+
+            if self.files[slice.file_index].is_none() {
+                self.files[slice.file_index] = Some(File::open(self.file_path(slice.file_index))?);
+            }
+            let file = self.files[slice.file_index].as_mut().expect("opened above");
+            file.seek(SeekFrom::Start(slice.file_offset))?;
+            file.read_exact(&mut buffer[start..])?;
+        }
+        Ok(())
+    }
+}
+
+/// How a file of the torrent is on disk, after its pieces were checked
+enum FileStatus {
+    Complete,
+    Missing,
+    /// shorter than the torrent says, the byte it ends at
+    Truncated(u64),
+    /// the bad pieces that have some of the file
+    Corrupt(Vec<usize>),
+}
+
+impl std::fmt::Display for FileStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Complete => write!(f, "complete"),
+            Self::Missing => write!(f, "missing"),
+            Self::Truncated(length) => write!(f, "truncated at byte {}", length),
+            Self::Corrupt(pieces) => {
+                let pieces: Vec<String> = pieces.iter().map(|index| index.to_string()).collect();
+                write!(f, "corrupt pieces: {}", pieces.join(", "))
+            }
+        }
+    }
+}
+
+/// Path and status of every file that has a checked piece (padding files aren't on disk),
+/// empty files are always complete
+fn file_statuses(
+    reader: &PieceReader,
+    encoding: TextEncoding,
+    checked: &[usize],
+    bad_pieces: &[usize],
+) -> Vec<(String, FileStatus)> {
+    let mut statuses = Vec::new();
+    let slices = reader.info.file_slices();
+    let is_checked = |file_index: usize| {
+        slices.iter().any(|slice| {
+            slice.file_index == file_index && checked.binary_search(&slice.piece_index).is_ok()
+        })
+    };
+
+    for (file_index, (path, range)) in reader.info.file_ranges().into_iter().enumerate() {
+        let length = range.end - range.start;
+        if reader.is_padding(file_index) || (length > 0 && !is_checked(file_index)) {
+            continue;
+        }
+
+        let status = match std::fs::metadata(reader.file_path(file_index)) {
+            _ if length == 0 => FileStatus::Complete,
+            Err(_) => FileStatus::Missing,
+            Ok(metadata) if metadata.len() < length => FileStatus::Truncated(metadata.len()),
+            Ok(_) => {
+                let mut corrupt: Vec<usize> = slices
+                    .iter()
+                    .filter(|slice| slice.file_index == file_index)
+                    .map(|slice| slice.piece_index)
+                    .filter(|index| bad_pieces.contains(index))
+                    .collect();
+                corrupt.dedup();
+                match corrupt.is_empty() {
+                    true => FileStatus::Complete,
+                    false => FileStatus::Corrupt(corrupt),
+                }
+            }
+        };
+        statuses.push((encoding.join(&path), status));
+    }
+    statuses
+}
+
+/// The pieces of `selected` whose data under `data_path` doesn't match their hash, in
+/// order. A file that is missing or too short makes its pieces bad, it isn't an error
+pub fn bad_pieces(info: &Info, data_path: &Path, selected: &[usize]) -> Vec<usize> {
+    let piece_hashes = info.get_piece_hashes();
+    let geometry = info.piece_geometry();
+    let selected_length = selected
+        .iter()
+        .filter_map(|&index| geometry.piece(index))
+        .map(|(_, size)| size)
+        .sum();
+    let mut progress = Progress::new("verifying", selected.len(), selected_length);
+    let mut reader = PieceReader::new(info, data_path);
+    let mut buffer = Vec::with_capacity(info.piece_length as usize);
+    let mut bad_pieces = Vec::new();
+
+    for &index in selected {
+        let piece_hash = &piece_hashes[index];
+        let matches = match reader.read_piece(index, &mut buffer) {
+            Ok(()) => calculate_hash(&buffer) == *piece_hash,
+            Err(_) => false,
+        };
+        if !matches {
+            bad_pieces.push(index);
+        }
+        progress.advance(geometry.piece(index).map_or(0, |(_, size)| size));
+    }
+    progress.finish();
+    bad_pieces
+}
+
+/// For command: "verify"
+pub fn execute(args: &VerifyArgs) {
+    let torrent_metadata = read_torrent_file(args.torrent_file_path.clone());
+    let info = &torrent_metadata.info;
+    if info.is_v2_only() {
+        fail("v2 only torrents have no SHA-1 piece hashes to verify");
+    }
+    // checked before anything is read
+    let selected = selected_pieces(args, info);
+    if !args.data_path.exists() {
+        eprintln!("error: {} does not exist", args.data_path.display());
+        std::process::exit(EXIT_CODE_MISSING_FILES);
+    }
+    let bad_pieces = bad_pieces(info, &args.data_path, &selected);
+
+    let count = selected.len();
+    match bad_pieces.is_empty() {
+        true => println!("{}/{} pieces OK", count, count),
+        false => println!(
+            "{}/{} pieces OK, bad pieces: {}",
+            count - bad_pieces.len(),
+            count,
+            bad_pieces
+                .iter()
+                .map(|index| index.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+
+    let encoding = torrent_metadata.text_encoding().unwrap_or_default();
+    let reader = PieceReader::new(info, &args.data_path);
+    let statuses = file_statuses(&reader, encoding, &selected, &bad_pieces);
+    for (path, status) in &statuses {
+        println!("{}: {}", path, status);
+    }
+
+    if args.list_bad {
+        for &index in &bad_pieces {
+            // a torrent with too many hashes warns about it, their pieces are empty
+            let range = info.piece_range(index).unwrap_or_default();
+            println!("piece {}: bytes {}..{}", index, range.start, range.end);
+        }
+    }
+
+    if statuses
+        .iter()
+        .any(|(_, status)| matches!(status, FileStatus::Missing))
+    {
+        std::process::exit(EXIT_CODE_MISSING_FILES);
+    }
+    if !bad_pieces.is_empty() {
+        std::process::exit(EXIT_CODE_BAD_PIECES);
+    }
+}